@@ -0,0 +1,374 @@
+extern crate alloc;
+
+use core::fmt;
+
+use bumpalo::collections::Vec as BumpVec;
+
+use elliptic_curve::zeroize::{
+    Zeroize,
+    Zeroizing
+};
+
+use rand_core::{
+    OsRng,
+    RngCore
+};
+
+use hkdf::Hkdf;
+use sha2::Sha256;
+
+use chacha20poly1305::{
+    ChaCha20Poly1305,
+    Key as CipherKey,
+    Nonce,
+    Tag,
+    aead::AeadInPlace,
+    aead::NewAead
+};
+
+use crate::mem::AllocatorCell;
+use crate::schedule::KeySchedule;
+
+pub const MESSAGE_KEY_LEN: usize = 32;
+pub const HEADER_LEN: usize = 32;
+pub const TAG_LEN: usize = 16;
+pub const NONCE_LEN: usize = 12;
+
+const MESSAGE_KEY_LABEL: &[u8] = b"art message key";
+
+#[derive(Debug, Clone)]
+pub struct CipherError<'a> {
+    pub reason: &'a str
+}
+
+impl<'a> fmt::Display for CipherError<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return write!(f, "Invalid Cipher Operation: {}", self.reason);
+    }
+}
+
+/*
+* Plaintext header sent in front of every ciphertext and bound to it as associated data.
+* Layout: epoch (u64 BE) || sender leaf index (u64 BE) || session (u64 BE) || sequence (u64 BE)
+* `session` is drawn at random by the sending GroupCipher every time it's keyed, see GroupCipher::session.
+*/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MessageHeader {
+    pub epoch: u64,
+    pub sender: usize,
+    pub session: u64,
+    pub sequence: u64
+}
+
+impl MessageHeader {
+    pub fn to_bytes(&self) -> [u8; HEADER_LEN] {
+        let mut bytes: [u8; HEADER_LEN] = [0; HEADER_LEN];
+
+        bytes[0..8].copy_from_slice(&self.epoch.to_be_bytes());
+        bytes[8..16].copy_from_slice(&(self.sender as u64).to_be_bytes());
+        bytes[16..24].copy_from_slice(&self.session.to_be_bytes());
+        bytes[24..32].copy_from_slice(&self.sequence.to_be_bytes());
+
+        return bytes;
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() < HEADER_LEN {
+            return None;
+        }
+
+        let mut word: [u8; 8] = [0; 8];
+
+        word.copy_from_slice(&bytes[0..8]);
+        let epoch: u64 = u64::from_be_bytes(word);
+
+        word.copy_from_slice(&bytes[8..16]);
+        let sender: u64 = u64::from_be_bytes(word);
+
+        word.copy_from_slice(&bytes[16..24]);
+        let session: u64 = u64::from_be_bytes(word);
+
+        word.copy_from_slice(&bytes[24..32]);
+        let sequence: u64 = u64::from_be_bytes(word);
+
+        return Some(Self {
+            epoch: epoch,
+            sender: usize::try_from(sender).ok()?,
+            session: session,
+            sequence: sequence
+        });
+    }
+
+    /*
+    * Sender and sequence are unique per session, and each session of an epoch has its own message key.
+    * Senders that don't fit in the nonce's 32 bits would collide with another sender's nonces, so have none.
+    */
+    fn nonce(&self) -> Option<[u8; NONCE_LEN]> {
+        let mut nonce: [u8; NONCE_LEN] = [0; NONCE_LEN];
+        let sender: u32 = u32::try_from(self.sender).ok()?;
+
+        nonce[0..4].copy_from_slice(&sender.to_be_bytes());
+        nonce[4..12].copy_from_slice(&self.sequence.to_be_bytes());
+
+        return Some(nonce);
+    }
+}
+
+/*
+* Application message layer on top of the KeySchedule.
+* Payloads are sealed with ChaCha20-Poly1305 under a message key derived from the current stage key.
+* Frames are laid out as header || ciphertext || tag. Each sender numbers its messages, and we only accept
+* sequence numbers above the last one seen from that sender's session, so replays within an epoch are rejected.
+* The message key is derived per session, a random value drawn every time the cipher is keyed, so a cipher
+* rebuilt at the same epoch starting its sequence over never reuses a nonce under the same key.
+* Senders must be one of the `leaves` leaf indexes of the tree at the current epoch.
+* Call rekey after every KeySchedule::advance.
+*/
+pub struct GroupCipher<'a> {
+    key: [u8; MESSAGE_KEY_LEN],
+    epoch: u64,
+    index: usize,
+    leaves: usize,
+    session: u64,
+    sequence: u64,
+    // Next acceptable sequence number of every (sender, session) we've authenticated a message from
+    received: BumpVec<'a, (usize, u64, u64)>
+}
+
+// Wipes the message key, nothing can be sealed or opened until the next rekey
+impl<'a> Zeroize for GroupCipher<'a> {
+    fn zeroize(&mut self) {
+        self.key.zeroize();
+        self.epoch = 0;
+        self.received.clear();
+    }
+}
+
+impl<'a> Drop for GroupCipher<'a> {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+impl<'a> GroupCipher<'a> {
+    pub fn new(schedule: &KeySchedule, index: usize, leaves: usize, memory: &'a AllocatorCell) -> Result<Self, CipherError<'a>> {
+        let mut cipher: GroupCipher<'a> = Self {
+            key: [0; MESSAGE_KEY_LEN],
+            epoch: 0,
+            index: index,
+            leaves: 0,
+            session: 0,
+            sequence: 0,
+            received: BumpVec::new_in(memory)
+        };
+
+        cipher.rekey(schedule, leaves)?;
+
+        return Ok(cipher);
+    }
+
+    // `leaves` is the number of leaf slots in the tree at the schedule's epoch, see RatchetTree::get_layer_len
+    pub fn rekey(&mut self, schedule: &KeySchedule, leaves: usize) -> Result<(), CipherError<'a>> {
+        if schedule.epoch() == 0 {
+            return Err(CipherError{
+                reason: "Key schedule has not absorbed a tree key yet"
+            });
+        }
+
+        if u32::try_from(leaves).is_err() {
+            return Err(CipherError{
+                reason: "Group too large for 32 bit sender indexes"
+            });
+        }
+
+        if self.index == 0 || self.index > leaves {
+            return Err(CipherError{
+                reason: "Our leaf index is not part of the group"
+            });
+        }
+
+        let mut session: [u8; 8] = [0; 8];
+
+        if OsRng.try_fill_bytes(&mut session).is_err() {
+            return Err(CipherError{
+                reason: "Unable to draw a session for the message key"
+            });
+        }
+
+        if schedule.derive(MESSAGE_KEY_LABEL, &mut self.key).is_err() {
+            return Err(CipherError{
+                reason: "Unable to derive message key from key schedule"
+            });
+        }
+
+        self.epoch = schedule.epoch();
+        self.leaves = leaves;
+        self.session = u64::from_be_bytes(session);
+        self.sequence = 0;
+        self.received.clear();
+
+        return Ok(());
+    }
+
+    pub fn epoch(&self) -> u64 {
+        return self.epoch;
+    }
+
+    // Session our messages are sent under until the next rekey
+    pub fn session(&self) -> u64 {
+        return self.session;
+    }
+
+    fn session_key<'caller>(&self, session: u64) -> Result<Zeroizing<[u8; MESSAGE_KEY_LEN]>, CipherError<'caller>> {
+        let mut key: Zeroizing<[u8; MESSAGE_KEY_LEN]> = Zeroizing::new([0; MESSAGE_KEY_LEN]);
+
+        let hkdf: Hkdf<Sha256> = match Hkdf::from_prk(&self.key) {
+            Ok(hkdf) => hkdf,
+            Err(_) => {
+                return Err(CipherError{
+                    reason: "Unable to derive session key"
+                });
+            }
+        };
+
+        if hkdf.expand(&session.to_be_bytes(), key.as_mut_slice()).is_err() {
+            return Err(CipherError{
+                reason: "Unable to derive session key"
+            });
+        }
+
+        return Ok(key);
+    }
+
+    pub fn encrypt<'caller>(&mut self, plaintext: &[u8], scratch: &'caller AllocatorCell) -> Result<BumpVec<'caller, u8>, CipherError<'caller>> {
+        if self.epoch == 0 {
+            return Err(CipherError{
+                reason: "Message key has been wiped, rekey first"
+            });
+        }
+
+        let header: MessageHeader = MessageHeader {
+            epoch: self.epoch,
+            sender: self.index,
+            session: self.session,
+            sequence: self.sequence
+        };
+
+        let next: u64 = match self.sequence.checked_add(1) {
+            Some(next) => next,
+            None => {
+                return Err(CipherError{
+                    reason: "Sequence numbers exhausted for this epoch"
+                });
+            }
+        };
+
+        let aad: [u8; HEADER_LEN] = header.to_bytes();
+        let mut frame: BumpVec<'caller, u8> = BumpVec::with_capacity_in(HEADER_LEN + plaintext.len() + TAG_LEN, scratch);
+
+        frame.extend_from_slice(&aad);
+        frame.extend_from_slice(plaintext);
+
+        // Our index was checked on rekey
+        let nonce: [u8; NONCE_LEN] = header.nonce().unwrap();
+        let key: Zeroizing<[u8; MESSAGE_KEY_LEN]> = self.session_key(self.session)?;
+        let cipher: ChaCha20Poly1305 = ChaCha20Poly1305::new(CipherKey::from_slice(key.as_slice()));
+        let tag: Tag = match cipher.encrypt_in_place_detached(Nonce::from_slice(&nonce), &aad, &mut frame[HEADER_LEN..]) {
+            Ok(tag) => tag,
+            Err(_) => {
+                return Err(CipherError{
+                    reason: "Unable to encrypt message"
+                });
+            }
+        };
+
+        frame.extend_from_slice(tag.as_slice());
+        self.sequence = next;
+
+        return Ok(frame);
+    }
+
+    pub fn decrypt<'caller>(&mut self, frame: &[u8], scratch: &'caller AllocatorCell) -> Result<(MessageHeader, BumpVec<'caller, u8>), CipherError<'caller>> {
+        if frame.len() < HEADER_LEN + TAG_LEN {
+            return Err(CipherError{
+                reason: "Message frame too short"
+            });
+        }
+
+        let header: MessageHeader = match MessageHeader::from_bytes(frame) {
+            Some(header) => header,
+            None => {
+                return Err(CipherError{
+                    reason: "Message sender index out of range"
+                });
+            }
+        };
+
+        if header.epoch != self.epoch {
+            return Err(CipherError{
+                reason: "Message is not from the current epoch"
+            });
+        }
+
+        // Checked before anything else touches the header, only authenticated frames grow our state
+        if header.sender == 0 || header.sender > self.leaves {
+            return Err(CipherError{
+                reason: "Message sender is not part of the group"
+            });
+        }
+
+        let tracked: Option<usize> = self.received.iter().position(|(sender, session, _)| *sender == header.sender && *session == header.session);
+
+        if tracked.map_or(false, |position| header.sequence < self.received[position].2) {
+            return Err(CipherError{
+                reason: "Replayed message sequence number"
+            });
+        }
+
+        let next: u64 = match header.sequence.checked_add(1) {
+            Some(next) => next,
+            None => {
+                return Err(CipherError{
+                    reason: "Message sequence number out of range"
+                });
+            }
+        };
+
+        let nonce: [u8; NONCE_LEN] = match header.nonce() {
+            Some(nonce) => nonce,
+            None => {
+                return Err(CipherError{
+                    reason: "Message sender index out of range"
+                });
+            }
+        };
+
+        let body_end: usize = frame.len() - TAG_LEN;
+        let mut plaintext: BumpVec<'caller, u8> = BumpVec::with_capacity_in(body_end - HEADER_LEN, scratch);
+
+        plaintext.extend_from_slice(&frame[HEADER_LEN..body_end]);
+
+        let key: Zeroizing<[u8; MESSAGE_KEY_LEN]> = self.session_key(header.session)?;
+        let cipher: ChaCha20Poly1305 = ChaCha20Poly1305::new(CipherKey::from_slice(key.as_slice()));
+        let result = cipher.decrypt_in_place_detached(
+            Nonce::from_slice(&nonce),
+            &frame[..HEADER_LEN],
+            &mut plaintext,
+            Tag::from_slice(&frame[body_end..])
+        );
+
+        if result.is_err() {
+            return Err(CipherError{
+                reason: "Message failed authentication"
+            });
+        }
+
+        // Only track the sequence once the message has been authenticated
+        match tracked {
+            Some(position) => self.received[position].2 = next,
+            None => self.received.push((header.sender, header.session, next))
+        }
+
+        return Ok((header, plaintext));
+    }
+}
@@ -29,7 +29,8 @@ use k256::{
 
 use elliptic_curve::{
     AffineXCoordinate,
-    Scalar
+    Scalar,
+    zeroize::Zeroize
 };
 
 use crate::errors::ECError;
@@ -47,11 +48,27 @@ pub trait KeyOps {
     fn diffie_hellman<'a>(&self, target: &PublicKey) -> Result<Secret, ECError<'a>>;
 }
 
-#[derive(Copy, Clone)]
+/*
+* Not Copy, so every copy of a secret is one we know about: each is wiped once it's dropped or
+* overwritten. Moves can still leave the bytes behind, wipe the allocator they lived in for those.
+*/
+#[derive(Clone)]
 pub struct Secret {
     scalar: NonZeroScalar
 }
 
+impl Zeroize for Secret {
+    fn zeroize(&mut self) {
+        self.scalar.zeroize();
+    }
+}
+
+impl Drop for Secret {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
 impl Secret {
     pub fn random(rng: impl CryptoRng + RngCore) -> Self {
         Self {
@@ -69,7 +86,9 @@ impl Secret {
         return Err(ECError{reason: "Invalid scalar provided!"});
     }
 
+    // The scalar replaced is wiped first, NonZeroScalar is Copy so assigning alone would leave it behind
     pub fn replace_scalar<'a>(&mut self, scalar: NonZeroScalar) -> Result<(), ECError<'a>> {
+        self.scalar.zeroize();
         self.scalar = scalar;
 
         return Ok(());
@@ -78,6 +97,10 @@ impl Secret {
     pub fn public_key(&self) -> PublicKey {
         return PublicKey::from_secret_scalar(&self.scalar);
     }
+
+    pub fn to_repr(&self) -> FieldBytes {
+        return FieldBytes::from(&self.scalar);
+    }
 }
 
 impl Debug for Secret {
@@ -100,7 +123,7 @@ impl KeyOps for PublicKey {
     }
 }
 
-#[derive(Copy, Clone)]
+#[derive(Clone)]
 pub struct Key {
     pub sk: Option<Secret>,
     pub pk: PublicKey
@@ -112,6 +135,17 @@ impl PartialEq for Key {
     }
 }
 
+// Wipes & drops the secret, the public key is left as is
+impl Zeroize for Key {
+    fn zeroize(&mut self) {
+        if let Some(secret) = self.sk.as_mut() {
+            secret.zeroize();
+        }
+
+        self.sk = None;
+    }
+}
+
 impl Default for Key {
     fn default() -> Self {
         Self {
@@ -150,7 +184,7 @@ impl<'a> Key {
         self.sk = sk;
 
         if self.sk.is_some() {
-            self.pk = self.sk.unwrap().public_key()
+            self.pk = self.sk.as_ref().unwrap().public_key()
         }
 
         return Ok(());
@@ -159,7 +193,7 @@ impl<'a> Key {
     pub fn set_pk(&mut self, pk: PublicKey) -> Result<(), ECError<'a>> {
         self.pk = pk;
 
-        match self.sk {
+        match self.sk.as_ref() {
             Some(secret) => {
                 if secret.public_key() != self.pk {
                     return Err(ECError{
@@ -173,21 +207,25 @@ impl<'a> Key {
         return Ok(());
     }
 
-    pub fn set_secret_scalar(&self, scalar: NonZeroScalar) -> Result<(), ECError<'a>> {
-        match self.sk {
-            Some(mut secret) => {
-                return secret.replace_scalar(scalar);
+    // Swap the scalar of our secret in place, the public key follows it
+    pub fn set_secret_scalar(&mut self, scalar: NonZeroScalar) -> Result<(), ECError<'a>> {
+        match self.sk.as_mut() {
+            Some(secret) => {
+                secret.replace_scalar(scalar)?;
+                self.pk = secret.public_key();
+
+                return Ok(());
             },
             None => {
                 return Err(ECError{
-                    reason: &format_args!("No Secret Key found for key {:?}", self.pk).as_str().unwrap()
+                    reason: "No Secret Key found for key to set the scalar of"
                 });
             }
         }
     }
 
     pub fn diffie_hellman(&self, target: &Key) -> Result<Key, ECError<'a>> {
-        match self.sk {
+        match self.sk.as_ref() {
             Some(secret) => {
                 match secret.diffie_hellman(&target.pk) {
                     Ok(result) => return Ok(result.into()),
@@ -0,0 +1,123 @@
+extern crate alloc;
+
+use core::fmt;
+
+use rand_core::{
+    CryptoRng,
+    RngCore
+};
+
+use k256::ecdsa::{
+    Signature,
+    SigningKey,
+    VerifyingKey,
+    signature::DigestSigner,
+    signature::DigestVerifier
+};
+
+use sha2::{
+    Sha256,
+    Digest
+};
+
+use elliptic_curve::sec1::ToEncodedPoint;
+
+use crate::wire::UpdateMessage;
+
+const UPDATE_SIGNATURE_LABEL: &[u8] = b"art update signature";
+
+#[derive(Debug, Clone)]
+pub struct IdentityError<'a> {
+    pub reason: &'a str
+}
+
+impl<'a> fmt::Display for IdentityError<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return write!(f, "Invalid Identity Operation: {}", self.reason);
+    }
+}
+
+/*
+* Long-term ECDSA identity of a group member, used to authenticate the updates they send.
+* Kept separate from the tree Keys, which are rotated on every update.
+*/
+pub struct IdentityKey {
+    signing_key: SigningKey
+}
+
+/*
+* Digest signed for every update message:
+* SHA256(label || kind || sender (u64 BE) || leaf index (u64 BE) || epoch (u64 BE) || tree hash || compressed path public keys...)
+*/
+pub fn update_digest(message: &UpdateMessage) -> Sha256 {
+    let mut digest: Sha256 = Sha256::new();
+
+    digest.update(UPDATE_SIGNATURE_LABEL);
+    digest.update(&[message.kind.as_u8()]);
+    digest.update(&(message.sender as u64).to_be_bytes());
+    digest.update(&(message.branch.root as u64).to_be_bytes());
+    digest.update(&message.branch.epoch.to_be_bytes());
+    digest.update(&message.tree_hash);
+
+    for key in message.branch.iter() {
+        digest.update(key.pk.to_encoded_point(true).as_bytes());
+    }
+
+    return digest;
+}
+
+pub fn verify_update<'a>(identity: &VerifyingKey, message: &UpdateMessage) -> Result<(), IdentityError<'a>> {
+    let signature: &Signature = match &message.signature {
+        Some(signature) => signature,
+        None => {
+            return Err(IdentityError{
+                reason: "Update message is not signed"
+            });
+        }
+    };
+
+    if identity.verify_digest(update_digest(message), signature).is_err() {
+        return Err(IdentityError{
+            reason: "Update signature is invalid"
+        });
+    }
+
+    return Ok(());
+}
+
+impl IdentityKey {
+    pub fn random(rng: impl CryptoRng + RngCore) -> Self {
+        return Self {
+            signing_key: SigningKey::random(rng)
+        };
+    }
+
+    pub fn from_bytes<'a>(bytes: &[u8]) -> Result<Self, IdentityError<'a>> {
+        match SigningKey::from_bytes(bytes) {
+            Ok(signing_key) => return Ok(Self { signing_key: signing_key }),
+            Err(_) => {
+                return Err(IdentityError{
+                    reason: "Invalid identity key bytes"
+                });
+            }
+        }
+    }
+
+    pub fn verifying_key(&self) -> VerifyingKey {
+        return self.signing_key.verifying_key();
+    }
+
+    pub fn sign_update<'a>(&self, message: &mut UpdateMessage) -> Result<(), IdentityError<'a>> {
+        match self.signing_key.try_sign_digest(update_digest(message)) {
+            Ok(signature) => {
+                message.signature = Some(signature);
+                return Ok(());
+            },
+            Err(_) => {
+                return Err(IdentityError{
+                    reason: "Unable to sign update message"
+                });
+            }
+        }
+    }
+}
@@ -13,6 +13,13 @@ pub mod sync;
 pub mod tree;
 pub mod ecdh;
 pub mod mem;
+pub mod wire;
+pub mod schedule;
+pub mod cipher;
+pub mod identity;
+pub mod roster;
+pub mod member;
+pub mod oplog;
 
 //#[cfg(build)]
 //mod panic;
\ No newline at end of file
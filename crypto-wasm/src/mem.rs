@@ -1,6 +1,7 @@
 use wasm_bindgen::prelude::*;
 
 use core::{
+    any::type_name,
     borrow::Borrow,
     convert::From,
     convert::Into,
@@ -10,11 +11,17 @@ use core::{
     marker::Sized,
     marker::Copy,
     option::Option,
+    cell::RefCell,
+    ptr::NonNull,
+    mem::ManuallyDrop,
     ops::Deref,
     ops::DerefMut,
     ops::Range,
     ops::IndexMut,
     ops::Index,
+    sync::atomic::AtomicBool,
+    sync::atomic::AtomicUsize,
+    sync::atomic::Ordering,
     convert::AsMut,
     convert::AsRef
 };
@@ -40,12 +47,20 @@ use serde_cbor::{
     de::from_mut_slice
 };
 
+use alloc::{
+    boxed::Box,
+    string::String,
+    string::ToString,
+    vec::Vec
+};
+
 use bumpalo::{
     Bump,
-    collections::Vec as BumpVec,
-    collections::CollectionAllocErr
+    collections::Vec as BumpVec
 };
 
+use elliptic_curve::zeroize::Zeroize;
+
 use crate::log::*;
 use crate::sync::Arc;
 
@@ -107,38 +122,252 @@ impl<'a> core::fmt::Display for AllocatorPoolError<'a> {
     }
 }
 
-#[derive(Debug, Clone)]
-
 pub struct AllocatorPool<'a> {
     root_alloc: &'a Bump,
-    allocators: BumpVec<'a, AllocatorCell>,
+    // Grown through a shared pool by register, release & provision
+    inner: RefCell<PoolInner>,
+}
+
+/*
+* Cells are leaked boxes the list only points at, so growing the list never moves (or retags) a cell get_ref
+* handed out. They're only freed through &mut AllocatorPool, once nothing can still borrow them.
+*/
+struct PoolInner {
+    allocators: Vec<NonNull<AllocatorCell>>,
+    slots: Vec<SlotEntry>
+}
+
+// A named allocator, claimed by whichever subsystem registered it first
+#[derive(Debug, Clone)]
+struct SlotEntry {
+    name: String,
+    type_name: &'static str,
+    index: usize,
+    capacity: usize
+}
+
+/*
+* Typed handle to a named slot of an AllocatorPool, see AllocatorPool::register.
+* `capacity` is the number of T the slot was registered for.
+*/
+#[derive(Debug)]
+pub struct AllocatorSlot<T> {
+    index: usize,
+    capacity: usize,
+    marker: PhantomData<T>
+}
+
+impl<T> Clone for AllocatorSlot<T> {
+    fn clone(&self) -> Self {
+        return *self;
+    }
+}
+
+impl<T> Copy for AllocatorSlot<T> {}
+
+impl<T> AllocatorSlot<T> {
+    pub fn index(&self) -> usize {
+        return self.index;
+    }
+
+    pub fn capacity(&self) -> usize {
+        return self.capacity;
+    }
+}
+
+impl<'a> Clone for AllocatorPool<'a> {
+    fn clone(&self) -> Self {
+        let allocators: Vec<Box<AllocatorCell>> = (0..self.len())
+            .filter_map(|index| self.cell(index))
+            .map(|cell| Box::new(cell.clone()))
+            .collect();
+
+        let pool: AllocatorPool<'a> = Self::from_allocators(self.root_alloc, allocators);
+        pool.inner.borrow_mut().slots = self.inner.borrow().slots.clone();
+
+        return pool;
+    }
+}
+
+impl<'a> core::fmt::Debug for AllocatorPool<'a> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        let allocators: Vec<&AllocatorCell> = (0..self.len()).filter_map(|index| self.cell(index)).collect();
+
+        return f.debug_struct("AllocatorPool")
+            .field("root_alloc", &self.root_alloc)
+            .field("allocators", &allocators)
+            .field("slots", &self.inner.borrow().slots)
+            .finish();
+    }
+}
+
+impl<'a> Drop for AllocatorPool<'a> {
+    fn drop(&mut self) {
+        for cell in self.inner.get_mut().allocators.drain(..) {
+            unsafe { drop(Box::from_raw(cell.as_ptr())) };
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct AllocatorCell {
-    allocator: Arc<Bump>
+    allocator: Arc<PoolAllocator>
+}
+
+// Bump behind an AllocatorCell, counting how often it's been reset
+#[derive(Debug)]
+pub struct PoolAllocator {
+    bump: Bump,
+    resets: AtomicUsize,
+    // Secret-bearing allocators are wiped before they're reset or freed
+    secret: AtomicBool
+}
+
+impl PoolAllocator {
+    fn reset(&mut self) {
+        self.wipe();
+        self.bump.reset();
+        self.resets.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn wipe(&mut self) {
+        if !self.secret.load(Ordering::Relaxed) {
+            return;
+        }
+
+        // Nothing can allocate while we hold the allocator mutably
+        unsafe {
+            for (chunk, len) in self.bump.iter_allocated_chunks_raw() {
+                core::slice::from_raw_parts_mut(chunk, len).zeroize();
+            }
+        }
+    }
+}
+
+impl Drop for PoolAllocator {
+    fn drop(&mut self) {
+        self.wipe();
+    }
+}
+
+impl Deref for PoolAllocator {
+    type Target = Bump;
+
+    fn deref(&self) -> &Bump {
+        return &self.bump;
+    }
+}
+
+/*
+* Snapshot of how much memory an allocator (or a whole pool, summed) is using.
+* `references` counts live AllocatorCells, the pool's own included.
+*/
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct AllocatorStats {
+    #[wasm_bindgen(js_name = allocatedBytes)]
+    pub allocated_bytes: usize,
+    #[wasm_bindgen(js_name = chunkCapacity)]
+    pub chunk_capacity: usize,
+    pub resets: usize,
+    pub references: usize
+}
+
+impl AllocatorStats {
+    pub fn add(&mut self, other: &AllocatorStats) {
+        self.allocated_bytes += other.allocated_bytes;
+        self.chunk_capacity += other.chunk_capacity;
+        self.resets += other.resets;
+        self.references += other.references;
+    }
+}
+
+/*
+* Stats of every registered slot of a pool, by name, along with the whole pool summed, see AllocatorPool::report.
+* Slots are looked up by position for JS, in the order they were registered.
+*/
+#[wasm_bindgen]
+#[derive(Debug, Clone, Default)]
+pub struct PoolStats {
+    slots: Vec<(String, AllocatorStats)>,
+    total: AllocatorStats
+}
+
+#[wasm_bindgen]
+impl PoolStats {
+    #[wasm_bindgen(getter)]
+    pub fn total(&self) -> AllocatorStats {
+        return self.total;
+    }
+
+    #[wasm_bindgen(getter, js_name = slotCount)]
+    pub fn slot_count(&self) -> usize {
+        return self.slots.len();
+    }
+
+    #[wasm_bindgen(js_name = slotName)]
+    pub fn slot_name(&self, position: usize) -> Option<String> {
+        return self.slots.get(position).map(|(name, _)| name.clone());
+    }
+
+    #[wasm_bindgen(js_name = slotStats)]
+    pub fn slot_stats(&self, position: usize) -> Option<AllocatorStats> {
+        return self.slots.get(position).map(|(_, stats)| *stats);
+    }
+}
+
+impl PoolStats {
+    pub fn slots(&self) -> &[(String, AllocatorStats)] {
+        return &self.slots;
+    }
+
+    pub fn get(&self, name: &str) -> Option<AllocatorStats> {
+        return self.slots.iter().find(|(slot, _)| slot == name).map(|(_, stats)| *stats);
+    }
 }
 
 impl AllocatorCell {
     pub fn new(root_allocator: &Bump, allocator: Bump) -> Self {
         return Self {
-            allocator: Arc::new_in(root_allocator, allocator)
+            allocator: Arc::new_in(root_allocator, PoolAllocator {
+                bump: allocator,
+                resets: AtomicUsize::new(0),
+                secret: AtomicBool::new(false)
+            })
         }
     }
 
     pub fn get_mut(&mut self) -> Option<&mut Bump> {
         if self.allocator.ref_count() <= 2 {
-            return unsafe { Some(self.allocator.get_mut_unchecked()) };
+            return unsafe { Some(&mut self.allocator.get_mut_unchecked().bump) };
         }
 
         return None;
     }
+
+    // Wipe everything allocated with this cell before it's reset, for memory that held secrets
+    pub fn set_secret(&self, secret: bool) {
+        self.allocator.secret.store(secret, Ordering::Relaxed);
+    }
+
+    pub fn is_secret(&self) -> bool {
+        return self.allocator.secret.load(Ordering::Relaxed);
+    }
+
+    pub fn stats(&self) -> AllocatorStats {
+        return AllocatorStats {
+            allocated_bytes: self.allocator.allocated_bytes(),
+            chunk_capacity: self.allocator.chunk_capacity(),
+            resets: self.allocator.resets.load(Ordering::Relaxed),
+            references: self.allocator.ref_count()
+        };
+    }
 }
 
 impl Deref for AllocatorCell {
-    type Target = Arc<Bump>;
+    type Target = Arc<PoolAllocator>;
 
-    fn deref(&self) -> &Arc<Bump> {
+    fn deref(&self) -> &Arc<PoolAllocator> {
         return &self.allocator;
     }
 }
@@ -156,27 +385,33 @@ impl Drop for AllocatorCell {
 // long lived AllocatorCell References on the root_alloc heap
 impl<'a> AllocatorPool<'a> {
     pub fn new(root_alloc: &'a Bump) -> Self {
-        let allocators: BumpVec<'a, AllocatorCell> = BumpVec::new_in(root_alloc);
+        let allocators: Vec<Box<AllocatorCell>> = Vec::new();
 
-        return AllocatorPool {
-            root_alloc: root_alloc,
-            allocators: allocators
-        }
+        return Self::from_allocators(root_alloc, allocators);
     }
 
     pub fn new_with_init<T>(root_alloc: &'a Bump, num_allocators: usize, length: usize) -> Self {
         assert!(length <= root_alloc.chunk_capacity() / CELL_SIZE_BYTES);
 
-        let mut allocators: BumpVec<'a, AllocatorCell> = BumpVec::with_capacity_in(num_allocators, &root_alloc);
+        let mut allocators: Vec<Box<AllocatorCell>> = Vec::with_capacity(num_allocators);
 
         // fill_with doesn't seem to work for some reason, perhaps because it's capacity is f'ed idk why
         for i in 0..num_allocators {
-            allocators.insert(i, AllocatorCell::new(root_alloc, Bump::with_capacity(size_of::<T>() * length)));
+            allocators.insert(i, Box::new(AllocatorCell::new(root_alloc, Bump::with_capacity(size_of::<T>() * length))));
         }
 
+        return Self::from_allocators(root_alloc, allocators);
+    }
+
+    fn from_allocators(root_alloc: &'a Bump, allocators: Vec<Box<AllocatorCell>>) -> Self {
+        let inner: PoolInner = PoolInner {
+            allocators: allocators.into_iter().map(|cell| NonNull::from(Box::leak(cell))).collect(),
+            slots: Vec::new()
+        };
+
         return AllocatorPool {
             root_alloc: root_alloc,
-            allocators: allocators
+            inner: RefCell::new(inner)
         }
     }
 
@@ -184,37 +419,182 @@ impl<'a> AllocatorPool<'a> {
         return Bump::with_capacity(size_of::<T>() * length);
     }
 
-    pub fn expand(&mut self, length: usize) -> Result<(), CollectionAllocErr> {
-        return self.allocators.try_reserve(length);
+    pub fn expand(&mut self, length: usize) -> Result<(), AllocatorPoolError<'a>> {
+        return self.inner.get_mut().allocators.try_reserve(length).map_err(|_| AllocatorPoolError{
+            reason: "Unable to reserve room for more allocators"
+        });
     }
 
+    // Slots registered past `length` go with their allocators
     pub fn shrink(&mut self, length: usize) {
-        self.allocators.truncate(length);
-        self.allocators.shrink_to_fit();
+        let inner: &mut PoolInner = self.inner.get_mut();
+
+        for cell in inner.allocators.drain(length.min(inner.allocators.len())..) {
+            unsafe { drop(Box::from_raw(cell.as_ptr())) };
+        }
+
+        inner.allocators.shrink_to_fit();
+        inner.slots.retain(|slot| slot.index < length);
     }
 
     pub fn initialize<T>(&mut self, index: usize, length: usize) {
-        self.allocators.insert(index, AllocatorCell::new(self.root_alloc, Bump::with_capacity(size_of::<T>() * length)));
+        let cell: AllocatorCell = AllocatorCell::new(self.root_alloc, Bump::with_capacity(size_of::<T>() * length));
+        let inner: &mut PoolInner = self.inner.get_mut();
+
+        inner.allocators.insert(index, NonNull::from(Box::leak(Box::new(cell))));
+
+        for slot in inner.slots.iter_mut().filter(|slot| slot.index >= index) {
+            slot.index += 1;
+        }
     }
 
-    pub fn get(&self, index: usize) -> AllocatorCell {
-        return self.allocators.get(index).expect(format!("No allocator at specified index {:#}", index).as_str()).clone();
+    pub fn get(&self, index: usize) -> Result<AllocatorCell, AllocatorPoolError<'a>> {
+        return self.get_ref(index).map(|cell| cell.clone());
     }
 
-    pub fn get_ref(&self, index: usize) -> &AllocatorCell {
-        return self.allocators.get(index).expect(format!("No allocator at specified index {:#}", index).as_str());
+    pub fn get_ref(&self, index: usize) -> Result<&AllocatorCell, AllocatorPoolError<'a>> {
+        return self.cell(index).ok_or(AllocatorPoolError{
+            reason: "No allocator found at index"
+        });
     }
 
-    pub fn get_mut(&mut self, index: usize) -> &mut AllocatorCell {
-        return self.allocators.get_mut(index).expect(format!("No allocator at specified index {:#}", index).as_str());
+    pub fn get_mut(&mut self, index: usize) -> Result<&mut AllocatorCell, AllocatorPoolError<'a>> {
+        return self.cell_mut(index).ok_or(AllocatorPoolError{
+            reason: "No allocator found at index"
+        });
+    }
+
+    /*
+    * Register a named slot holding `capacity` elements of T, so several subsystems (a tree, a ring buffer,
+    * the message layer...) can share one pool without agreeing on indices up front.
+    * Registering a name again hands back the same slot, as long as it's for the same type.
+    * A new slot claims the first allocator no other slot has, or provisions one if they're all taken.
+    */
+    pub fn register<T>(&self, name: &str, capacity: usize) -> Result<AllocatorSlot<T>, AllocatorPoolError<'a>> {
+        if let Ok(slot) = self.slot::<T>(name) {
+            return Ok(slot);
+        }
+
+        if self.find_slot(name).is_some() {
+            return Err(AllocatorPoolError{
+                reason: "Slot is registered for a different type"
+            });
+        }
+
+        let index: usize = match self.next_unclaimed() {
+            Some(index) => index,
+            None => self.provision::<T>(capacity)?
+        };
+
+        let entry: SlotEntry = SlotEntry {
+            name: name.to_string(),
+            type_name: type_name::<T>(),
+            index: index,
+            capacity: capacity
+        };
+
+        self.inner.borrow_mut().slots.push(entry);
+
+        return Ok(AllocatorSlot {
+            index: index,
+            capacity: capacity,
+            marker: PhantomData
+        });
+    }
+
+    // Register a slot for memory holding secrets, wiped whenever its allocator is reset
+    pub fn register_secret<T>(&self, name: &str, capacity: usize) -> Result<AllocatorSlot<T>, AllocatorPoolError<'a>> {
+        let slot: AllocatorSlot<T> = self.register::<T>(name, capacity)?;
+
+        self.get_slot_ref(&slot)?.set_secret(true);
+
+        return Ok(slot);
+    }
+
+    /*
+    * Give up a slot, its allocator goes to the next slot registered.
+    * Once no other slot claims the allocator & no clone of its cell is held, it's reset (wiped first if it held
+    * secrets) and stops being secret, so callers drop whatever they still keep in the slot beforehand.
+    */
+    pub fn release(&self, name: &str) -> Result<(), AllocatorPoolError<'a>> {
+        let index: usize = {
+            let slots: &mut Vec<SlotEntry> = &mut self.inner.borrow_mut().slots;
+
+            match slots.iter().position(|slot| slot.name == name) {
+                Some(position) => slots.remove(position).index,
+                None => {
+                    return Err(AllocatorPoolError{
+                        reason: "No slot registered under name"
+                    });
+                }
+            }
+        };
+
+        if self.is_claimed(index) {
+            return Ok(());
+        }
+
+        let cell: Option<NonNull<AllocatorCell>> = self.inner.borrow().allocators.get(index).copied();
+
+        if let Some(cell) = cell {
+            // Only the pool's own reference is left, same as AllocatorCell::drop
+            let cell: &mut AllocatorCell = unsafe { &mut *cell.as_ptr() };
+
+            if cell.allocator.ref_count() <= 1 {
+                unsafe { cell.allocator.get_mut_unchecked().reset() };
+                cell.set_secret(false);
+            }
+        }
+
+        return Ok(());
+    }
+
+    pub fn has_slot(&self, name: &str) -> bool {
+        return self.find_slot(name).is_some();
+    }
+
+    // Look up a slot someone already registered
+    pub fn slot<T>(&self, name: &str) -> Result<AllocatorSlot<T>, AllocatorPoolError<'a>> {
+        match self.find_slot(name) {
+            Some(entry) if entry.type_name == type_name::<T>() => {
+                return Ok(AllocatorSlot {
+                    index: entry.index,
+                    capacity: entry.capacity,
+                    marker: PhantomData
+                });
+            },
+            Some(_) => {
+                return Err(AllocatorPoolError{
+                    reason: "Slot is registered for a different type"
+                });
+            },
+            None => {
+                return Err(AllocatorPoolError{
+                    reason: "No slot registered under name"
+                });
+            }
+        }
+    }
+
+    pub fn get_slot<T>(&self, slot: &AllocatorSlot<T>) -> Result<AllocatorCell, AllocatorPoolError<'a>> {
+        return self.get(slot.index);
+    }
+
+    pub fn get_slot_ref<T>(&self, slot: &AllocatorSlot<T>) -> Result<&AllocatorCell, AllocatorPoolError<'a>> {
+        return self.get_ref(slot.index);
+    }
+
+    // Allocators no slot has claimed yet
+    pub fn unclaimed(&self) -> usize {
+        return (0..self.len()).filter(|index| !self.is_claimed(*index)).count();
     }
 
     pub fn has(&self, index: usize) -> bool {
-        return self.allocators.get(index).is_some();
+        return self.cell(index).is_some();
     }
 
     pub fn clear(&mut self, index: usize) -> Result<(), AllocatorPoolError<'a>> {
-        if let Some(cell) = self.allocators.get_mut(index) {
+        if let Some(cell) = self.cell_mut(index) {
             if let Some(allocator) = cell.allocator.get_mut() {
                 allocator.reset();
                 return Ok(());
@@ -230,16 +610,158 @@ impl<'a> AllocatorPool<'a> {
         });
     }
 
+    /*
+    * Add an unclaimed allocator through a shared pool, for register to hand to the next new slot.
+    * The pool grows to fit it; the list only points at cells, so the ones already handed out never move.
+    * Returns the index of the new allocator.
+    */
+    pub fn provision<T>(&self, length: usize) -> Result<usize, AllocatorPoolError<'a>> {
+        let allocators: &mut Vec<NonNull<AllocatorCell>> = &mut self.inner.borrow_mut().allocators;
+
+        if allocators.try_reserve(1).is_err() {
+            return Err(AllocatorPoolError{
+                reason: "Unable to grow the pool for another allocator"
+            });
+        }
+
+        let cell: AllocatorCell = AllocatorCell::new(self.root_alloc, Bump::with_capacity(size_of::<T>() * length));
+        allocators.push(NonNull::from(Box::leak(Box::new(cell))));
+
+        return Ok(allocators.len() - 1);
+    }
+
+    pub fn stats(&self, index: usize) -> Result<AllocatorStats, AllocatorPoolError<'a>> {
+        return self.get_ref(index).map(|cell| cell.stats());
+    }
+
+    pub fn slot_stats<T>(&self, slot: &AllocatorSlot<T>) -> Result<AllocatorStats, AllocatorPoolError<'a>> {
+        return self.stats(slot.index);
+    }
+
+    // Stats of every registered slot, by name. Taken up front, so `f` is free to register more slots
+    pub fn for_each_slot<F: FnMut(&str, AllocatorStats)>(&self, mut f: F) {
+        let mut stats: Vec<(String, AllocatorStats)> = Vec::new();
+
+        for slot in self.inner.borrow().slots.iter() {
+            if let Some(cell) = self.cell(slot.index) {
+                stats.push((slot.name.clone(), cell.stats()));
+            }
+        }
+
+        for (name, stats) in stats.iter() {
+            f(name.as_str(), *stats);
+        }
+    }
+
+    // Stats of every registered slot & the pool's total in one go, for graphing memory growth
+    pub fn report(&self) -> PoolStats {
+        let mut slots: Vec<(String, AllocatorStats)> = Vec::new();
+
+        self.for_each_slot(|name, stats| slots.push((name.to_string(), stats)));
+
+        return PoolStats {
+            slots: slots,
+            total: self.total_stats()
+        };
+    }
+
+    // Every allocator in the pool summed, whether a slot claimed it or not
+    pub fn total_stats(&self) -> AllocatorStats {
+        let mut total: AllocatorStats = AllocatorStats::default();
+
+        for cell in (0..self.len()).filter_map(|index| self.cell(index)) {
+            total.add(&cell.stats());
+        }
+
+        return total;
+    }
+
+    // Bytes taken on root_alloc by every AllocatorCell, which only ever grows
+    pub fn root_bytes(&self) -> usize {
+        return self.root_alloc.allocated_bytes();
+    }
+
     pub fn len(&self) -> usize {
-        return self.allocators.len();
+        return self.inner.borrow().allocators.len();
     }
 
     pub fn capacity(&self) -> usize {
-        return self.allocators.capacity();
+        return self.inner.borrow().allocators.capacity();
+    }
+
+    fn find_slot(&self, name: &str) -> Option<SlotEntry> {
+        return self.inner.borrow().slots.iter().find(|slot| slot.name == name).cloned();
+    }
+
+    fn is_claimed(&self, index: usize) -> bool {
+        return self.inner.borrow().slots.iter().any(|slot| slot.index == index);
+    }
+
+    fn next_unclaimed(&self) -> Option<usize> {
+        return (0..self.len()).find(|index| !self.is_claimed(*index));
+    }
+
+    // Cells are only freed through &mut self, so the reference can't outlive the cell
+    fn cell(&self, index: usize) -> Option<&AllocatorCell> {
+        return self.inner.borrow().allocators.get(index).map(|cell| unsafe { &*cell.as_ptr() });
+    }
+
+    fn cell_mut(&mut self, index: usize) -> Option<&mut AllocatorCell> {
+        return self.inner.get_mut().allocators.get(index).map(|cell| unsafe { &mut *cell.as_ptr() });
+    }
+}
+
+/*
+* An AllocatorPool owning its root allocator, so JS can hold on to one and graph memory growth through stats.
+* The root is leaked for as long as the pool lives & freed right after it, see Drop.
+*/
+#[wasm_bindgen]
+pub struct MemoryPool {
+    pool: ManuallyDrop<AllocatorPool<'static>>,
+    root: NonNull<Bump>
+}
+
+#[wasm_bindgen]
+impl MemoryPool {
+    // `allocators` provisioned up front with room for `bytes` each, the pool grows past them as slots are registered
+    #[wasm_bindgen(constructor)]
+    pub fn new(allocators: usize, bytes: usize) -> Result<MemoryPool, JsValue> {
+        let root: NonNull<Bump> = NonNull::from(Box::leak(Box::new(Bump::new())));
+        let mut memory: MemoryPool = MemoryPool {
+            pool: ManuallyDrop::new(AllocatorPool::new(unsafe { &*root.as_ptr() })),
+            root: root
+        };
+
+        memory.pool.expand(allocators).map_err(|error| JsValue::from_str(error.reason))?;
+
+        for _ in 0..allocators {
+            memory.pool.provision::<u8>(bytes).map_err(|error| JsValue::from_str(error.reason))?;
+        }
+
+        return Ok(memory);
+    }
+
+    pub fn stats(&self) -> PoolStats {
+        return self.pool.report();
+    }
+}
+
+impl MemoryPool {
+    pub fn pool(&self) -> &AllocatorPool<'static> {
+        return &self.pool;
+    }
+}
+
+impl Drop for MemoryPool {
+    fn drop(&mut self) {
+        // Every cell lives on the root, so they all go before it does
+        unsafe {
+            ManuallyDrop::drop(&mut self.pool);
+            drop(Box::from_raw(self.root.as_ptr()));
+        }
     }
 }
 
-#[derive(Debug, Clone)]
 pub struct RingBufferError<'a> {
     pub reason: &'a str
 }
@@ -285,6 +807,11 @@ impl<T: ?Sized + Serialize + DeserializeOwned> SharedRingBuffer<T> {
         };
     }
 
+    // Scratch memory used to (de)serialize values, the SharedArrayBuffer itself is fixed at `capacity` slices
+    pub fn stats(&self) -> AllocatorStats {
+        return self.scratch.stats();
+    }
+
     // Read a value at an index without consuming it
     pub fn read(&self, index: usize) -> Option<T> {
         let local_scratch: AllocatorCell = self.scratch.clone();
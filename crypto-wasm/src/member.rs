@@ -0,0 +1,309 @@
+extern crate alloc;
+
+use bumpalo::collections::Vec as BumpVec;
+
+use k256::PublicKey;
+
+use crate::ecdh::Key;
+use crate::mem::AllocatorCell;
+use crate::tree::{
+    RatchetTree,
+    RatchetBranch,
+    RatchetError,
+    RatchetErrorCause,
+    PathNode,
+    get_next_index,
+    get_sibling_index,
+    height_of,
+    ratchet_node
+};
+
+/*
+* A single member's view of a RatchetTree: the secrets on our own leaf-to-root path and the public
+* keys of our copath, nothing else. Laid out per height the same way as the tree's layers, so
+* path[h] sits at (h, index >> h) and copath[h] is its sibling.
+* Anything that would need a secret off our own path is refused with MISSING_SECRET, we only ever
+* ratchet our own leaf and take other members' updates as public branches.
+* Removed leaves are tracked like the tree's orphans, so we shrink at the same epoch the tree does.
+*/
+pub struct MemberTree<'a> {
+    index: usize,
+    size: usize,
+    epoch: u64,
+    path: RatchetBranch<'a>,
+    copath: BumpVec<'a, Key>,
+    orphans: BumpVec<'a, usize>,
+    pub tombstone: Option<Key>
+}
+
+impl<'a> MemberTree<'a> {
+    // Cut our view out of a full tree, secrets off our path are never copied
+    pub fn from_tree(tree: &RatchetTree, index: usize, memory: &'a AllocatorCell) -> Result<Self, RatchetError<'a>> {
+        let direct_path: BumpVec<'a, PathNode> = tree.direct_path(index, memory)?;
+        let copath: BumpVec<'a, PathNode> = tree.copath(index, memory)?;
+
+        let mut member: MemberTree<'a> = Self {
+            index: index,
+            size: tree.get_layer_len(0) - 1,
+            epoch: tree.epoch(),
+            path: RatchetBranch::new(memory, index),
+            copath: BumpVec::with_capacity_in(copath.len(), memory),
+            orphans: BumpVec::with_capacity_in(tree.get_orphans().len(), memory),
+            tombstone: tree.tombstone.clone()
+        };
+
+        member.orphans.extend_from_slice(tree.get_orphans());
+
+        for node in direct_path.iter() {
+            match tree.get(node.height, node.index) {
+                Some(key) if key.sk.is_some() => member.path.add_node(key.clone()),
+                _ => {
+                    return Err(RatchetError{
+                        description: "No secret held for node on our own path",
+                        cause: RatchetErrorCause::MISSING_SECRET,
+                        index: node.index,
+                        height: node.height
+                    });
+                }
+            }
+        }
+
+        for node in copath.iter() {
+            let pk: PublicKey = node.pk.unwrap_or(member.tombstone.as_ref().unwrap().pk);
+            member.copath.push(Key::new(pk, None));
+        }
+
+        return Ok(member);
+    }
+
+    pub fn get_index(&self) -> usize {
+        return self.index;
+    }
+
+    pub fn height(&self) -> usize {
+        return height_of(self.size);
+    }
+
+    pub fn epoch(&self) -> u64 {
+        return self.epoch;
+    }
+
+    pub fn get_root(&self) -> Option<&Key> {
+        return self.path.get_last();
+    }
+
+    pub fn get_path(&self) -> &RatchetBranch<'a> {
+        return &self.path;
+    }
+
+    pub fn get_copath(&self) -> &[Key] {
+        return &self.copath;
+    }
+
+    // Only our own leaf can be ratcheted, any other leaf needs secrets we don't hold
+    pub fn ratchet<'caller>(&self, index: usize, key: &Key, scratch: &'caller AllocatorCell) -> Result<RatchetBranch<'caller>, RatchetError<'caller>> {
+        if index != self.index {
+            return Err(RatchetError{
+                description: "Ratcheting another member's leaf needs secrets we do not hold",
+                cause: RatchetErrorCause::MISSING_SECRET,
+                index: index,
+                height: 0
+            });
+        }
+
+        return self.ratchet_with(key, &self.copath, scratch);
+    }
+
+    pub fn remove<'caller>(&self, index: usize, scratch: &'caller AllocatorCell) -> Result<RatchetBranch<'caller>, RatchetError<'caller>> {
+        return self.ratchet(index, self.tombstone.as_ref().unwrap(), scratch);
+    }
+
+    fn ratchet_with<'caller>(&self, key: &Key, copath: &[Key], scratch: &'caller AllocatorCell) -> Result<RatchetBranch<'caller>, RatchetError<'caller>> {
+        let mut branch: RatchetBranch<'caller> = RatchetBranch::new(scratch, self.index);
+        let mut position: usize = self.index;
+
+        branch.epoch = self.epoch;
+        branch.add_node(key.clone());
+
+        for (height, sibling) in copath.iter().enumerate() {
+            match ratchet_node(branch.get_last(), Some(sibling), self.tombstone.as_ref()) {
+                Ok(key) => branch.add_node(key),
+                Err(_) => {
+                    return Err(RatchetError{
+                        description: "Diffie hellman needs a secret off our own path",
+                        cause: RatchetErrorCause::MISSING_SECRET,
+                        index: get_next_index(position),
+                        height: height + 1
+                    });
+                }
+            }
+
+            position = get_next_index(position);
+        }
+
+        return Ok(branch);
+    }
+
+    // Commit a branch produced by our own ratchet
+    pub fn commit(&mut self, branch: &RatchetBranch) -> Result<&Key, RatchetError> {
+        if branch.root != self.index {
+            return Err(RatchetError{
+                description: "Branch is not rooted at our leaf, apply it as an update instead",
+                cause: RatchetErrorCause::INVALID_INDEX,
+                index: branch.root,
+                height: 0
+            });
+        }
+
+        if branch.epoch != self.epoch {
+            return Err(RatchetError{
+                description: "Branch was computed against a different epoch of the tree",
+                cause: RatchetErrorCause::STALE_EPOCH,
+                index: branch.root,
+                height: 0
+            });
+        }
+
+        if branch.len() != self.height() + 1 {
+            return Err(RatchetError{
+                description: "Branch & Tree height mismatch: Committing branch would result in desynced state",
+                cause: RatchetErrorCause::INVALID_BRANCH,
+                index: branch.root,
+                height: branch.len()
+            });
+        }
+
+        if let Some(height) = branch.iter().position(|key| key.sk.is_none()) {
+            return Err(RatchetError{
+                description: "No secret held for node on our own path",
+                cause: RatchetErrorCause::MISSING_SECRET,
+                index: branch.root,
+                height: height
+            });
+        }
+
+        self.path.clear();
+
+        for key in branch.iter() {
+            self.path.add_node(key.clone());
+        }
+
+        self.track_orphan(branch.root, branch.get_node(0));
+        self.shrink();
+        self.epoch += 1;
+
+        return Ok(self.path.get_last().unwrap());
+    }
+
+    /*
+    * Apply another member's public update branch. Wherever their path crosses our copath we take their
+    * public key, then re-derive our own path. Nodes both paths share must agree with what we derive,
+    * otherwise the update is rejected before anything is written. Inserts may grow the tree by a layer.
+    */
+    pub fn apply_update<'caller>(&mut self, update: &RatchetBranch, scratch: &'caller AllocatorCell) -> Result<&Key, RatchetError<'caller>> {
+        if update.root == self.index || update.root == 0 || update.root > self.size + 1 {
+            return Err(RatchetError{
+                description: "Update is not for another member's leaf in this tree",
+                cause: RatchetErrorCause::INVALID_INDEX,
+                index: update.root,
+                height: 0
+            });
+        }
+
+        if update.epoch != self.epoch {
+            return Err(RatchetError{
+                description: "Remote update was computed against a different epoch of the tree",
+                cause: RatchetErrorCause::STALE_EPOCH,
+                index: update.root,
+                height: 0
+            });
+        }
+
+        let size: usize = core::cmp::max(self.size, update.root);
+        let height: usize = height_of(size);
+
+        if update.len() != height + 1 {
+            return Err(RatchetError{
+                description: "Branch & Tree height mismatch: Committing branch would result in desynced state",
+                cause: RatchetErrorCause::INVALID_BRANCH,
+                index: update.root,
+                height: update.len()
+            });
+        }
+
+        let mut copath: BumpVec<'caller, Key> = BumpVec::new_in(scratch);
+        let mut position: usize = self.index;
+
+        for h in 0..height {
+            let sibling: Key = match update.get_at(h, get_sibling_index(position)) {
+                Some(key) => Key::new(key.pk, None),
+                None => self.copath.get(h).or(self.tombstone.as_ref()).unwrap().clone()
+            };
+
+            copath.push(sibling);
+            position = get_next_index(position);
+        }
+
+        let path: RatchetBranch<'caller> = self.ratchet_with(self.path.get_node(0).unwrap(), &copath, scratch)?;
+        let mut position: usize = self.index;
+
+        for (h, key) in path.iter().enumerate() {
+            if let Some(remote) = update.get_at(h, position) {
+                if remote != key {
+                    return Err(RatchetError{
+                        description: "Remote update does not agree with the path derived from our leaf",
+                        cause: RatchetErrorCause::INVALID_BRANCH,
+                        index: position,
+                        height: h
+                    });
+                }
+            }
+
+            position = get_next_index(position);
+        }
+
+        self.size = size;
+        self.epoch += 1;
+        self.copath.clear();
+        self.path.clear();
+
+        for key in copath.iter() {
+            self.copath.push(key.clone());
+        }
+
+        for key in path.iter() {
+            self.path.add_node(key.clone());
+        }
+
+        self.track_orphan(update.root, update.get_node(0));
+        self.shrink();
+
+        return Ok(self.path.get_last().unwrap());
+    }
+
+    fn track_orphan(&mut self, index: usize, leaf: Option<&Key>) {
+        let position: Option<usize> = self.orphans.iter().position(|orphan| *orphan == index);
+
+        if leaf == self.tombstone.as_ref() {
+            if position.is_none() {
+                self.orphans.push(index);
+            }
+        } else if let Some(position) = position {
+            self.orphans.remove(position);
+        }
+    }
+
+    // Drop trailing removed leaves the same way RatchetTree does, the root passes straight down
+    fn shrink(&mut self) {
+        while self.size > 0 && self.orphans.contains(&self.size) {
+            self.size -= 1;
+        }
+
+        let size: usize = self.size;
+        let height: usize = self.height();
+
+        self.orphans.retain(|orphan| *orphan <= size);
+        self.copath.truncate(height);
+        self.path.nodes.truncate(height + 1);
+    }
+}
@@ -0,0 +1,314 @@
+extern crate alloc;
+
+use bumpalo::collections::Vec as BumpVec;
+
+use crate::ecdh::Key;
+use crate::mem::{
+    AllocatorPool,
+    AllocatorCell
+};
+use crate::tree::{
+    RatchetTree,
+    RatchetBranch,
+    RatchetError,
+    RatchetErrorCause,
+    TREE_HASH_LEN
+};
+use crate::roster::{
+    Member,
+    Roster
+};
+use crate::wire::{
+    TreeSnapshot,
+    UpdateKind,
+    UpdateMessage
+};
+
+/*
+* Append-only log of every operation committed to a RatchetTree, oldest first, on top of an optional
+* checkpoint. Each entry is an unsigned UpdateMessage: the kind of operation, the public path with the
+* leaf index & the epoch it was committed at, and the tree hash of the tree it was committed to.
+* Along with each INSERT & REMOVE entry goes the member it binds or unbinds, and checkpoints carry the
+* roster, so replay rebuilds member bindings as well as the tree.
+* Entries sharing an epoch were committed together with commit_batch.
+* Operations are committed through the log, which commits them through the group's Roster in turn.
+*/
+pub struct OperationLog<'a> {
+    memory: &'a AllocatorCell,
+    checkpoint: Option<TreeSnapshot<'a>>,
+    entries: BumpVec<'a, UpdateMessage<'a>>,
+    // Member bound or unbound by each entry, by entry, None for updates
+    members: BumpVec<'a, Option<Member>>
+}
+
+impl<'a> OperationLog<'a> {
+    pub fn new(memory: &'a AllocatorCell) -> Self {
+        return Self {
+            memory: memory,
+            checkpoint: None,
+            entries: BumpVec::new_in(memory),
+            members: BumpVec::new_in(memory)
+        };
+    }
+
+    // Epoch of the tree the log currently leads to
+    pub fn epoch(&self) -> u64 {
+        match self.entries.last() {
+            Some(entry) => return entry.branch.epoch + 1,
+            None => return self.checkpoint.as_ref().map_or(0, |checkpoint| checkpoint.epoch)
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        return self.entries.len();
+    }
+
+    pub fn entries(&self) -> &[UpdateMessage<'a>] {
+        return &self.entries;
+    }
+
+    pub fn get_checkpoint(&self) -> Option<&TreeSnapshot<'a>> {
+        return self.checkpoint.as_ref();
+    }
+
+    // Member bound by the INSERT or unbound by the REMOVE at `index` in entries(), None for updates
+    pub fn get_member(&self, index: usize) -> Option<&Member> {
+        match self.members.get(index) {
+            Some(member) => return member.as_ref(),
+            None => return None
+        }
+    }
+
+    // See Roster::commit, `member` names the member joining through an insert
+    pub fn commit<'t, 'tree, 'caller>(&mut self, tree: &'t mut RatchetTree<'tree>, roster: &mut Roster, branch: &RatchetBranch, member: Option<Member>, memory: &'tree AllocatorPool) -> Result<&'t Key, RatchetError<'caller>> {
+        self.check_epoch(tree)?;
+
+        let kind: UpdateKind = UpdateKind::of(tree, branch);
+        let binding: Option<Member> = binding_of(kind, roster, branch.root, member);
+        let tree_hash: [u8; TREE_HASH_LEN] = tree.tree_hash();
+        let root: &'t Key = roster.commit(tree, branch, member, memory)?;
+
+        self.append(kind, tree_hash, branch, binding);
+
+        return Ok(root);
+    }
+
+    pub fn apply_update<'t, 'tree, 'caller>(&mut self, tree: &'t mut RatchetTree<'tree>, roster: &mut Roster, update: &RatchetBranch, member: Option<Member>, index: usize, memory: &'tree AllocatorPool, scratch: &'caller AllocatorCell) -> Result<&'t Key, RatchetError<'caller>> {
+        self.check_epoch(tree)?;
+
+        let kind: UpdateKind = UpdateKind::of(tree, update);
+        let binding: Option<Member> = binding_of(kind, roster, update.root, member);
+        let tree_hash: [u8; TREE_HASH_LEN] = tree.tree_hash();
+        let root: &'t Key = roster.apply_update(tree, update, member, index, memory, scratch)?;
+
+        self.append(kind, tree_hash, update, binding);
+
+        return Ok(root);
+    }
+
+    // Logs the merged update, one entry per branch in leaf order, see Roster::commit_batch
+    pub fn commit_batch<'tree, 'caller>(&mut self, tree: &mut RatchetTree<'tree>, roster: &mut Roster, branches: &[RatchetBranch], members: &[Option<Member>], memory: &'tree AllocatorPool, scratch: &'caller AllocatorCell) -> Result<BumpVec<'caller, RatchetBranch<'caller>>, RatchetError<'caller>> {
+        self.check_epoch(tree)?;
+
+        let mut kinds: BumpVec<'caller, (usize, UpdateKind, Option<Member>)> = BumpVec::with_capacity_in(branches.len(), scratch);
+
+        for (branch, member) in branches.iter().zip(members.iter()) {
+            let kind: UpdateKind = UpdateKind::of(tree, branch);
+
+            kinds.push((branch.root, kind, binding_of(kind, roster, branch.root, *member)));
+        }
+
+        kinds.sort_by_key(|(root, _, _)| *root);
+
+        let tree_hash: [u8; TREE_HASH_LEN] = tree.tree_hash();
+        let update: BumpVec<'caller, RatchetBranch<'caller>> = roster.commit_batch(tree, branches, members, memory, scratch)?;
+
+        for (branch, (_, kind, binding)) in update.iter().zip(kinds.iter()) {
+            self.append(*kind, tree_hash, branch, *binding);
+        }
+
+        return Ok(update);
+    }
+
+    // Logs a merged update received from another member, see Roster::apply_batch
+    pub fn apply_batch<'t, 'tree, 'caller>(&mut self, tree: &'t mut RatchetTree<'tree>, roster: &mut Roster, update: &[RatchetBranch], members: &[Option<Member>], index: usize, memory: &'tree AllocatorPool, scratch: &'caller AllocatorCell) -> Result<&'t Key, RatchetError<'caller>> {
+        self.check_epoch(tree)?;
+
+        let mut kinds: BumpVec<'caller, (usize, UpdateKind, Option<Member>)> = BumpVec::with_capacity_in(update.len(), scratch);
+
+        for (i, (branch, member)) in update.iter().zip(members.iter()).enumerate() {
+            let kind: UpdateKind = UpdateKind::of(tree, branch);
+
+            kinds.push((i, kind, binding_of(kind, roster, branch.root, *member)));
+        }
+
+        kinds.sort_by_key(|(i, _, _)| update[*i].root);
+
+        let tree_hash: [u8; TREE_HASH_LEN] = tree.tree_hash();
+        let root: &'t Key = roster.apply_batch(tree, update, members, index, memory, scratch)?;
+
+        for (i, kind, binding) in kinds.iter() {
+            self.append(*kind, tree_hash, &update[*i], *binding);
+        }
+
+        return Ok(root);
+    }
+
+    /*
+    * Roll the tree & roster back `n` commits & drop the entries for them, see Roster::rollback.
+    * Commits compacted into the checkpoint can't be rolled back through the log.
+    */
+    pub fn rollback<'caller>(&mut self, tree: &mut RatchetTree, roster: &mut Roster, n: usize) -> Result<u64, RatchetError<'caller>> {
+        self.check_epoch(tree)?;
+
+        let base: u64 = self.checkpoint.as_ref().map_or(0, |checkpoint| checkpoint.epoch);
+
+        if tree.epoch() < base + n as u64 {
+            return Err(RatchetError{
+                description: "Cannot roll back past the log's checkpoint",
+                cause: RatchetErrorCause::JOURNAL_EXHAUSTED,
+                index: 0,
+                height: n
+            });
+        }
+
+        let epoch: u64 = roster.rollback(tree, n)?;
+        let kept: usize = self.entries.iter().take_while(|entry| entry.branch.epoch < epoch).count();
+
+        self.entries.truncate(kept);
+        self.members.truncate(kept);
+
+        return Ok(epoch);
+    }
+
+    /*
+    * Compact every entry into a public snapshot of `tree` & its `roster`, which must be the tree the
+    * log leads to. Replay starts from the latest checkpoint.
+    */
+    pub fn checkpoint<'caller>(&mut self, tree: &RatchetTree, roster: &Roster) -> Result<(), RatchetError<'caller>> {
+        self.check_epoch(tree)?;
+
+        let mut checkpoint: TreeSnapshot<'a> = tree.snapshot(false, self.memory);
+        roster.snapshot(&mut checkpoint);
+
+        self.checkpoint = Some(checkpoint);
+        self.entries.clear();
+        self.members.clear();
+
+        return Ok(());
+    }
+
+    /*
+    * Rebuild the tree & its roster from the checkpoint (or an empty tree) by re-committing every entry
+    * in order, through the roster. Entries sharing an epoch were merged when they were committed, and are
+    * written back as they are rather than merged again, see Roster::apply_merged. Every entry's tree hash
+    * is checked before it's applied, so a replay that diverges stops with INVALID_TREE_HASH, and every
+    * REMOVE must unbind the member it logged, INVALID_MEMBER otherwise.
+    * The rebuilt tree is public only, it holds the same nodes and root public key as the logged tree.
+    */
+    pub fn replay<'tree: 'caller, 'r, 'caller>(&self, memory: &'tree AllocatorPool, roster_memory: &'r AllocatorCell, scratch: &'caller AllocatorCell) -> Result<(RatchetTree<'tree>, Roster<'r>), RatchetError<'caller>> {
+        let mut tree: RatchetTree<'tree> = match self.checkpoint.as_ref() {
+            Some(checkpoint) => RatchetTree::restore(memory, checkpoint)?,
+            None => RatchetTree::new(memory)?
+        };
+
+        let mut roster: Roster<'r> = match self.checkpoint.as_ref() {
+            Some(checkpoint) => Roster::restore(checkpoint, &tree, roster_memory)?,
+            None => Roster::new(roster_memory)
+        };
+
+        let mut start: usize = 0;
+
+        while start < self.entries.len() {
+            let first: &UpdateMessage = &self.entries[start];
+            let end: usize = start + self.entries[start..].iter().take_while(|entry| entry.branch.epoch == first.branch.epoch).count();
+
+            if tree.tree_hash() != first.tree_hash {
+                return Err(RatchetError{
+                    description: "Replayed tree diverged from the logged tree",
+                    cause: RatchetErrorCause::INVALID_TREE_HASH,
+                    index: first.branch.root,
+                    height: 0
+                });
+            }
+
+            let mut joining: BumpVec<'caller, Option<Member>> = BumpVec::with_capacity_in(end - start, scratch);
+
+            for i in start..end {
+                let entry: &UpdateMessage = &self.entries[i];
+
+                if entry.kind == UpdateKind::REMOVE && roster.get(entry.branch.root) != self.members[i].as_ref() {
+                    return Err(RatchetError{
+                        description: "Replayed removal unbinds a different member than the one logged",
+                        cause: RatchetErrorCause::INVALID_MEMBER,
+                        index: entry.branch.root,
+                        height: 0
+                    });
+                }
+
+                joining.push(if entry.kind == UpdateKind::INSERT { self.members[i] } else { None });
+            }
+
+            if end - start == 1 {
+                roster.commit(&mut tree, &first.branch, joining[0], memory)?;
+            } else {
+                let mut batch: BumpVec<'caller, RatchetBranch<'caller>> = BumpVec::with_capacity_in(end - start, scratch);
+
+                for entry in self.entries[start..end].iter() {
+                    batch.push(self.copy_branch(&entry.branch, scratch));
+                }
+
+                roster.apply_merged(&mut tree, &batch, &joining, memory, scratch)?;
+            }
+
+            start = end;
+        }
+
+        return Ok((tree, roster));
+    }
+
+    fn check_epoch<'caller>(&self, tree: &RatchetTree) -> Result<(), RatchetError<'caller>> {
+        if tree.epoch() != self.epoch() {
+            return Err(RatchetError{
+                description: "Tree is not at the epoch the log leads to",
+                cause: RatchetErrorCause::STALE_EPOCH,
+                index: 0,
+                height: 0
+            });
+        }
+
+        return Ok(());
+    }
+
+    fn append(&mut self, kind: UpdateKind, tree_hash: [u8; TREE_HASH_LEN], branch: &RatchetBranch, member: Option<Member>) {
+        let public: RatchetBranch<'a> = self.copy_branch(branch, self.memory);
+
+        // Entries are never signed, the leaf changed stands in for whoever sent it
+        let sender: usize = public.root;
+
+        self.entries.push(UpdateMessage::new(kind, sender, tree_hash, public));
+        self.members.push(member);
+    }
+
+    // Public keys only, secrets never make it into the log
+    fn copy_branch<'b>(&self, branch: &RatchetBranch, memory: &'b AllocatorCell) -> RatchetBranch<'b> {
+        let mut public: RatchetBranch<'b> = RatchetBranch::new(memory, branch.root);
+
+        public.epoch = branch.epoch;
+
+        for key in branch.iter() {
+            public.add_node(Key::from(key.pk));
+        }
+
+        return public;
+    }
+}
+
+// Member an entry of `kind` binds (the one joining) or unbinds (the one sitting in `index`)
+fn binding_of(kind: UpdateKind, roster: &Roster, index: usize, member: Option<Member>) -> Option<Member> {
+    match kind {
+        UpdateKind::INSERT => return member,
+        UpdateKind::REMOVE => return roster.get(index).copied(),
+        UpdateKind::UPDATE => return None
+    }
+}
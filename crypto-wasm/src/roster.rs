@@ -0,0 +1,441 @@
+extern crate alloc;
+
+use bumpalo::collections::Vec as BumpVec;
+
+use k256::ecdsa::VerifyingKey;
+
+use crate::ecdh::Key;
+use crate::mem::{
+    AllocatorPool,
+    AllocatorCell
+};
+use crate::tree::{
+    RatchetTree,
+    RatchetBranch,
+    RatchetError,
+    RatchetErrorCause,
+    ForkResolution,
+    TreeChanges
+};
+use crate::wire::TreeSnapshot;
+
+pub type MemberId = u64;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Member {
+    pub id: MemberId,
+    pub identity: VerifyingKey
+}
+
+/*
+* Binds each occupied leaf of a RatchetTree to the member sitting in it.
+* Indexed by leaf index, same as layer 0 of the tree, so index 0 is never bound.
+* Branches should be committed through the roster rather than the tree directly, so inserts, removals
+* and orphan re-use are reflected on both at the same time:
+* - Branches into an empty or tombstoned leaf are inserts and must name the joining Member
+* - Branches whose leaf is the tombstone are removals and unbind the leaf
+* - Anything else is an update of the sitting member
+* The same goes for batches, rollbacks, fork resolution & promotion, which the roster wraps as well.
+* Every binding it changes is kept for as long as the tree journals the commit that changed it, so rolling
+* the tree back rolls the roster back with it.
+*/
+pub struct Roster<'a> {
+    members: BumpVec<'a, Option<Member>>,
+    // (epoch of the commit, leaf index, binding before the commit), oldest first
+    history: BumpVec<'a, (u64, usize, Option<Member>)>
+}
+
+impl<'a> Roster<'a> {
+    pub fn new(memory: &'a AllocatorCell) -> Self {
+        return Self {
+            members: BumpVec::new_in(memory),
+            history: BumpVec::new_in(memory)
+        };
+    }
+
+    // Copy of the roster to go with a fork of its tree, see RatchetTree::fork & Roster::promote
+    pub fn fork<'b>(&self, memory: &'b AllocatorCell) -> Roster<'b> {
+        let mut roster: Roster<'b> = Roster::new(memory);
+
+        roster.members.extend_from_slice(&self.members);
+
+        return roster;
+    }
+
+    /*
+    * Rebuild the roster recorded in `snapshot`, against `tree` restored from the same snapshot.
+    * Every member must sit in an occupied leaf of the tree, at most one member per leaf.
+    */
+    pub fn restore<'e>(snapshot: &TreeSnapshot, tree: &RatchetTree, memory: &'a AllocatorCell) -> Result<Self, RatchetError<'e>> {
+        let mut roster: Roster<'a> = Roster::new(memory);
+
+        for (index, member) in snapshot.members.iter() {
+            let leaf: Option<&Key> = tree.get(0, *index);
+
+            if *index == 0 || leaf.is_none() || leaf == tree.tombstone.as_ref() {
+                return Err(RatchetError{
+                    description: "Snapshot binds a member to an empty leaf",
+                    cause: RatchetErrorCause::INVALID_INDEX,
+                    index: *index,
+                    height: 0
+                });
+            }
+
+            if roster.get(*index).is_some() || roster.index_of(member.id).is_some() || roster.index_of_identity(&member.identity).is_some() {
+                return Err(RatchetError{
+                    description: "Snapshot binds a member or leaf more than once",
+                    cause: RatchetErrorCause::INVALID_MEMBER,
+                    index: *index,
+                    height: 0
+                });
+            }
+
+            roster.bind(*index, *member);
+        }
+
+        return Ok(roster);
+    }
+
+    // Record every bound member into a snapshot taken with RatchetTree::snapshot
+    pub fn snapshot(&self, snapshot: &mut TreeSnapshot) {
+        snapshot.members.clear();
+
+        for (index, member) in self.members.iter().enumerate() {
+            if let Some(member) = member {
+                snapshot.members.push((index, *member));
+            }
+        }
+    }
+
+    pub fn get(&self, index: usize) -> Option<&Member> {
+        match self.members.get(index) {
+            Some(member) => return member.as_ref(),
+            None => return None
+        }
+    }
+
+    pub fn index_of(&self, id: MemberId) -> Option<usize> {
+        return self.members.iter().position(|m| m.map_or(false, |m| m.id == id));
+    }
+
+    pub fn index_of_identity(&self, identity: &VerifyingKey) -> Option<usize> {
+        return self.members.iter().position(|m| m.map_or(false, |m| &m.identity == identity));
+    }
+
+    pub fn len(&self) -> usize {
+        return self.members.iter().filter(|m| m.is_some()).count();
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (usize, &Member)> {
+        return self.members.iter().enumerate().filter_map(|(i, m)| m.as_ref().map(|m| (i, m)));
+    }
+
+    pub fn commit<'t, 'tree, 'caller>(&mut self, tree: &'t mut RatchetTree<'tree>, branch: &RatchetBranch, member: Option<Member>, memory: &'tree AllocatorPool) -> Result<&'t Key, RatchetError<'caller>> {
+        let epoch: u64 = tree.epoch();
+
+        self.trim(tree.journal_base());
+
+        let binding: Option<Option<Member>> = self.stage(tree, tree.get(0, branch.root), branch, member)?;
+        let root: &'t Key = tree.commit(branch, memory)?;
+
+        self.apply(epoch, branch.root, binding);
+
+        return Ok(root);
+    }
+
+    pub fn apply_update<'t, 'tree, 'caller>(&mut self, tree: &'t mut RatchetTree<'tree>, update: &RatchetBranch, member: Option<Member>, index: usize, memory: &'tree AllocatorPool, scratch: &'caller AllocatorCell) -> Result<&'t Key, RatchetError<'caller>> {
+        let epoch: u64 = tree.epoch();
+
+        self.trim(tree.journal_base());
+
+        let binding: Option<Option<Member>> = self.stage(tree, tree.get(0, update.root), update, member)?;
+        let root: &'t Key = tree.apply_update(update, index, memory, scratch)?;
+
+        self.apply(epoch, update.root, binding);
+
+        return Ok(root);
+    }
+
+    /*
+    * RatchetTree::commit_batch, with `members[i]` naming the member joining through `branches[i]`, if any.
+    * Every branch is staged as if committed on its own, and nobody may join twice in the same batch.
+    */
+    pub fn commit_batch<'tree, 'caller>(&mut self, tree: &mut RatchetTree<'tree>, branches: &[RatchetBranch], members: &[Option<Member>], memory: &'tree AllocatorPool, scratch: &'caller AllocatorCell) -> Result<BumpVec<'caller, RatchetBranch<'caller>>, RatchetError<'caller>> {
+        let epoch: u64 = tree.epoch();
+
+        self.trim(tree.journal_base());
+
+        let bindings: BumpVec<'caller, Option<Option<Member>>> = self.stage_batch(tree, branches, members, scratch)?;
+        let update: BumpVec<'caller, RatchetBranch<'caller>> = tree.commit_batch(branches, memory, scratch)?;
+
+        for (branch, binding) in branches.iter().zip(bindings.iter()) {
+            self.apply(epoch, branch.root, *binding);
+        }
+
+
+        return Ok(update);
+    }
+
+    // RatchetTree::apply_batch, with `members[i]` naming the member joining through `update[i]`, if any
+    pub fn apply_batch<'t, 'tree, 'caller>(&mut self, tree: &'t mut RatchetTree<'tree>, update: &[RatchetBranch], members: &[Option<Member>], index: usize, memory: &'tree AllocatorPool, scratch: &'caller AllocatorCell) -> Result<&'t Key, RatchetError<'caller>> {
+        let epoch: u64 = tree.epoch();
+
+        self.trim(tree.journal_base());
+
+        let bindings: BumpVec<'caller, Option<Option<Member>>> = self.stage_batch(tree, update, members, scratch)?;
+        let root: &'t Key = tree.apply_batch(update, index, memory, scratch)?;
+
+        for (branch, binding) in update.iter().zip(bindings.iter()) {
+            self.apply(epoch, branch.root, *binding);
+        }
+
+
+        return Ok(root);
+    }
+
+    // RatchetTree::apply_merged, with `members[i]` naming the member joining through `update[i]`, if any
+    pub fn apply_merged<'t, 'tree, 'caller>(&mut self, tree: &'t mut RatchetTree<'tree>, update: &[RatchetBranch], members: &[Option<Member>], memory: &'tree AllocatorPool, scratch: &'caller AllocatorCell) -> Result<&'t Key, RatchetError<'caller>> {
+        let epoch: u64 = tree.epoch();
+
+        self.trim(tree.journal_base());
+
+        let bindings: BumpVec<'caller, Option<Option<Member>>> = self.stage_batch(tree, update, members, scratch)?;
+        let root: &'t Key = tree.apply_merged(update, memory, scratch)?;
+
+        for (branch, binding) in update.iter().zip(bindings.iter()) {
+            self.apply(epoch, branch.root, *binding);
+        }
+
+        return Ok(root);
+    }
+
+    // RatchetTree::rollback, putting back every binding the undone commits changed
+    pub fn rollback<'caller>(&mut self, tree: &mut RatchetTree, n: usize) -> Result<u64, RatchetError<'caller>> {
+        let epoch: u64 = tree.rollback(n)?;
+
+        self.revert(epoch);
+
+        return Ok(epoch);
+    }
+
+    /*
+    * RatchetTree::resolve_fork, with `member` naming the member joining through `branch`, if any.
+    * The incoming branch is staged against the roster & tree as they were before our own commit, and is
+    * rejected before anything is resolved if it doesn't fit, whichever side would have won.
+    */
+    pub fn resolve_fork<'tree, 'caller>(&mut self, tree: &mut RatchetTree<'tree>, branch: &RatchetBranch, member: Option<Member>, index: Option<usize>, memory: &'tree AllocatorPool, scratch: &'caller AllocatorCell) -> Result<ForkResolution, RatchetError<'caller>> {
+        self.trim(tree.journal_base());
+
+        // Put our own commit's bindings aside, they go back unless the incoming branch wins
+        let mut members: BumpVec<'caller, Option<Member>> = BumpVec::with_capacity_in(self.members.len(), scratch);
+        let mut history: BumpVec<'caller, (u64, usize, Option<Member>)> = BumpVec::with_capacity_in(self.history.len(), scratch);
+
+        members.extend_from_slice(&self.members);
+        history.extend_from_slice(&self.history);
+        self.revert(branch.epoch);
+
+        let resolution: Result<ForkResolution, RatchetError<'caller>> = match self.stage(tree, tree.last_leaf(branch.root), branch, member) {
+            Ok(binding) => match tree.resolve_fork(branch, index, memory, scratch) {
+                Ok(ForkResolution::REPLACED { loser }) => {
+                    self.apply(branch.epoch, branch.root, binding);
+
+                    return Ok(ForkResolution::REPLACED { loser: loser });
+                },
+                resolution => resolution
+            },
+            Err(e) => Err(e)
+        };
+
+        self.members.clear();
+        self.members.extend_from_slice(&members);
+        self.history.clear();
+        self.history.extend_from_slice(&history);
+
+        return resolution;
+    }
+
+    /*
+    * RatchetTree::promote, taking on the bindings of `fork`, the roster that followed the fork's commits.
+    * Every member of `fork` must sit in a leaf the promoted tree has occupied.
+    */
+    pub fn promote<'t, 'tree, 'caller>(&mut self, tree: &'t mut RatchetTree<'tree>, changes: &TreeChanges, fork: &Roster, memory: &'tree AllocatorPool) -> Result<Option<&'t Key>, RatchetError<'caller>> {
+        // The fork may have written the leaf layer, the tree's own leaves stand otherwise
+        let leaves: Option<&BumpVec<Key>> = changes.layers.iter().find(|(height, _)| *height == 0).map(|(_, layer)| layer);
+
+        for (index, _) in fork.iter() {
+            let leaf: Option<&Key> = match leaves {
+                Some(leaves) => leaves.get(index),
+                None => tree.get(0, index)
+            };
+
+            if leaf.is_none() || leaf == tree.tombstone.as_ref() {
+                return Err(RatchetError{
+                    description: "Fork roster binds a member to an empty leaf",
+                    cause: RatchetErrorCause::INVALID_INDEX,
+                    index: index,
+                    height: 0
+                });
+            }
+        }
+
+        let epoch: u64 = tree.epoch();
+
+        self.trim(tree.journal_base());
+
+        let root: Option<&'t Key> = tree.promote(changes, memory)?;
+
+        for index in 0..core::cmp::max(self.members.len(), fork.members.len()) {
+            let binding: Option<Member> = fork.get(index).copied();
+
+            if self.get(index).copied() != binding {
+                self.apply(epoch, index, Some(binding));
+            }
+        }
+
+
+        return Ok(root);
+    }
+
+    fn stage_batch<'caller>(&self, tree: &RatchetTree, branches: &[RatchetBranch], members: &[Option<Member>], scratch: &'caller AllocatorCell) -> Result<BumpVec<'caller, Option<Option<Member>>>, RatchetError<'caller>> {
+        if branches.len() != members.len() {
+            return Err(RatchetError{
+                description: "Batch must name a member, or none, for every branch",
+                cause: RatchetErrorCause::INVALID_MEMBER,
+                index: 0,
+                height: 0
+            });
+        }
+
+        let mut bindings: BumpVec<'caller, Option<Option<Member>>> = BumpVec::with_capacity_in(branches.len(), scratch);
+
+        for (branch, member) in branches.iter().zip(members.iter()) {
+            let binding: Option<Option<Member>> = self.stage(tree, tree.get(0, branch.root), branch, *member)?;
+
+            if let Some(Some(joining)) = binding {
+                let twice: bool = bindings.iter().any(|b| b.map_or(false, |b| b.map_or(false, |b| b.id == joining.id || b.identity == joining.identity)));
+
+                if twice {
+                    return Err(RatchetError{
+                        description: "Member joins more than once in the same batch",
+                        cause: RatchetErrorCause::INVALID_MEMBER,
+                        index: branch.root,
+                        height: 0
+                    });
+                }
+            }
+
+            bindings.push(binding);
+        }
+
+        return Ok(bindings);
+    }
+
+    /*
+    * Work out what committing `branch` over `leaf` does to the roster, without changing anything.
+    * Some(Some(member)) binds a new member, Some(None) unbinds the leaf, None leaves the roster as-is.
+    */
+    fn stage<'e>(&self, tree: &RatchetTree, leaf: Option<&Key>, branch: &RatchetBranch, member: Option<Member>) -> Result<Option<Option<Member>>, RatchetError<'e>> {
+        let index: usize = branch.root;
+        let is_empty: bool = leaf.is_none() || leaf == tree.tombstone.as_ref();
+        let is_removal: bool = branch.get_node(0) == tree.tombstone.as_ref();
+
+        if is_removal {
+            if member.is_some() {
+                return Err(RatchetError{
+                    description: "Removal branches cannot bind a member",
+                    cause: RatchetErrorCause::INVALID_MEMBER,
+                    index: index,
+                    height: 0
+                });
+            }
+
+            return Ok(Some(None));
+        }
+
+        if !is_empty {
+            if member.is_some() && member.as_ref() != self.get(index) {
+                return Err(RatchetError{
+                    description: "Leaf is already bound to another member",
+                    cause: RatchetErrorCause::INVALID_MEMBER,
+                    index: index,
+                    height: 0
+                });
+            }
+
+            return Ok(None);
+        }
+
+        let member: Member = match member {
+            Some(member) => member,
+            None => {
+                return Err(RatchetError{
+                    description: "Insert branches must bind a member",
+                    cause: RatchetErrorCause::INVALID_MEMBER,
+                    index: index,
+                    height: 0
+                });
+            }
+        };
+
+        if self.index_of(member.id).is_some() || self.index_of_identity(&member.identity).is_some() {
+            return Err(RatchetError{
+                description: "Member is already part of the group",
+                cause: RatchetErrorCause::INVALID_MEMBER,
+                index: index,
+                height: 0
+            });
+        }
+
+        return Ok(Some(Some(member)));
+    }
+
+    // Change the binding of leaf `index` for the commit made at `epoch`, keeping what it was
+    fn apply(&mut self, epoch: u64, index: usize, binding: Option<Option<Member>>) {
+        let previous: Option<Member> = self.get(index).copied();
+
+        match binding {
+            Some(Some(member)) => self.bind(index, member),
+            Some(None) => self.unbind(index),
+            None => return
+        }
+
+        self.history.push((epoch, index, previous));
+    }
+
+    // Undo every binding changed by commits made at `epoch` or later, newest first
+    fn revert(&mut self, epoch: u64) {
+        while let Some((at, index, previous)) = self.history.last().copied() {
+            if at < epoch {
+                break;
+            }
+
+            match previous {
+                Some(member) => self.bind(index, member),
+                None => self.unbind(index)
+            }
+
+            // BumpVec::pop trips over bumpalo's own precondition checks, truncate instead
+            self.history.truncate(self.history.len() - 1);
+        }
+    }
+
+    // Forget bindings changed before `epoch`, the tree can't be rolled back that far anymore. Done ahead of
+    // every commit, so at most one commit's bindings outlive their journal entry
+    fn trim(&mut self, epoch: u64) {
+        self.history.retain(|(at, _, _)| *at >= epoch);
+    }
+
+    fn bind(&mut self, index: usize, member: Member) {
+        if index >= self.members.len() {
+            self.members.resize(index + 1, None);
+        }
+
+        self.members[index] = Some(member);
+    }
+
+    fn unbind(&mut self, index: usize) {
+        if let Some(member) = self.members.get_mut(index) {
+            *member = None;
+        }
+    }
+}
@@ -0,0 +1,157 @@
+extern crate alloc;
+
+use core::fmt;
+
+use elliptic_curve::zeroize::{
+    Zeroize,
+    Zeroizing
+};
+
+use hkdf::Hkdf;
+use sha2::Sha256;
+
+use crate::ecdh::Key;
+
+pub const STAGE_KEY_LEN: usize = 32;
+
+const STAGE_LABEL: &[u8] = b"art stage key";
+const EPOCH_LEN: usize = 8;
+const SCALAR_LEN: usize = 32;
+// Longest label accepted by derive, keeps the HKDF info on the stack
+const MAX_LABEL_LEN: usize = 64;
+
+#[derive(Debug, Clone)]
+pub struct ScheduleError<'a> {
+    pub reason: &'a str
+}
+
+impl<'a> fmt::Display for ScheduleError<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return write!(f, "Invalid Key Schedule Operation: {}", self.reason);
+    }
+}
+
+/*
+* ART stage key schedule.
+* Every time the tree commits a new root, the next stage key is derived from the previous stage key
+* and the new tree key: stage_key(n) = HKDF(salt: stage_key(n - 1), ikm: tree_key(n), info: label || n)
+* so a compromised tree key alone never reveals past stage keys, and a single honest update heals
+* a compromised stage key. Application keys are only ever derived from the stage key, never the tree key.
+*/
+pub struct KeySchedule {
+    stage_key: [u8; STAGE_KEY_LEN],
+    epoch: u64
+}
+
+impl Default for KeySchedule {
+    fn default() -> Self {
+        return Self {
+            stage_key: [0; STAGE_KEY_LEN],
+            epoch: 0
+        };
+    }
+}
+
+// Wipes the stage key & starts over from epoch 0, nothing can be derived until the next advance
+impl Zeroize for KeySchedule {
+    fn zeroize(&mut self) {
+        self.stage_key.zeroize();
+        self.epoch = 0;
+    }
+}
+
+impl Drop for KeySchedule {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+impl KeySchedule {
+    pub fn new() -> Self {
+        return Self::default();
+    }
+
+    pub fn epoch(&self) -> u64 {
+        return self.epoch;
+    }
+
+    pub fn stage_key(&self) -> &[u8; STAGE_KEY_LEN] {
+        return &self.stage_key;
+    }
+
+    // Call with the root returned from RatchetTree::commit/apply_update, once per commit
+    pub fn advance<'a>(&mut self, tree_key: &Key) -> Result<u64, ScheduleError<'a>> {
+        let secret = match tree_key.sk.as_ref() {
+            Some(secret) => secret,
+            None => {
+                return Err(ScheduleError{
+                    reason: "Tree key has no secret to advance the key schedule with"
+                });
+            }
+        };
+
+        let epoch: u64 = self.epoch + 1;
+        let mut info: [u8; STAGE_LABEL.len() + EPOCH_LEN] = [0; STAGE_LABEL.len() + EPOCH_LEN];
+
+        info[..STAGE_LABEL.len()].copy_from_slice(STAGE_LABEL);
+        info[STAGE_LABEL.len()..].copy_from_slice(&epoch.to_be_bytes());
+
+        // Both wiped once we're done with them, whichever way we return
+        let repr: Zeroizing<[u8; SCALAR_LEN]> = Zeroizing::new(secret.to_repr().into());
+        let mut next: Zeroizing<[u8; STAGE_KEY_LEN]> = Zeroizing::new([0; STAGE_KEY_LEN]);
+
+        let hkdf: Hkdf<Sha256> = Hkdf::new(Some(&self.stage_key), repr.as_slice());
+
+        if hkdf.expand(&info, next.as_mut_slice()).is_err() {
+            return Err(ScheduleError{
+                reason: "Unable to expand next stage key"
+            });
+        }
+
+        self.stage_key.copy_from_slice(next.as_slice());
+        self.epoch = epoch;
+
+        return Ok(epoch);
+    }
+
+    /*
+    * Derive an application key for the current epoch, bound to `label`.
+    * The epoch 0 stage key is a public constant, nothing is derived until a tree key has been absorbed.
+    */
+    pub fn derive<'a>(&self, label: &[u8], out: &mut [u8]) -> Result<(), ScheduleError<'a>> {
+        if self.epoch == 0 {
+            return Err(ScheduleError{
+                reason: "No tree key absorbed into the key schedule yet"
+            });
+        }
+
+        if label.len() > MAX_LABEL_LEN {
+            return Err(ScheduleError{
+                reason: "Label too long for key derivation"
+            });
+        }
+
+        let hkdf: Hkdf<Sha256> = match Hkdf::from_prk(&self.stage_key) {
+            Ok(hkdf) => hkdf,
+            Err(_) => {
+                return Err(ScheduleError{
+                    reason: "Stage key too short for key derivation"
+                });
+            }
+        };
+
+        let mut info: [u8; MAX_LABEL_LEN + EPOCH_LEN] = [0; MAX_LABEL_LEN + EPOCH_LEN];
+        let info_len: usize = label.len() + EPOCH_LEN;
+
+        info[..label.len()].copy_from_slice(label);
+        info[label.len()..info_len].copy_from_slice(&self.epoch.to_be_bytes());
+
+        if hkdf.expand(&info[..info_len], out).is_err() {
+            return Err(ScheduleError{
+                reason: "Requested key too long for key derivation"
+            });
+        }
+
+        return Ok(());
+    }
+}
@@ -10,6 +10,7 @@ use bumpalo::{
 use core::{
     usize,
     mem,
+    cmp,
     cmp::Ord,
     cmp::Ordering,
     cmp::PartialOrd,
@@ -27,18 +28,41 @@ use core::{
     future::Future,
     pin::Pin,
     ops::Deref,
+    ops::DerefMut,
     fmt
 };
 
 use crate::sync::Arc;
 use crate::mem::{
     AllocatorPool,
+    AllocatorPoolError,
+    AllocatorSlot,
+    AllocatorStats,
     AllocatorCell
 };
 
 use crate::ecdh::{
     Key
 };
+use crate::wire::{
+    TreeSnapshot,
+    UpdateKind,
+    UpdateMessage
+};
+use crate::identity::verify_update;
+use crate::roster::{
+    Member,
+    Roster
+};
+
+use k256::PublicKey;
+use elliptic_curve::sec1::ToEncodedPoint;
+
+use sha2::{
+    Sha256,
+    Digest
+};
+
 use crate::log::*;
 
 use hashbrown::{
@@ -47,10 +71,28 @@ use hashbrown::{
     hash_map
 };
 
-pub const MEMORY_ROOT_NODE_INDEX: usize = 0;
-pub const MEMORY_ORPHAN_NODE_INDEX: usize = 1;
-pub const MEMORY_BRANCH_INDEX: usize = 2;
-pub const MEMORY_TREE_START_INDEX: usize = 3;
+// Every tree's own slots are namespaced as "tree.{id}.{slot}", see RatchetTree::slot_name
+pub const SLOT_ROOT_NODES: &str = "nodes";
+pub const SLOT_ORPHANS: &str = "orphans";
+pub const SLOT_JOURNAL: &str = "journal";
+// Layer h lives in slot "tree.{id}.layer.h"
+pub const SLOT_LAYER: &str = "layer";
+// Scratch memory for the tree's branches, see RatchetTree::branch_memory
+pub const SLOT_BRANCH: &str = "branch";
+// Scratch memory for branches built without a tree of their own, see branch_memory
+pub const SLOT_SCRATCH: &str = "tree.scratch";
+
+const SLOT_LEN: usize = 16;
+
+pub const DEFAULT_JOURNAL_DEPTH: usize = 8;
+
+pub const TREE_HASH_LEN: usize = 32;
+
+// 2^32 leaves, anything taller can't be indexed on wasm32
+pub const MAX_TREE_HEIGHT: usize = 32;
+
+const TREE_HASH_LABEL: &[u8] = b"art tree hash";
+const EMPTY_NODE_HASH: [u8; TREE_HASH_LEN] = [0; TREE_HASH_LEN];
 
 pub fn is_even(i: usize) -> bool {
     return i & 0x1 == 0;
@@ -71,6 +113,20 @@ pub fn get_next_index(i: usize) -> usize {
     return round_up(i) / 2;
 }
 
+fn slot_name(id: usize, slot: &str) -> alloc::string::String {
+    return alloc::format!("tree.{}.{}", id, slot);
+}
+
+// Height of a tree holding `leaves` leaves
+pub(crate) fn height_of(leaves: usize) -> usize {
+    return if leaves <= 1 { 0 } else { (leaves as f64).log(2.0).ceil() as usize };
+}
+
+// Scratch memory to build branches in, for callers without a tree on `memory`, see RatchetTree::branch_memory
+pub fn branch_memory<'a>(memory: &AllocatorPool<'a>) -> Result<AllocatorCell, AllocatorPoolError<'a>> {
+    return memory.get_slot(&memory.register_secret::<Key>(SLOT_SCRATCH, SLOT_LEN)?);
+}
+
 /*
 * Get the sibling pair index for key at index i
 * get_sibling_index(1) = 2
@@ -85,12 +141,63 @@ pub fn get_sibling_index(i: usize) -> usize {
     return i + 1
 }
 
+/*
+* Work out a parent node from its two children:
+* - Both children empty (missing or tombstoned), the parent is a tombstone
+* - One child empty, the parent passes the other child through
+* - Otherwise the parent is the DH of both children, which needs the secret of at least one of them
+*/
+pub fn ratchet_node<'a>(k1: Option<&Key>, k2: Option<&Key>, tombstone: Option<&Key>) -> Result<Key, crate::errors::ECError<'a>> {
+    let no_key1: bool = k1.is_none() || k1 == tombstone;
+    let no_key2: bool = k2.is_none() || k2 == tombstone;
+
+    if no_key1 && no_key2 {
+        return Ok(tombstone.unwrap().clone());
+    }
+
+    if !no_key1 && no_key2 {
+        return Ok(k1.unwrap().clone());
+    }
+
+    if no_key1 && !no_key2 {
+        return Ok(k2.unwrap().clone());
+    }
+
+    // I don't implicitly convert into an Option<Key> here because I want to explicitly
+    // warn of a diffie-hellman failure
+    return k1.unwrap().diffie_hellman(k2.unwrap());
+}
+
+fn find_node<'a>(layer: &'a [(usize, Key)], index: usize) -> Option<&'a Key> {
+    return layer.iter().find(|(i, _)| *i == index).map(|(_, key)| key);
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum RatchetErrorCause {
     OOM,
     INVALID_BRANCH,
     INVALID_INDEX,
-    INVALID_HEIGHT
+    INVALID_HEIGHT,
+    INVALID_SIGNATURE,
+    INVALID_MEMBER,
+    INVALID_TREE_HASH,
+    INCONSISTENT_NODE,
+    MISSING_SECRET,
+    STALE_EPOCH,
+    FORK,
+    JOURNAL_EXHAUSTED
+}
+
+/*
+* Outcome of RatchetTree::resolve_fork. The losing branch's leaf has to be re-ratcheted on top of the
+* winner, see RatchetTree::rebase.
+*/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ForkResolution {
+    // The branch we had committed wins, the incoming branch lost
+    KEPT,
+    // The incoming branch wins and replaced ours, the branch we had committed for leaf `loser` lost
+    REPLACED { loser: usize }
 }
 
 #[derive(Debug, Clone)]
@@ -107,19 +214,324 @@ impl<'a> fmt::Display for RatchetError<'a> {
     }
 }
 
-// TODO: Implement Clone/Copy for tree cache
 //#[derive(Debug)]
 pub struct RatchetTree<'tree> {
-    nodes: BumpVec<'tree, BumpVec<'tree, Key>>,
+    // Namespace of our slots in the pool, the lowest no other tree had registered
+    id: usize,
+    nodes: BumpVec<'tree, Layer<'tree>>,
     orphans: BumpVec<'tree, usize>,
+    epoch: u64,
+    journal: Journal<'tree>,
+    // Epoch of the tree this one was forked from, when it was forked, see RatchetTree::fork
+    forked_at: Option<u64>,
+    policy: &'tree dyn OrphanPolicy,
+    growth: GrowthPolicy,
+    // Pool slot of every layer the tree has had, by height
+    slots: BumpVec<'tree, AllocatorSlot<Key>>,
+    // Layers dropped by shrinking, by height, kept so growing back into them re-uses their memory
+    spare: BumpVec<'tree, (usize, BumpVec<'tree, Key>)>,
     pub tombstone: Option<Key>
 }
 
+/*
+* How a tree grows its AllocatorPool when it needs a layer there's no allocator for yet.
+* Allocators are provisioned `headroom` layers ahead of the one needed, each with room for `layer_len`
+* Keys, growing the pool as needed, see AllocatorPool::provision. Writes that could take the bytes allocated
+* in the tree's own slots past `max_bytes` are refused with OOM, see RatchetTree::allocated_bytes.
+*/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GrowthPolicy {
+    pub layer_len: usize,
+    pub headroom: usize,
+    pub max_bytes: usize
+}
+
+impl Default for GrowthPolicy {
+    fn default() -> Self {
+        return Self {
+            layer_len: 4,
+            headroom: 0,
+            max_bytes: usize::MAX
+        };
+    }
+}
+
+/*
+* Picks the leaf the next insert goes into, see RatchetTree::get_next_index.
+* `orphans` are the free (removed) leaves in the order they were removed, `leaves` the number of leaves
+* in the tree including them. None appends a new leaf after the last one.
+* Every member committing inserts has to use the same policy for the group to agree on the tree.
+*/
+pub trait OrphanPolicy {
+    fn select(&self, orphans: &[usize], leaves: usize) -> Option<usize>;
+}
+
+// Re-use leaves in the order they were removed, the default
+pub struct FifoPolicy;
+
+// Re-use the free leaf furthest to the left
+pub struct LeftmostPolicy;
+
+/*
+* Re-use the free leaf that keeps the tree most balanced: starting from the root, step into whichever
+* subtree holding a free leaf has the fewest occupied leaves, the left one on a tie.
+*/
+pub struct BalancedPolicy;
+
+impl OrphanPolicy for FifoPolicy {
+    fn select(&self, orphans: &[usize], _leaves: usize) -> Option<usize> {
+        return orphans.first().copied();
+    }
+}
+
+impl OrphanPolicy for LeftmostPolicy {
+    fn select(&self, orphans: &[usize], _leaves: usize) -> Option<usize> {
+        return orphans.iter().min().copied();
+    }
+}
+
+impl OrphanPolicy for BalancedPolicy {
+    fn select(&self, orphans: &[usize], leaves: usize) -> Option<usize> {
+        if orphans.is_empty() {
+            return None;
+        }
+
+        let free = |start: usize, end: usize| orphans.iter().filter(|o| **o >= start && **o < end).count();
+        let occupied = |start: usize, end: usize| core::cmp::min(end, leaves + 1).saturating_sub(start) - free(start, end);
+
+        let mut start: usize = 1;
+        let mut span: usize = leaves.next_power_of_two();
+
+        while span > 1 {
+            let half: usize = span / 2;
+            let middle: usize = start + half;
+            let end: usize = start + span;
+
+            let go_right: bool = match (free(start, middle), free(middle, end)) {
+                (0, _) => true,
+                (_, 0) => false,
+                _ => occupied(middle, end) < occupied(start, middle)
+            };
+
+            if go_right {
+                start = middle;
+            }
+
+            span = half;
+        }
+
+        return Some(start);
+    }
+}
+
+// Bytes a BumpVec of `capacity` Ts allocates growing a push at a time until it holds `length`
+fn growth_bytes<T>(capacity: usize, length: usize) -> usize {
+    let mut capacity: usize = capacity;
+    let mut bytes: usize = 0;
+
+    while capacity < length {
+        capacity = cmp::max(capacity * 2, capacity + 1);
+        bytes += capacity * mem::size_of::<T>();
+    }
+
+    return bytes;
+}
+
+// Every (height, index) a branch of `len` nodes from leaf `root` writes to
+fn branch_writes(root: usize, len: usize) -> impl Iterator<Item = (usize, usize)> {
+    return (0..len).scan(root, |index, height| {
+        let write: (usize, usize) = (height, *index);
+        *index = get_next_index(*index);
+
+        return Some(write);
+    });
+}
+
+// An empty layer for `height`, the one last dropped from there if it's still spare
+fn new_layer<'tree>(spare: &mut BumpVec<'tree, (usize, BumpVec<'tree, Key>)>, height: usize, memory: &'tree AllocatorCell) -> BumpVec<'tree, Key> {
+    if let Some(position) = spare.iter().position(|(spare, _)| *spare == height) {
+        return spare.remove(position).1;
+    }
+
+    let mut layer: BumpVec<'tree, Key> = BumpVec::new_in(memory);
+    layer.push(Key::default());
+
+    return layer;
+}
+
+/*
+* A single layer of the tree. Forks start out borrowing every layer of the tree they were forked from,
+* and only copy a layer into their own allocator the first time they write to it.
+*/
+enum Layer<'tree> {
+    Owned(BumpVec<'tree, Key>),
+    Shared(&'tree [Key], &'tree AllocatorCell)
+}
+
+impl<'tree> Layer<'tree> {
+    // Copy a shared layer into its allocator, ready for writing
+    fn to_mut(&mut self) -> &mut BumpVec<'tree, Key> {
+        if let Layer::Shared(keys, memory) = self {
+            let mut layer: BumpVec<'tree, Key> = BumpVec::with_capacity_in(keys.len() + 1, *memory);
+            layer.extend_from_slice(keys);
+
+            *self = Layer::Owned(layer);
+        }
+
+        match self {
+            Layer::Owned(layer) => return layer,
+            Layer::Shared(_, _) => unreachable!()
+        }
+    }
+
+    fn is_shared(&self) -> bool {
+        return matches!(self, Layer::Shared(_, _));
+    }
+}
+
+impl<'tree> Deref for Layer<'tree> {
+    type Target = [Key];
+
+    fn deref(&self) -> &[Key] {
+        match self {
+            Layer::Owned(layer) => return layer,
+            Layer::Shared(keys, _) => return keys
+        }
+    }
+}
+
+impl<'tree> DerefMut for Layer<'tree> {
+    fn deref_mut(&mut self) -> &mut [Key] {
+        return self.to_mut();
+    }
+}
+
+/*
+* Everything a fork changed since it was forked: the layers it copied (with any secrets they hold), the
+* orphan list & epoch. Allocated outside the fork, so it outlives the fork's borrow of its parent and
+* can be handed back to the parent with RatchetTree::promote.
+*/
+pub struct TreeChanges<'a> {
+    pub base: u64,
+    pub epoch: u64,
+    pub orphans: BumpVec<'a, usize>,
+    pub layers: BumpVec<'a, (usize, BumpVec<'a, Key>)>
+}
+
+/*
+* Undo journal of the last `depth` commits, oldest first, allocated in the tree's SLOT_JOURNAL slot.
+* Entries are flattened into shared vectors: each entry owns the last `nodes`/`orphans` elements
+* left over once every later entry has been taken off the end.
+* Nodes are (height, index, previous value), None where the commit appended a new node.
+*/
+struct Journal<'tree> {
+    // Layers dropped by shrinking are re-created in their own allocators when rolled back
+    memory: &'tree AllocatorPool<'tree>,
+    depth: usize,
+    entries: BumpVec<'tree, JournalEntry>,
+    nodes: BumpVec<'tree, (usize, usize, Option<Key>)>,
+    orphans: BumpVec<'tree, usize>
+}
+
+#[derive(Clone, Copy)]
+struct JournalEntry {
+    // Leaf the commit was rooted at, 0 for batches & promotions
+    root: usize,
+    epoch: u64,
+    // Single branch commits a competing branch for the same epoch can take the place of, see resolve_fork
+    resolvable: bool,
+    layers: usize,
+    nodes: usize,
+    orphans: usize
+}
+
+impl<'tree> Journal<'tree> {
+    fn new(depth: usize, name: &str, memory: &'tree AllocatorPool<'tree>) -> Result<Self, AllocatorPoolError<'tree>> {
+        let journal: &'tree AllocatorCell = memory.get_slot_ref(&memory.register_secret::<JournalEntry>(name, depth)?)?;
+
+        return Ok(Self {
+            memory: memory,
+            depth: depth,
+            entries: BumpVec::with_capacity_in(depth, journal),
+            nodes: BumpVec::new_in(journal),
+            orphans: BumpVec::new_in(journal)
+        });
+    }
+
+    fn last(&self) -> Option<&JournalEntry> {
+        return self.entries.last();
+    }
+
+    // Nodes replaced by the last entry, in the order they were written
+    fn last_nodes(&self) -> &[(usize, usize, Option<Key>)] {
+        let count: usize = self.last().map_or(0, |entry| entry.nodes);
+
+        return &self.nodes[self.nodes.len() - count..];
+    }
+
+    // Drop the oldest entries until at most `depth` remain
+    fn trim(&mut self, depth: usize) {
+        while self.entries.len() > depth {
+            let oldest: JournalEntry = self.entries.remove(0);
+
+            self.nodes.drain(..oldest.nodes);
+            self.orphans.drain(..oldest.orphans);
+        }
+    }
+
+    fn begin(&mut self, root: usize, epoch: u64, layers: usize, orphans: &[usize]) {
+        if self.depth == 0 {
+            return;
+        }
+
+        self.trim(self.depth - 1);
+        self.entries.push(JournalEntry {
+            root: root,
+            epoch: epoch,
+            resolvable: false,
+            layers: layers,
+            nodes: 0,
+            orphans: orphans.len()
+        });
+        self.orphans.extend_from_slice(orphans);
+    }
+
+    // Writes outside of a commit (or with journaling off) aren't recorded
+    fn record(&mut self, height: usize, index: usize, previous: Option<Key>) {
+        if let Some(entry) = self.entries.last_mut() {
+            entry.nodes += 1;
+            self.nodes.push((height, index, previous));
+        }
+    }
+}
+
 pub struct RatchetBranch<'a> {
     pub root: usize,
+    // Epoch of the tree the branch was computed against, only committable at that epoch
+    pub epoch: u64,
     pub nodes: BumpVec<'a, Key>
 }
 
+/*
+* Group setup message broadcast by the initiator of a group.
+* Contains the public half of the setup key, the prekeys used to derive each member's leaf
+* and the public keys of every node in the tree, laid out exactly as the tree's layers are.
+* Prekey 0 belongs to leaf index 2, prekey 1 to leaf index 3 and so on; the initiator is always leaf 1.
+*/
+pub struct RatchetSetup<'a> {
+    pub setup_key: Key,
+    pub prekeys: BumpVec<'a, Key>,
+    pub nodes: BumpVec<'a, BumpVec<'a, Key>>
+}
+
+// A node position in the tree & the public key stored there, if any
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PathNode {
+    pub height: usize,
+    pub index: usize,
+    pub pk: Option<PublicKey>
+}
+
 pub struct RatchetIter {
     index: usize,
     height: usize,
@@ -143,9 +555,10 @@ impl RatchetIter {
 }
 
 impl<'a> RatchetBranch<'a> {
-    fn new(allocator_ref: &'a AllocatorCell, root: usize) -> Self {        
+    pub fn new(allocator_ref: &'a AllocatorCell, root: usize) -> Self {
         return Self {
             root: root,
+            epoch: 0,
             nodes: BumpVec::new_in(allocator_ref)
         }
     }
@@ -166,6 +579,26 @@ impl<'a> RatchetBranch<'a> {
         return self.nodes.get(self.len() - 1);
     }
 
+    // Get the node at (height, index) if it lies on this branch's path
+    pub fn get_at(&self, height: usize, index: usize) -> Option<&Key> {
+        if !self.passes(height, index) {
+            return None;
+        }
+
+        return self.nodes.get(height);
+    }
+
+    // Whether (height, index) is an ancestor of our leaf, even above the top of the branch
+    pub fn passes(&self, height: usize, index: usize) -> bool {
+        let mut node_index: usize = self.root;
+
+        for _ in 0..height {
+            node_index = get_next_index(node_index);
+        }
+
+        return node_index == index;
+    }
+
     pub fn iter(&self) -> core::slice::Iter<Key> {
         return self.nodes.iter();
     }
@@ -174,6 +607,7 @@ impl<'a> RatchetBranch<'a> {
         return self.nodes.len();
     }
 
+    // Dropping each node wipes its secret
     pub fn clear(&mut self) {
         self.nodes.clear();
     }
@@ -207,180 +641,1573 @@ impl Iterator for RatchetIter {
 *
 * Memory is provided via multiple Bump allocators, each tied to a specific role in the tree. This allows for
 * cleaner segmentation of memory and makes each layer of the tree droppable so memory can be freed.
-* Memory Pool indexes:
-* - Root leaf Nodes: 0
-* - Orphan Node vec: 1
-* - Ratchet Branch  vec: 2
-* - Layer 1 - 3
-* - Layer 2 - 4
+* Each is a named slot of the pool, so the pool can be shared with anything else registering slots, other
+* trees included: each tree's slots are under its own "tree.{id}" namespace, given back to the pool on drop.
+* Slots holding keys (the branch, the journal & every layer) are secret-bearing, wiped before they're reset:
+* - Root leaf Nodes: tree.{id}.SLOT_ROOT_NODES
+* - Orphan Node vec: tree.{id}.SLOT_ORPHANS
+* - Undo journal: tree.{id}.SLOT_JOURNAL
+* - Layer 0: tree.{id}.SLOT_LAYER.0
+* - Layer 1: tree.{id}.SLOT_LAYER.1
 * ... and so forth
+* - Ratchet Branch vec: tree.{id}.SLOT_BRANCH
 *
 */
 impl<'tree> RatchetTree<'tree> {
-    pub fn new(memory: &'tree AllocatorPool) -> Self {
-        assert!(memory.capacity() >= 4);
-
-        let mut nodes: BumpVec<BumpVec<Key>> = BumpVec::with_capacity_in(16, memory.get_ref(MEMORY_ROOT_NODE_INDEX));
-        let mut first_layer: BumpVec<Key> = BumpVec::new_in(memory.get_ref(MEMORY_TREE_START_INDEX));
+    pub fn new<'caller>(memory: &'tree AllocatorPool) -> Result<Self, RatchetError<'caller>> {
+        let id: usize = (0..).find(|id| !memory.has_slot(&slot_name(*id, SLOT_ROOT_NODES))).unwrap();
+        let root: Result<&'tree AllocatorCell, AllocatorPoolError> = memory.register::<Layer>(&slot_name(id, SLOT_ROOT_NODES), SLOT_LEN).and_then(|slot| memory.get_slot_ref(&slot));
+        let orphans: Result<&'tree AllocatorCell, AllocatorPoolError> = memory.register::<usize>(&slot_name(id, SLOT_ORPHANS), SLOT_LEN).and_then(|slot| memory.get_slot_ref(&slot));
+        let journal: Result<Journal<'tree>, AllocatorPoolError> = Journal::new(DEFAULT_JOURNAL_DEPTH, &slot_name(id, SLOT_JOURNAL), memory);
+        let branch: Result<AllocatorSlot<Key>, AllocatorPoolError> = memory.register_secret::<Key>(&slot_name(id, SLOT_BRANCH), SLOT_LEN);
+
+        let (root, orphans, journal) = match (root, orphans, journal, branch) {
+            (Ok(root), Ok(orphans), Ok(journal), Ok(_)) => (root, orphans, journal),
+            _ => {
+                for slot in [SLOT_ROOT_NODES, SLOT_ORPHANS, SLOT_JOURNAL, SLOT_BRANCH] {
+                    let _ = memory.release(&slot_name(id, slot));
+                }
 
-        first_layer.insert(0, Key::default());
-        nodes.insert(0, first_layer);
+                return Err(RatchetError{
+                    description: "Not enough allocators in memory_pool for tree",
+                    cause: RatchetErrorCause::OOM,
+                    index: 0,
+                    height: 0
+                });
+            }
+        };
 
-        return Self {
-            nodes: nodes,
-            orphans: BumpVec::new_in(memory.get_ref(MEMORY_ORPHAN_NODE_INDEX)),
+        let mut tree: Self = Self {
+            id: id,
+            nodes: BumpVec::with_capacity_in(16, root),
+            orphans: BumpVec::new_in(orphans),
+            epoch: 0,
+            journal: journal,
+            forked_at: None,
+            policy: &FifoPolicy,
+            growth: GrowthPolicy::default(),
+            slots: BumpVec::new_in(root),
+            spare: BumpVec::new_in(root),
             tombstone: Some(Key::default())
-        }
-    }
+        };
 
-    pub fn get_next_index(&self) -> usize {
-        match self.orphans.get(0) {
-            Some(orphan) => return *orphan,
-            None => return self.nodes[0].len()
+        // Dropping the tree gives back whatever it registered so far
+        if !tree.provision_layers(1, memory) {
+            return Err(RatchetError{
+                description: "Not enough allocators in memory_pool for tree",
+                cause: RatchetErrorCause::OOM,
+                index: 0,
+                height: 1
+            });
         }
-    }
 
-    pub fn height(&self) -> usize {
-        let node_len: usize = self.nodes[0].len() - 1;
-        return if node_len == 0 { 0 } else { (node_len as f64).log(2.0).ceil() as usize };
+        tree.ensure_layer_present(0, tree.layer_memory(0, memory)?);
+
+        return Ok(tree);
     }
 
-    pub fn iter(&self, index: usize) -> RatchetIter {
-        return RatchetIter::new(index, self.height(), 0);
+    // Name of one of our slots in the pool, "tree.{id}.{slot}"
+    pub fn slot_name(&self, slot: &str) -> alloc::string::String {
+        return slot_name(self.id, slot);
     }
 
-    pub fn ensure_layer_present(&mut self, height: usize, memory: &'tree AllocatorCell) {
-        if self.nodes.get(height).is_none() {
-            let mut layer: BumpVec<Key> = BumpVec::new_in(memory);
-            layer.insert(0, Key::default());
+    // Scratch memory to build our branches in, reset once the last clone of it is dropped
+    pub fn branch_memory(&self) -> Result<AllocatorCell, AllocatorPoolError<'tree>> {
+        let memory: &'tree AllocatorPool<'tree> = self.journal.memory;
 
-            self.nodes.insert(height, layer);
-        }
+        return memory.get_slot(&memory.slot::<Key>(&self.slot_name(SLOT_BRANCH))?);
     }
 
-    pub fn ratchet<'caller>(&self, index: usize, key: &Key, scratch: &'caller AllocatorCell) -> Result<RatchetBranch<'caller>, RatchetError<'caller>> {
-        let mut iterator: RatchetIter = self.iter(index);
-        let mut branch: RatchetBranch<'caller> = RatchetBranch::new(
-            scratch,
-            index
-        );
+    /*
+    * Fork the tree for speculative commits. The fork borrows every layer of ours and only copies a layer
+    * into its own `memory` the first time it writes to it, the orphan list is the only thing copied up front.
+    * The fork starts with an empty undo journal. Drop it to throw it away, or hand its changes back with promote.
+    */
+    pub fn fork<'fork, 'caller>(&'fork self, memory: &'fork AllocatorPool) -> Result<RatchetTree<'fork>, RatchetError<'caller>> {
+        let mut fork: RatchetTree<'fork> = RatchetTree::new(memory)?;
 
-        // Root of the branch is our node
-        branch.add_node(*key);
+        fork.growth = self.growth;
 
-        // Two phase commit
-        while let Some(key_tuple) = iterator.next() {
-            let height: usize = key_tuple.0;
+        if !fork.provision_layers(self.nodes.len(), memory) {
+            return Err(RatchetError{
+                description: "Not enough memory available in memory_pool for tree",
+                cause: RatchetErrorCause::OOM,
+                index: 0,
+                height: self.nodes.len()
+            });
+        }
 
-            if let Some(layer) = self.nodes.get(height) {
-                // Seed Key1 from previous DH result, if available
-                let k1: Option<&Key> = branch.get_last();
-                let k2: Option<&Key> = layer.get(key_tuple.2); // Key 2
+        fork.drop_layers(0);
 
-                let no_key1: bool = k1.is_none() || k1 == self.tombstone.as_ref();
-                let no_key2: bool = k2.is_none() || k2 == self.tombstone.as_ref();
+        for (height, layer) in self.nodes.iter().enumerate() {
+            let cell: &'fork AllocatorCell = fork.layer_memory(height, memory)?;
 
-                if no_key1 && no_key2 {
-                    branch.add_node(self.tombstone.unwrap());
-                    continue;
-                }
+            fork.nodes.push(Layer::Shared(layer, cell));
+        }
 
-                if !no_key1 && no_key2 {
-                    branch.add_node(*k1.unwrap());
-                    continue;
-                }
+        fork.orphans.extend_from_slice(&self.orphans);
+        fork.epoch = self.epoch;
+        fork.forked_at = Some(self.epoch);
+        fork.policy = self.policy;
+        fork.tombstone = self.tombstone.clone();
 
-                if no_key1 && !no_key2 {
-                    branch.add_node(*k2.unwrap());
-                    continue;
-                }
+        return Ok(fork);
+    }
 
-                // I don't implicitly convert into an Option<Key> here because I want to explicitly
-                // warn of a diffie-hellman failure
-                let res: Result<Key, crate::errors::ECError> = k1.unwrap().diffie_hellman(k2.unwrap());
+    // Whether `height` is still borrowed from the tree we were forked from
+    pub fn is_shared_layer(&self, height: usize) -> bool {
+        return self.nodes.get(height).map_or(false, |layer| layer.is_shared());
+    }
 
-                match res {
-                    Ok(key) => branch.add_node(key),
-                    Err(_) => {
-                        return Err(RatchetError{
-                            description: "Diffie hellman failed",
-                            cause: RatchetErrorCause::INVALID_INDEX,
-                            index: key_tuple.1,
-                            height: height
-                        });
-                    }
-                }
+    // Copy out what this fork changed, so it can be promoted once the fork is dropped
+    pub fn changes<'caller>(&self, scratch: &'caller AllocatorCell) -> Result<TreeChanges<'caller>, RatchetError<'caller>> {
+        let base: u64 = match self.forked_at {
+            Some(base) => base,
+            None => {
+                return Err(RatchetError{
+                    description: "Only a forked tree has changes to promote",
+                    cause: RatchetErrorCause::INVALID_BRANCH,
+                    index: 0,
+                    height: 0
+                });
             }
+        };
+
+        let mut changes: TreeChanges<'caller> = TreeChanges {
+            base: base,
+            epoch: self.epoch,
+            orphans: BumpVec::with_capacity_in(self.orphans.len(), scratch),
+            layers: BumpVec::new_in(scratch)
+        };
+
+        changes.orphans.extend_from_slice(&self.orphans);
+
+        for (height, layer) in self.nodes.iter().enumerate().filter(|(_, layer)| !layer.is_shared()) {
+            let mut keys: BumpVec<'caller, Key> = BumpVec::with_capacity_in(layer.len(), scratch);
+            keys.extend_from_slice(layer);
+
+            changes.layers.push((height, keys));
         }
 
-        return Ok(branch);
+        return Ok(changes);
     }
 
-    pub fn commit(&mut self, branch: &RatchetBranch, memory: &'tree AllocatorPool) -> Result<&Key, RatchetError> {
-        if branch.len() < self.height() {
+    /*
+    * Take on the changes of a fork of this tree, as if its commits had been made here. Only the layers the
+    * fork copied are written, & the whole promotion is journaled as a single entry so rollback can undo it.
+    * Changes from a fork of an earlier epoch are STALE_EPOCH. Groups with a Roster promote through Roster::promote.
+    */
+    pub fn promote<'caller>(&mut self, changes: &TreeChanges, memory: &'tree AllocatorPool) -> Result<Option<&Key>, RatchetError<'caller>> {
+        if changes.base != self.epoch {
             return Err(RatchetError{
-                description: "Branch & Tree height mismatch: Committing branch would result in desynced state",
-                cause: RatchetErrorCause::INVALID_BRANCH,
-                index: branch.root,
-                height: branch.len()
+                description: "Fork was taken from a different epoch of the tree",
+                cause: RatchetErrorCause::STALE_EPOCH,
+                index: 0,
+                height: 0
             });
         }
 
-        if MEMORY_TREE_START_INDEX + branch.len() > memory.len() {
+        let layers: usize = changes.layers.iter().map(|(height, _)| height + 1).max().unwrap_or(0);
+
+        self.check_growth(changes.layers.iter().flat_map(|(height, layer)| (1..layer.len()).map(move |index| (*height, index))), memory)?;
+
+        if !self.provision_layers(layers, memory) {
             return Err(RatchetError{
                 description: "Not enough memory available in memory_pool for tree",
                 cause: RatchetErrorCause::OOM,
-                index: branch.root,
-                height: 0
+                index: 0,
+                height: layers
             });
-            /*for i in self.memory.len()..MEMORY_TREE_START_INDEX + branch.len() {
-                self.memory.initialize::<Key>(i, 4);
-            }*/
         }
 
-        let mut index: usize = branch.root;
+        self.begin_entry(0);
 
-        if self.orphans.get(0) == Some(&index) {
-            self.orphans.remove(0); // remove for BumpVec shifts elements to the left
+        for (height, layer) in changes.layers.iter() {
+            for index in 1..layer.len() {
+                self.journal.record(*height, index, self.get(*height, index).cloned());
+                self.write_node(*height, index, layer[index].clone(), memory);
+            }
         }
 
-        // If we're committing a deletion, push the index to orphaned indexes for re-use later
-        if branch.nodes.get(0) == self.tombstone.as_ref() {
-            self.orphans.push(index);
+        // The fork may have been shrunk, which always copies its leaf layer
+        if let Some((_, leaves)) = changes.layers.iter().find(|(height, _)| *height == 0) {
+            self.truncate_layers(leaves.len());
         }
 
-        let mut iter: core::slice::Iter<Key> = branch.iter();
-        let mut height: usize = 0;
+        self.orphans.clear();
+        self.orphans.extend_from_slice(&changes.orphans);
+        self.end_entry(false);
+        self.epoch = changes.epoch;
 
-        while let Some(key) = iter.next() {
-            self.ensure_layer_present(height, memory.get_ref(MEMORY_TREE_START_INDEX + height));
-            let layer: &mut BumpVec<Key> = &mut self.nodes[height];
+        return Ok(self.get_root());
+    }
 
-            // Lol Vec.insert shifts elements to the right and there's no nice way to allocate manually
-            if index >= layer.len() {
-                layer.insert(index, *key);
-            } else {
-                layer[index] = *key;
-            }
+    // Number of commits applied to the tree so far
+    pub fn epoch(&self) -> u64 {
+        return self.epoch;
+    }
 
-            height += 1;
-            index = get_next_index(index);
+    pub fn get_next_index(&self) -> usize {
+        match self.policy.select(&self.orphans, self.nodes[0].len() - 1) {
+            Some(orphan) => return orphan,
+            None => return self.nodes[0].len()
         }
+    }
 
-        if height == 0 { height = 1 };
-        return Ok(&self.nodes[height - 1][1]);
+    pub fn set_growth_policy(&mut self, growth: GrowthPolicy) {
+        self.growth = growth;
     }
 
-    // Do not immediately commit the key, return a commit view so we can commit on txn confirmation
+    // Bytes allocated in the tree's own slots, what max_bytes caps. The branch slot it shares isn't counted
+    pub fn allocated_bytes(&self, memory: &AllocatorPool) -> usize {
+        let fixed = [
+            memory.slot::<Layer>(&self.slot_name(SLOT_ROOT_NODES)).map(|slot| slot.index()),
+            memory.slot::<usize>(&self.slot_name(SLOT_ORPHANS)).map(|slot| slot.index()),
+            memory.slot::<JournalEntry>(&self.slot_name(SLOT_JOURNAL)).map(|slot| slot.index())
+        ];
+
+        return fixed.iter().flatten().copied()
+            .chain(self.slots.iter().map(|slot| slot.index()))
+            .filter_map(|index| memory.stats(index).ok())
+            .map(|stats| stats.allocated_bytes)
+            .sum();
+    }
+
+    /*
+    * Make sure there's a slot for each of `layers` layers, registering any missing along with `headroom` more.
+    * The pool grows to fit them, so only failing to grow it is OOM; headroom is best effort.
+    */
+    fn provision_layers(&mut self, layers: usize, memory: &AllocatorPool) -> bool {
+        if layers <= self.slots.len() {
+            return true;
+        }
+
+        while self.slots.len() < layers + self.growth.headroom {
+            let name: alloc::string::String = self.slot_name(&alloc::format!("{}.{}", SLOT_LAYER, self.slots.len()));
+
+            match memory.register_secret::<Key>(&name, self.growth.layer_len) {
+                Ok(slot) => self.slots.push(slot),
+                Err(_) => return self.slots.len() >= layers
+            }
+        }
+
+        return true;
+    }
+
+    /*
+    * Refuse `writes` (height, index) that could take the tree's own slots past max_bytes. On top of what
+    * the slots hold already, a write may copy a shared layer, grow its layer the way BumpVec does to fit
+    * the index, & adds a record to the journal.
+    */
+    fn check_growth<'caller, I: Iterator<Item = (usize, usize)>>(&self, writes: I, memory: &AllocatorPool) -> Result<(), RatchetError<'caller>> {
+        // Length each layer needs to grow to, heights can't go past the bits of an index
+        let mut lengths: [usize; mem::size_of::<usize>() * 8] = [0; mem::size_of::<usize>() * 8];
+        let mut layers: usize = 0;
+        let mut records: usize = 0;
+
+        for (height, index) in writes {
+            lengths[height] = cmp::max(lengths[height], index + 1);
+            layers = cmp::max(layers, height + 1);
+            records += 1;
+        }
+
+        // Slots of missing layers & their headroom are registered, and layers pushed, in the root slot
+        let mut growth: usize = growth_bytes::<Layer>(self.nodes.capacity(), layers);
+
+        if layers > self.slots.len() {
+            growth += growth_bytes::<AllocatorSlot<Key>>(self.slots.capacity(), layers + self.growth.headroom);
+        }
+
+        for (height, length) in lengths[..layers].iter().enumerate() {
+            growth += match self.nodes.get(height) {
+                Some(Layer::Owned(layer)) => growth_bytes::<Key>(layer.capacity(), *length),
+                Some(Layer::Shared(keys, _)) => (keys.len() + 1) * mem::size_of::<Key>() + growth_bytes::<Key>(keys.len() + 1, *length),
+                None => {
+                    let spare: usize = self.spare.iter().find(|(spare, _)| *spare == height).map_or(0, |(_, layer)| layer.capacity());
+
+                    growth_bytes::<Key>(spare, *length)
+                }
+            };
+        }
+
+        growth += growth_bytes::<usize>(self.orphans.capacity(), self.orphans.len() + 1);
+
+        if self.journal.depth > 0 {
+            growth += growth_bytes::<(usize, usize, Option<Key>)>(self.journal.nodes.capacity(), self.journal.nodes.len() + records);
+            growth += growth_bytes::<usize>(self.journal.orphans.capacity(), self.journal.orphans.len() + self.orphans.len());
+        }
+
+        if self.allocated_bytes(memory).saturating_add(growth) > self.growth.max_bytes {
+            return Err(RatchetError{
+                description: "Commit could allocate past the tree's max_bytes",
+                cause: RatchetErrorCause::OOM,
+                index: 0,
+                height: layers
+            });
+        }
+
+        return Ok(());
+    }
+
+    // Memory behind the tree's own slots summed, its branch slot included
+    pub fn memory_stats(&self, memory: &AllocatorPool) -> AllocatorStats {
+        let mut total: AllocatorStats = AllocatorStats::default();
+        let fixed = [
+            memory.slot::<Layer>(&self.slot_name(SLOT_ROOT_NODES)).map(|slot| slot.index()),
+            memory.slot::<usize>(&self.slot_name(SLOT_ORPHANS)).map(|slot| slot.index()),
+            memory.slot::<Key>(&self.slot_name(SLOT_BRANCH)).map(|slot| slot.index()),
+            memory.slot::<JournalEntry>(&self.slot_name(SLOT_JOURNAL)).map(|slot| slot.index())
+        ];
+
+        for index in fixed.iter().flatten().copied().chain(self.slots.iter().map(|slot| slot.index())) {
+            if let Ok(stats) = memory.stats(index) {
+                total.add(&stats);
+            }
+        }
+
+        return total;
+    }
+
+    fn layer_memory<'caller>(&self, height: usize, memory: &'tree AllocatorPool) -> Result<&'tree AllocatorCell, RatchetError<'caller>> {
+        if let Some(cell) = self.slots.get(height).and_then(|slot| memory.get_slot_ref(slot).ok()) {
+            return Ok(cell);
+        }
+
+        return Err(RatchetError{
+            description: "No allocator provisioned for layer",
+            cause: RatchetErrorCause::OOM,
+            index: 0,
+            height: height
+        });
+    }
+
+    // Choose how inserts re-use removed leaves, FifoPolicy by default
+    pub fn set_orphan_policy(&mut self, policy: &'tree dyn OrphanPolicy) {
+        self.policy = policy;
+    }
+
+    pub fn height(&self) -> usize {
+        return height_of(self.nodes[0].len() - 1);
+    }
+
+    // Current group secret, the DH result at the top of the tree
+    pub fn get_root(&self) -> Option<&Key> {
+        return self.get(self.height(), 1);
+    }
+
+    pub fn iter(&self, index: usize) -> RatchetIter {
+        return RatchetIter::new(index, self.height(), 0);
+    }
+
+    pub fn ensure_layer_present(&mut self, height: usize, memory: &'tree AllocatorCell) {
+        if self.nodes.get(height).is_none() {
+            let layer: BumpVec<'tree, Key> = new_layer(&mut self.spare, height, memory);
+
+            self.nodes.insert(height, Layer::Owned(layer));
+        }
+    }
+
+    // Drop every layer from `layers` up, keeping the ones we own as spares
+    fn drop_layers(&mut self, layers: usize) {
+        for (height, layer) in (layers..).zip(self.nodes.drain(layers..)) {
+            if let Layer::Owned(mut layer) = layer {
+                layer.truncate(1);
+                self.spare.push((height, layer));
+            }
+        }
+    }
+
+    pub fn ratchet<'caller>(&self, index: usize, key: &Key, scratch: &'caller AllocatorCell) -> Result<RatchetBranch<'caller>, RatchetError<'caller>> {
+        return self.ratchet_over(index, key, &[], scratch);
+    }
+
+    /*
+    * Ratchet as if every branch in `overlay` had already been committed to the tree: any sibling lying
+    * on an overlay's path is read from the overlay instead of our layers. Used to work out our own path
+    * against a remote update before we write anything.
+    */
+    fn ratchet_over<'caller>(&self, index: usize, key: &Key, overlay: &[RatchetBranch], scratch: &'caller AllocatorCell) -> Result<RatchetBranch<'caller>, RatchetError<'caller>> {
+        let height: usize = overlay.iter().map(|o| o.len().saturating_sub(1)).fold(self.height(), core::cmp::max);
+
+        let mut iterator: RatchetIter = RatchetIter::new(index, height, 0);
+        let mut branch: RatchetBranch<'caller> = RatchetBranch::new(
+            scratch,
+            index
+        );
+        branch.epoch = self.epoch;
+
+        // Root of the branch is our node
+        branch.add_node(key.clone());
+
+        // Two phase commit
+        while let Some(key_tuple) = iterator.next() {
+            let height: usize = key_tuple.0;
+
+            if let Some(layer) = self.nodes.get(height) {
+                // Seed Key1 from previous DH result, if available
+                let k1: Option<&Key> = branch.get_last();
+                let k2: Option<&Key> = match overlay.iter().find_map(|o| o.get_at(height, key_tuple.2)) {
+                    Some(k) => Some(k),
+                    None => layer.get(key_tuple.2) // Key 2
+                };
+
+                match ratchet_node(k1, k2, self.tombstone.as_ref()) {
+                    Ok(key) => branch.add_node(key),
+                    Err(_) => {
+                        return Err(RatchetError{
+                            description: "Diffie hellman failed",
+                            cause: RatchetErrorCause::INVALID_INDEX,
+                            index: key_tuple.1,
+                            height: height
+                        });
+                    }
+                }
+            }
+        }
+
+        return Ok(branch);
+    }
+
+    /*
+    * Write a branch into the tree & move on to the next epoch. Branches computed against any other
+    * epoch are rejected with STALE_EPOCH, they need re-ratcheting against the current tree first.
+    * A different branch for the epoch we just committed is a FORK instead, see resolve_fork.
+    */
+    pub fn commit<'caller>(&mut self, branch: &RatchetBranch, memory: &'tree AllocatorPool) -> Result<&Key, RatchetError<'caller>> {
+        self.commit_recorded(branch, memory)?;
+
+        return Ok(self.committed_root());
+    }
+
+    fn commit_recorded<'caller>(&mut self, branch: &RatchetBranch, memory: &'tree AllocatorPool) -> Result<(), RatchetError<'caller>> {
+        self.check_epoch(branch)?;
+        self.check_branch(branch, memory)?;
+        self.begin_entry(branch.root);
+        self.write_branch(branch, memory)?;
+        self.end_entry(true);
+
+        return Ok(());
+    }
+
+    // Root once a commit has gone through, the tombstone if it removed the last leaf & shrunk the tree away
+    fn committed_root(&self) -> &Key {
+        return self.get_root().or(self.tombstone.as_ref()).unwrap();
+    }
+
+    fn check_epoch<'caller>(&self, branch: &RatchetBranch) -> Result<(), RatchetError<'caller>> {
+        if branch.epoch == self.epoch {
+            return Ok(());
+        }
+
+        if self.is_fork(branch) {
+            return Err(RatchetError{
+                description: "Branch conflicts with the branch already committed for its epoch",
+                cause: RatchetErrorCause::FORK,
+                index: branch.root,
+                height: 0
+            });
+        }
+
+        return Err(RatchetError{
+            description: "Branch was computed against a different epoch of the tree",
+            cause: RatchetErrorCause::STALE_EPOCH,
+            index: branch.root,
+            height: 0
+        });
+    }
+
+    // A branch for the epoch we last committed, which isn't the branch we committed
+    fn is_fork(&self, branch: &RatchetBranch) -> bool {
+        let ours: &JournalEntry = match self.journal.last() {
+            Some(entry) if entry.resolvable => entry,
+            _ => return false
+        };
+
+        if branch.epoch + 1 != self.epoch {
+            return false;
+        }
+
+        return branch.root != ours.root || branch.get_last() != self.get_root();
+    }
+
+    fn begin_entry(&mut self, root: usize) {
+        self.journal.begin(root, self.epoch, self.nodes.len(), &self.orphans);
+    }
+
+    fn end_entry(&mut self, resolvable: bool) {
+        self.shrink();
+
+        if let Some(entry) = self.journal.entries.last_mut() {
+            entry.resolvable = resolvable;
+        }
+
+        self.epoch += 1;
+    }
+
+    /*
+    * Drop trailing tombstoned leaves along with the tombstoned nodes above them & any top layers left
+    * empty. Every parent of a fully tombstoned subtree passes its other child through, so the root is
+    * unchanged & nothing is re-ratcheted. Run by every commit as part of its own journal entry, so each
+    * member shrinks at the same epoch & replaying the commit shrinks the same way.
+    */
+    fn shrink(&mut self) {
+        let mut leaves: usize = self.nodes[0].len();
+
+        while leaves > 1 && Some(&self.nodes[0][leaves - 1]) == self.tombstone.as_ref() {
+            leaves -= 1;
+        }
+
+        if leaves == self.nodes[0].len() {
+            return;
+        }
+
+        self.orphans.retain(|orphan| *orphan < leaves);
+        self.truncate_layers(leaves);
+    }
+
+    // Cut the tree down to a leaf layer of length `leaves`, journaling every node removed
+    fn truncate_layers(&mut self, leaves: usize) {
+        let size: usize = leaves - 1;
+        let layers: usize = if size <= 1 { 1 } else { (size as f64).log(2.0).ceil() as usize + 1 };
+
+        // Top down & right to left, so a rollback re-creates layers & nodes in order
+        for height in (0..self.nodes.len()).rev() {
+            let mut keep: usize = if height < layers { leaves } else { 1 };
+
+            if height < layers {
+                for _ in 0..height {
+                    keep = get_next_index(keep - 1) + 1;
+                }
+            }
+
+            for index in (keep..self.nodes[height].len()).rev() {
+                self.journal.record(height, index, self.get(height, index).cloned());
+            }
+
+            if keep < self.nodes[height].len() {
+                self.nodes[height].to_mut().truncate(keep);
+            }
+        }
+
+        self.drop_layers(layers);
+    }
+
+    // Number of commits rollback can currently undo
+    pub fn journal_len(&self) -> usize {
+        return self.journal.entries.len();
+    }
+
+    // Epoch the oldest journaled commit was made at, as far back as rollback can currently go
+    pub fn journal_base(&self) -> u64 {
+        return self.journal.entries.first().map_or(self.epoch, |entry| entry.epoch);
+    }
+
+    // Leaf `index` as it was before the last journaled commit, see Roster::resolve_fork
+    pub fn last_leaf(&self, index: usize) -> Option<&Key> {
+        match self.journal.last_nodes().iter().find(|(height, i, _)| *height == 0 && *i == index) {
+            Some((_, _, previous)) => return previous.as_ref(),
+            None => return self.get(0, index)
+        }
+    }
+
+    // Keep at most `depth` commits in the undo journal, dropping the oldest. 0 turns journaling (and resolve_fork) off
+    pub fn set_journal_depth(&mut self, depth: usize) {
+        self.journal.depth = depth;
+        self.journal.trim(depth);
+    }
+
+    /*
+    * Undo the last `n` commits, newest first. Every replaced node, the orphan list (and with it the
+    * next free index), any layer the commits grew and the epoch go back to exactly what they were.
+    * Fails with JOURNAL_EXHAUSTED, leaving the tree untouched, if fewer than `n` commits are journaled.
+    * Groups with a Roster roll back through Roster::rollback, so member bindings go back too.
+    */
+    pub fn rollback<'caller>(&mut self, n: usize) -> Result<u64, RatchetError<'caller>> {
+        if n > self.journal.entries.len() {
+            return Err(RatchetError{
+                description: "Cannot roll back further than the undo journal reaches",
+                cause: RatchetErrorCause::JOURNAL_EXHAUSTED,
+                index: 0,
+                height: n
+            });
+        }
+
+        for _ in 0..n {
+            self.revert_entry();
+        }
+
+        return Ok(self.epoch);
+    }
+
+    // Undo the last journaled commit, putting back every node, the orphan list, the layers & the epoch
+    fn revert_entry(&mut self) {
+        // BumpVec::pop trips over bumpalo's own precondition checks, truncate instead
+        let entry: JournalEntry = *self.journal.entries.last().unwrap();
+        let nodes: usize = self.journal.nodes.len() - entry.nodes;
+        let orphans: usize = self.journal.orphans.len() - entry.orphans;
+
+        for (height, index, key) in self.journal.nodes[nodes..].iter().rev() {
+            match key {
+                Some(key) => {
+                    // Put back a layer dropped by shrinking
+                    if self.nodes.get(*height).is_none() {
+                        // A layer keeps its slot once it's had one
+                        let memory: &'tree AllocatorCell = self.layer_memory(*height, self.journal.memory).expect("Dropped layer lost its slot");
+
+                        self.nodes.push(Layer::Owned(new_layer(&mut self.spare, *height, memory)));
+                    }
+
+                    let layer: &mut BumpVec<Key> = self.nodes[*height].to_mut();
+
+                    if *index >= layer.len() {
+                        layer.push(key.clone());
+                    } else {
+                        layer[*index] = key.clone();
+                    }
+                },
+                None => self.nodes[*height].to_mut().truncate(*index)
+            }
+        }
+
+        self.drop_layers(entry.layers);
+        self.orphans.clear();
+        self.orphans.extend_from_slice(&self.journal.orphans[orphans..]);
+        self.journal.entries.truncate(self.journal.entries.len() - 1);
+        self.journal.nodes.truncate(nodes);
+        self.journal.orphans.truncate(orphans);
+        self.epoch = entry.epoch;
+    }
+
+    /*
+    * Settle a FORK: `branch` was built for the epoch we already committed a different branch at.
+    * The lowest leaf index wins, ties (the same leaf updated twice) go to the lowest root public key.
+    * When the incoming branch wins ours is undone and it's applied in its place, via apply_update as the
+    * member owning leaf `index` if given, or commit otherwise. Either way every member ends up on the
+    * same tree, and the losing leaf has to be re-ratcheted with rebase. See Roster::resolve_fork for groups with a Roster.
+    */
+    pub fn resolve_fork<'caller>(&mut self, branch: &RatchetBranch, index: Option<usize>, memory: &'tree AllocatorPool, scratch: &'caller AllocatorCell) -> Result<ForkResolution, RatchetError<'caller>> {
+        if !self.is_fork(branch) {
+            return Err(RatchetError{
+                description: "Branch does not conflict with the last committed epoch",
+                cause: RatchetErrorCause::STALE_EPOCH,
+                index: branch.root,
+                height: 0
+            });
+        }
+
+        let ours: usize = self.journal.last().unwrap().root;
+        let ours_root: Key = self.get_root().unwrap().clone();
+        let theirs_root: &Key = branch.get_last().unwrap_or(self.tombstone.as_ref().unwrap());
+
+        let incoming_wins: bool = match branch.root.cmp(&ours) {
+            Ordering::Less => true,
+            Ordering::Greater => false,
+            Ordering::Equal => theirs_root.pk.to_encoded_point(true).as_bytes() < ours_root.pk.to_encoded_point(true).as_bytes()
+        };
+
+        if !incoming_wins {
+            return Ok(ForkResolution::KEPT);
+        }
+
+        // Keep enough around to put our commit back should the incoming branch fail to apply
+        let mut redo: BumpVec<'caller, (usize, usize, Key)> = BumpVec::with_capacity_in(self.journal.last_nodes().len(), scratch);
+        let mut orphans: BumpVec<'caller, usize> = BumpVec::with_capacity_in(self.orphans.len(), scratch);
+
+        // Nodes our commit shrank away go back as they were just before, redoing the commit shrinks again
+        for (height, index, previous) in self.journal.last_nodes().iter() {
+            if let Some(key) = self.get(*height, *index).or(previous.as_ref()) {
+                redo.push((*height, *index, key.clone()));
+            }
+        }
+
+        orphans.extend_from_slice(&self.orphans);
+        self.revert_entry();
+
+        let applied: Result<(), RatchetError<'caller>> = match index {
+            Some(index) => self.apply_update(branch, index, memory, scratch).map(|_| ()),
+            None => self.commit_recorded(branch, memory).map(|_| ())
+        };
+
+        if let Err(e) = applied {
+            self.begin_entry(ours);
+
+            for (height, index, key) in redo.iter() {
+                self.journal.record(*height, *index, self.get(*height, *index).cloned());
+                self.write_node(*height, *index, key.clone(), memory);
+            }
+
+            self.orphans.clear();
+            self.orphans.extend_from_slice(&orphans);
+            self.end_entry(true);
+
+            return Err(e);
+        }
+
+        return Ok(ForkResolution::REPLACED { loser: ours });
+    }
+
+    /*
+    * Re-ratchet the leaf of a branch that lost a fork on top of the winner, as the member owning it.
+    * If the winner took the very leaf we were inserting into, we insert into the next free leaf instead.
+    */
+    pub fn rebase<'caller>(&self, lost: &RatchetBranch, scratch: &'caller AllocatorCell) -> Result<RatchetBranch<'caller>, RatchetError<'caller>> {
+        let leaf: &Key = match lost.get_node(0) {
+            Some(leaf) => leaf,
+            None => {
+                return Err(RatchetError{
+                    description: "Cannot rebase an empty branch",
+                    cause: RatchetErrorCause::INVALID_BRANCH,
+                    index: lost.root,
+                    height: 0
+                });
+            }
+        };
+
+        let tombstone: Option<&Key> = self.tombstone.as_ref();
+        // Only the first record of the leaf counts, a shrink records it again once it's been written
+        let taken: bool = self.journal.last().map_or(false, |entry| entry.resolvable && entry.root == lost.root) && self.journal.last_nodes().iter()
+            .find(|(height, index, _)| *height == 0 && *index == lost.root)
+            .map_or(false, |(_, _, key)| key.is_none() || key.as_ref() == tombstone);
+
+        if taken && Some(leaf) != tombstone {
+            return self.insert(leaf, scratch);
+        }
+
+        return self.ratchet(lost.root, leaf, scratch);
+    }
+
+    fn check_branch<'caller>(&mut self, branch: &RatchetBranch, memory: &'tree AllocatorPool) -> Result<(), RatchetError<'caller>> {
+        if branch.len() < self.height() {
+            return Err(RatchetError{
+                description: "Branch & Tree height mismatch: Committing branch would result in desynced state",
+                cause: RatchetErrorCause::INVALID_BRANCH,
+                index: branch.root,
+                height: branch.len()
+            });
+        }
+
+        self.check_growth(branch_writes(branch.root, branch.len()), memory)?;
+
+        if !self.provision_layers(branch.len(), memory) {
+            return Err(RatchetError{
+                description: "Not enough memory available in memory_pool for tree",
+                cause: RatchetErrorCause::OOM,
+                index: branch.root,
+                height: 0
+            });
+        }
+
+        return Ok(());
+    }
+
+    // Write every node of `branch` without touching the epoch, returns the number of layers written to.
+    // Replaced values are added to the open journal entry
+    fn write_branch<'caller>(&mut self, branch: &RatchetBranch, memory: &'tree AllocatorPool) -> Result<usize, RatchetError<'caller>> {
+        let mut index: usize = branch.root;
+
+        // Whichever orphan the policy picked, it's no longer free
+        if let Some(position) = self.orphans.iter().position(|orphan| *orphan == index) {
+            self.orphans.remove(position); // remove for BumpVec shifts elements to the left
+        }
+
+        // If we're committing a deletion, push the index to orphaned indexes for re-use later
+        if branch.nodes.get(0) == self.tombstone.as_ref() {
+            self.orphans.push(index);
+        }
+
+        let mut iter: core::slice::Iter<Key> = branch.iter();
+        let mut height: usize = 0;
+
+        while let Some(key) = iter.next() {
+            self.journal.record(height, index, self.get(height, index).cloned());
+            self.write_node(height, index, key.clone(), memory);
+
+            height += 1;
+            index = get_next_index(index);
+        }
+
+        if height == 0 { height = 1 };
+        return Ok(height);
+    }
+
+    /*
+    * Commit several branches computed concurrently against this tree as one epoch.
+    * Branches are merged in ascending leaf order, and each affected node is worked out once: a node only
+    * one branch leads to keeps that branch's value, a node several branches lead to is recomputed from its
+    * merged children, which needs the secret of at least one of them (MISSING_SECRET otherwise). A branch inserting past the last power of two grows the tree, & every other branch then
+    * leads up to the new root as well. Nothing is written unless every branch merges. Returns the merged
+    * path of each branch, public keys only and in the same leaf order, stamped with the epoch they were
+    * merged at.
+    * Other members take the returned update with apply_batch, which re-derives their own path up to the
+    * new root from their leaf secret, the same way apply_update does for a single branch. An update that
+    * has already been merged is never merged again, see apply_merged. Batches that insert or remove
+    * members go through Roster::commit_batch & Roster::apply_batch.
+    */
+    pub fn commit_batch<'caller>(&mut self, branches: &[RatchetBranch], memory: &'tree AllocatorPool, scratch: &'caller AllocatorCell) -> Result<BumpVec<'caller, RatchetBranch<'caller>>, RatchetError<'caller>> {
+        let (order, height) = self.check_batch(branches, false, scratch)?;
+
+        // Merged (index, key) pairs per height, ascending by index
+        let mut merged: BumpVec<'caller, BumpVec<'caller, (usize, Key)>> = BumpVec::with_capacity_in(height + 1, scratch);
+        let mut leaves: BumpVec<'caller, (usize, Key)> = BumpVec::with_capacity_in(order.len(), scratch);
+
+        for branch in order.iter().map(|i| &branches[*i]) {
+            leaves.push((branch.root, branch.nodes[0].clone()));
+        }
+
+        merged.push(leaves);
+
+        for h in 1..height + 1 {
+            let mut layer: BumpVec<'caller, (usize, Key)> = BumpVec::new_in(scratch);
+
+            for (child, _) in merged[h - 1].iter() {
+                let index: usize = get_next_index(*child);
+
+                if layer.last().map_or(false, |(i, _)| *i == index) {
+                    continue;
+                }
+
+                // Branches shorter than the grown tree still lead to the nodes above their top
+                let mut passing = order.iter().map(|i| &branches[*i]).filter(|branch| branch.passes(h, index));
+                let first: &RatchetBranch = passing.next().unwrap();
+
+                if passing.next().is_none() {
+                    if let Some(key) = first.get_node(h) {
+                        layer.push((index, key.clone()));
+                        continue;
+                    }
+                }
+
+                let below: &BumpVec<(usize, Key)> = &merged[h - 1];
+                let k1: Option<&Key> = find_node(below, index * 2 - 1).or(self.get(h - 1, index * 2 - 1));
+                let k2: Option<&Key> = find_node(below, index * 2).or(self.get(h - 1, index * 2));
+
+                match ratchet_node(k1, k2, self.tombstone.as_ref()) {
+                    Ok(key) => layer.push((index, key)),
+                    Err(_) => {
+                        return Err(RatchetError{
+                            description: "Merging branches needs the secret of a child we do not hold",
+                            cause: RatchetErrorCause::MISSING_SECRET,
+                            index: index,
+                            height: h
+                        });
+                    }
+                }
+            }
+
+            merged.push(layer);
+        }
+
+        self.check_growth(merged.iter().enumerate().flat_map(|(h, layer)| layer.iter().map(move |(index, _)| (h, *index))), memory)?;
+
+        if !self.provision_layers(height + 1, memory) {
+            return Err(RatchetError{
+                description: "Not enough memory available in memory_pool for tree",
+                cause: RatchetErrorCause::OOM,
+                index: 0,
+                height: height
+            });
+        }
+
+        self.begin_entry(0);
+
+        for (h, layer) in merged.iter().enumerate() {
+            for (index, key) in layer.iter() {
+                self.journal.record(h, *index, self.get(h, *index).cloned());
+                self.write_node(h, *index, key.clone(), memory);
+            }
+        }
+
+        for (index, leaf) in merged[0].iter() {
+            let orphan: Option<usize> = self.orphans.iter().position(|o| o == index);
+
+            if Some(leaf) == self.tombstone.as_ref() {
+                if orphan.is_none() {
+                    self.orphans.push(*index);
+                }
+            } else if let Some(position) = orphan {
+                self.orphans.remove(position);
+            }
+        }
+
+        // Batches can be rolled back, but can't be unpicked by resolve_fork
+        self.end_entry(false);
+
+        let mut update: BumpVec<'caller, RatchetBranch<'caller>> = BumpVec::with_capacity_in(order.len(), scratch);
+
+        for branch in order.iter().map(|i| &branches[*i]) {
+            let mut public: RatchetBranch<'caller> = RatchetBranch::new(scratch, branch.root);
+            let mut index: usize = branch.root;
+
+            public.epoch = branch.epoch;
+
+            for layer in merged.iter() {
+                public.add_node(Key::from(find_node(layer, index).unwrap().pk));
+                index = get_next_index(index);
+            }
+
+            update.push(public);
+        }
+
+        return Ok(update);
+    }
+
+    /*
+    * Apply an update returned by another member's commit_batch, as the member owning leaf `index`.
+    * Only the public keys of the update are written. Our own path is re-derived from our leaf secret
+    * against every merged branch at once, so we hold the secret of every node from our leaf up to the
+    * new root afterwards, including the nodes the merge recomputed. If our own branch was part of the
+    * batch, pass it (with its leaf secret) in place of its public copy, otherwise the leaf we hold is used.
+    * Returns the new root.
+    */
+    pub fn apply_batch<'caller>(&mut self, update: &[RatchetBranch], index: usize, memory: &'tree AllocatorPool, scratch: &'caller AllocatorCell) -> Result<&Key, RatchetError<'caller>> {
+        let (order, height) = self.check_batch(update, true, scratch)?;
+
+        let leaf: Key = match update.iter().find(|branch| branch.root == index).and_then(|branch| branch.get_node(0)).or(self.get(0, index)) {
+            Some(key) if key.sk.is_some() => key.clone(),
+            _ => {
+                return Err(RatchetError{
+                    description: "No secret key available for our own leaf",
+                    cause: RatchetErrorCause::INVALID_INDEX,
+                    index: index,
+                    height: 0
+                });
+            }
+        };
+
+        let own: RatchetBranch<'caller> = self.ratchet_over(index, &leaf, update, scratch)?;
+        let mut node_index: usize = index;
+
+        for (h, key) in own.iter().enumerate() {
+            if update.iter().filter_map(|branch| branch.get_at(h, node_index)).any(|remote| remote != key) {
+                return Err(RatchetError{
+                    description: "Remote update does not agree with the path derived from our leaf",
+                    cause: RatchetErrorCause::INVALID_BRANCH,
+                    index: node_index,
+                    height: h
+                });
+            }
+
+            node_index = get_next_index(node_index);
+        }
+
+        if own.len() != height + 1 {
+            return Err(RatchetError{
+                description: "Remote update path length does not match tree height",
+                cause: RatchetErrorCause::INVALID_BRANCH,
+                index: index,
+                height: own.len()
+            });
+        }
+
+        self.check_growth(update.iter().chain(core::iter::once(&own)).flat_map(|branch| branch_writes(branch.root, branch.len())), memory)?;
+        self.check_branch(&own, memory)?;
+        self.begin_entry(0);
+
+        for branch in order.iter().map(|i| &update[*i]) {
+            let mut public: RatchetBranch<'caller> = RatchetBranch::new(scratch, branch.root);
+
+            for key in branch.iter() {
+                public.add_node(Key::from(key.pk));
+            }
+
+            self.write_branch(&public, memory)?;
+        }
+
+        self.write_branch(&own, memory)?;
+        self.end_entry(false);
+
+        return Ok(self.committed_root());
+    }
+
+    /*
+    * Write an update already merged by commit_batch exactly as it is, public keys only, as one epoch.
+    * Nothing is recomputed, so no secrets are needed & none are derived. Used to rebuild a public tree,
+    * see OperationLog::replay; members following the group take the update with apply_batch instead.
+    */
+    pub fn apply_merged<'caller>(&mut self, update: &[RatchetBranch], memory: &'tree AllocatorPool, scratch: &'caller AllocatorCell) -> Result<&Key, RatchetError<'caller>> {
+        let (order, height) = self.check_batch(update, true, scratch)?;
+
+        self.check_growth(update.iter().flat_map(|branch| branch_writes(branch.root, branch.len())), memory)?;
+
+        if !self.provision_layers(height + 1, memory) {
+            return Err(RatchetError{
+                description: "Not enough memory available in memory_pool for tree",
+                cause: RatchetErrorCause::OOM,
+                index: 0,
+                height: height
+            });
+        }
+
+        self.begin_entry(0);
+
+        for branch in order.iter().map(|i| &update[*i]) {
+            let mut public: RatchetBranch<'caller> = RatchetBranch::new(scratch, branch.root);
+
+            for key in branch.iter() {
+                public.add_node(Key::from(key.pk));
+            }
+
+            self.write_branch(&public, memory)?;
+        }
+
+        self.end_entry(false);
+
+        return Ok(self.committed_root());
+    }
+
+    /*
+    * Check a batch of branches can be committed together at this epoch, returning their indexes in leaf
+    * order & the height of the tree once they are. Fresh branches are as long as ratchet makes them against
+    * this tree, only the one inserting past the last power of two reaches a layer higher. Branches `merged`
+    * by commit_batch all reach the top of the grown tree, and agree on every node they share.
+    */
+    fn check_batch<'caller>(&self, branches: &[RatchetBranch], merged: bool, scratch: &'caller AllocatorCell) -> Result<(BumpVec<'caller, usize>, usize), RatchetError<'caller>> {
+        let mut order: BumpVec<'caller, usize> = BumpVec::with_capacity_in(branches.len(), scratch);
+
+        for i in 0..branches.len() {
+            order.push(i);
+        }
+
+        order.sort_by_key(|i| branches[*i].root);
+
+        let grown: usize = branches.iter().map(|branch| height_of(branch.root)).fold(self.height(), core::cmp::max);
+
+        for (i, branch) in order.iter().map(|i| &branches[*i]).enumerate() {
+            if branch.root == 0 || branch.root > self.get_layer_len(0) {
+                return Err(RatchetError{
+                    description: "index provided larger than leaf-node array len",
+                    cause: RatchetErrorCause::INVALID_INDEX,
+                    index: branch.root,
+                    height: 0
+                });
+            }
+
+            if branch.epoch != self.epoch {
+                return Err(RatchetError{
+                    description: "Branch was computed against a different epoch of the tree",
+                    cause: RatchetErrorCause::STALE_EPOCH,
+                    index: branch.root,
+                    height: 0
+                });
+            }
+
+            let height: usize = if merged { grown } else { core::cmp::max(self.height(), height_of(branch.root)) };
+
+            if branch.len() != height + 1 || (i > 0 && branches[order[i - 1]].root == branch.root) {
+                return Err(RatchetError{
+                    description: "Batched branches must be distinct & computed against the current tree",
+                    cause: RatchetErrorCause::INVALID_BRANCH,
+                    index: branch.root,
+                    height: branch.len()
+                });
+            }
+        }
+
+        if merged {
+            for (a, b) in order.iter().enumerate().flat_map(|(i, a)| order[i + 1..].iter().map(move |b| (*a, *b))) {
+                let (a, b) = (&branches[a], &branches[b]);
+                let mut index: usize = a.root;
+
+                for h in 0..a.len() {
+                    if b.get_at(h, index).map_or(false, |key| key != &a.nodes[h]) {
+                        return Err(RatchetError{
+                            description: "Merged branches disagree on a node they share",
+                            cause: RatchetErrorCause::INCONSISTENT_NODE,
+                            index: index,
+                            height: h
+                        });
+                    }
+
+                    index = get_next_index(index);
+                }
+            }
+        }
+
+        return Ok((order, grown));
+    }
+
+    fn write_node(&mut self, height: usize, index: usize, key: Key, memory: &'tree AllocatorPool) {
+        // Always provisioned before the first write to a layer, see provision_layers
+        if self.nodes.get(height).is_none() {
+            self.ensure_layer_present(height, self.layer_memory(height, memory).expect("Layer written to before it was provisioned"));
+        }
+
+        let layer: &mut BumpVec<Key> = self.nodes[height].to_mut();
+
+        // Lol Vec.insert shifts elements to the right and there's no nice way to allocate manually
+        if index >= layer.len() {
+            layer.insert(index, key);
+        } else {
+            layer[index] = key;
+        }
+    }
+
+    /*
+    * Create a whole group in one go, as the initiator.
+    * Each member's leaf is the DH of our setup key with their published prekey, so only they (and us)
+    * can derive it. We hold every secret in the resulting tree; members rebuild their own view from
+    * the returned RatchetSetup via RatchetTree::from_setup.
+    */
+    pub fn setup<'caller>(memory: &'tree AllocatorPool, initiator: &Key, setup_key: &Key, prekeys: &[Key], scratch: &'caller AllocatorCell) -> Result<(Self, RatchetSetup<'caller>), RatchetError<'caller>> {
+        let mut setup: RatchetSetup<'caller> = RatchetSetup {
+            setup_key: Key::from(setup_key.pk),
+            prekeys: BumpVec::with_capacity_in(prekeys.len(), scratch),
+            nodes: BumpVec::new_in(scratch)
+        };
+
+        let mut leaves: BumpVec<'caller, Key> = BumpVec::with_capacity_in(prekeys.len() + 1, scratch);
+        leaves.push(initiator.clone());
+
+        for (i, prekey) in prekeys.iter().enumerate() {
+            let leaf: Key = match setup_key.diffie_hellman(prekey) {
+                Ok(key) => key,
+                Err(_) => {
+                    return Err(RatchetError{
+                        description: "Setup key unable to Diffie-Hellman with member prekey",
+                        cause: RatchetErrorCause::INVALID_INDEX,
+                        index: i + 2,
+                        height: 0
+                    });
+                }
+            };
+
+            leaves.push(leaf);
+            setup.prekeys.push(Key::from(prekey.pk));
+        }
+
+        let tree: RatchetTree<'tree> = RatchetTree::from_leaves(memory, &leaves)?;
+
+        for layer in tree.nodes.iter() {
+            let mut public_layer: BumpVec<'caller, Key> = BumpVec::with_capacity_in(layer.len(), scratch);
+
+            for key in layer.iter() {
+                public_layer.push(Key::from(key.pk));
+            }
+
+            setup.nodes.push(public_layer);
+        }
+
+        return Ok((tree, setup));
+    }
+
+    /*
+    * Build a tree from `leaves` in one pass, leaf i at index i + 1. Each layer is computed from the
+    * one below it with the same rules as ratchet, so every internal node is worked out exactly once
+    * rather than re-ratcheting a full path per leaf. The result is identical to inserting & committing
+    * each leaf in order. Tombstoned leaves are recorded as orphans, ready for re-use.
+    */
+    pub fn from_leaves<'caller>(memory: &'tree AllocatorPool, leaves: &[Key]) -> Result<Self, RatchetError<'caller>> {
+        let mut tree: RatchetTree<'tree> = RatchetTree::new(memory)?;
+
+        if leaves.is_empty() {
+            return Ok(tree);
+        }
+
+        let height: usize = height_of(leaves.len());
+
+        if !tree.provision_layers(height + 1, memory) {
+            return Err(RatchetError{
+                description: "Not enough memory available in memory_pool for tree",
+                cause: RatchetErrorCause::OOM,
+                index: leaves.len(),
+                height: height
+            });
+        }
+
+        tree.nodes[0].to_mut().reserve(leaves.len());
+
+        for (i, leaf) in leaves.iter().enumerate() {
+            tree.nodes[0].to_mut().push(leaf.clone());
+
+            if Some(leaf) == tree.tombstone.as_ref() {
+                tree.orphans.push(i + 1);
+            }
+        }
+
+        for h in 1..height + 1 {
+            let children: usize = tree.nodes[h - 1].len() - 1;
+            let count: usize = get_next_index(children);
+
+            tree.ensure_layer_present(h, tree.layer_memory(h, memory)?);
+            tree.nodes[h].to_mut().reserve(count);
+
+            for index in 1..count + 1 {
+                let k1: Option<&Key> = tree.nodes[h - 1].get(index * 2 - 1);
+                let k2: Option<&Key> = tree.nodes[h - 1].get(index * 2);
+                let parent: Result<Key, crate::errors::ECError> = ratchet_node(k1, k2, tree.tombstone.as_ref());
+
+                match parent {
+                    Ok(key) => tree.nodes[h].to_mut().push(key),
+                    Err(_) => {
+                        return Err(RatchetError{
+                            description: "Diffie hellman failed",
+                            cause: RatchetErrorCause::INVALID_INDEX,
+                            index: index,
+                            height: h
+                        });
+                    }
+                }
+            }
+        }
+
+        return Ok(tree);
+    }
+
+    /*
+    * Rebuild a group from the initiator's RatchetSetup, as the member owning `prekey`.
+    * The public tree is written as-is, then our leaf and the secrets along our own path are
+    * recomputed and checked against the public keys the initiator sent.
+    */
+    pub fn from_setup<'caller>(memory: &'tree AllocatorPool, setup: &RatchetSetup, prekey: &Key, scratch: &'caller AllocatorCell) -> Result<Self, RatchetError<'caller>> {
+        let index: usize = match setup.prekeys.iter().position(|k| k == prekey) {
+            Some(i) => i + 2,
+            None => {
+                return Err(RatchetError{
+                    description: "Prekey not found in group setup",
+                    cause: RatchetErrorCause::INVALID_INDEX,
+                    index: 0,
+                    height: 0
+                });
+            }
+        };
+
+        let mut tree: RatchetTree<'tree> = RatchetTree::new(memory)?;
+
+        if !tree.provision_layers(setup.nodes.len(), memory) {
+            return Err(RatchetError{
+                description: "Not enough memory available in memory_pool for tree",
+                cause: RatchetErrorCause::OOM,
+                index: index,
+                height: 0
+            });
+        }
+
+        let leaf: Key = match prekey.diffie_hellman(&setup.setup_key) {
+            Ok(key) => key,
+            Err(_) => {
+                return Err(RatchetError{
+                    description: "Prekey unable to Diffie-Hellman with setup key",
+                    cause: RatchetErrorCause::INVALID_INDEX,
+                    index: index,
+                    height: 0
+                });
+            }
+        };
+
+        for (height, layer) in setup.nodes.iter().enumerate() {
+            for i in 1..layer.len() {
+                tree.write_node(height, i, Key::from(layer[i].pk), memory);
+            }
+        }
+
+        let branch: RatchetBranch<'caller> = tree.ratchet(index, &leaf, scratch)?;
+        let mut node_index: usize = index;
+
+        for (height, key) in branch.iter().enumerate() {
+            if tree.get(height, node_index) != Some(key) {
+                return Err(RatchetError{
+                    description: "Group setup does not match the path derived from our prekey",
+                    cause: RatchetErrorCause::INVALID_BRANCH,
+                    index: node_index,
+                    height: height
+                });
+            }
+
+            node_index = get_next_index(node_index);
+        }
+
+        tree.write_branch(&branch, memory)?;
+
+        return Ok(tree);
+    }
+
+    /*
+    * Apply a branch committed by another member, as the member owning leaf `index`.
+    * Only the public keys of the incoming branch are used. Our own path is re-derived from our leaf
+    * secret against the incoming branch, and wherever the two paths meet the public keys must agree
+    * before anything is written. Returns the new root, which we now hold the secret for.
+    */
+    pub fn apply_update<'caller>(&mut self, update: &RatchetBranch, index: usize, memory: &'tree AllocatorPool, scratch: &'caller AllocatorCell) -> Result<&Key, RatchetError<'caller>> {
+        if update.root == index {
+            return Err(RatchetError{
+                description: "Remote update targets our own leaf",
+                cause: RatchetErrorCause::INVALID_INDEX,
+                index: update.root,
+                height: 0
+            });
+        }
+
+        if update.root == 0 || update.root > self.get_layer_len(0) {
+            return Err(RatchetError{
+                description: "Remote update leaf is outside of the tree",
+                cause: RatchetErrorCause::INVALID_INDEX,
+                index: update.root,
+                height: 0
+            });
+        }
+
+        if update.len() != self.iter(update.root).count() + 1 {
+            return Err(RatchetError{
+                description: "Remote update path length does not match tree height",
+                cause: RatchetErrorCause::INVALID_BRANCH,
+                index: update.root,
+                height: update.len()
+            });
+        }
+
+        self.check_epoch(update)?;
+
+        let leaf: Key = match self.get(0, index) {
+            Some(key) if key.sk.is_some() => key.clone(),
+            _ => {
+                return Err(RatchetError{
+                    description: "No secret key available for our own leaf",
+                    cause: RatchetErrorCause::INVALID_INDEX,
+                    index: index,
+                    height: 0
+                });
+            }
+        };
+
+        let own: RatchetBranch<'caller> = self.ratchet_over(index, &leaf, core::slice::from_ref(update), scratch)?;
+        let mut node_index: usize = index;
+
+        for (height, key) in own.iter().enumerate() {
+            if let Some(remote) = update.get_at(height, node_index) {
+                if remote != key {
+                    return Err(RatchetError{
+                        description: "Remote update does not agree with the path derived from our leaf",
+                        cause: RatchetErrorCause::INVALID_BRANCH,
+                        index: node_index,
+                        height: height
+                    });
+                }
+            }
+
+            node_index = get_next_index(node_index);
+        }
+
+        let mut public: RatchetBranch<'caller> = RatchetBranch::new(scratch, update.root);
+
+        for key in update.iter() {
+            public.add_node(Key::from(key.pk));
+        }
+
+        self.check_branch(&public, memory)?;
+        self.begin_entry(update.root);
+        self.write_branch(&public, memory)?;
+        self.write_branch(&own, memory)?;
+
+        self.end_entry(true);
+
+        return Ok(self.committed_root());
+    }
+
+    /*
+    * Copy every layer, the orphan list, the epoch and the tombstone into a TreeSnapshot, ready to be encoded.
+    * Secrets are only copied across when `include_secrets` is set, otherwise every Key is public.
+    * Members are left empty, see Roster::snapshot.
+    */
+    pub fn snapshot<'caller>(&self, include_secrets: bool, scratch: &'caller AllocatorCell) -> TreeSnapshot<'caller> {
+        let mut snapshot: TreeSnapshot<'caller> = TreeSnapshot {
+            epoch: self.epoch,
+            secrets: include_secrets,
+            tombstone: self.tombstone.clone(),
+            orphans: BumpVec::with_capacity_in(self.orphans.len(), scratch),
+            nodes: BumpVec::with_capacity_in(self.nodes.len(), scratch),
+            members: BumpVec::new_in(scratch)
+        };
+
+        snapshot.orphans.extend_from_slice(&self.orphans);
+
+        for layer in self.nodes.iter() {
+            let mut snapshot_layer: BumpVec<'caller, Key> = BumpVec::with_capacity_in(layer.len(), scratch);
+
+            for key in layer.iter() {
+                snapshot_layer.push(if include_secrets { key.clone() } else { Key::from(key.pk) });
+            }
+
+            snapshot.nodes.push(snapshot_layer);
+        }
+
+        return snapshot;
+    }
+
+    pub fn restore(memory: &'tree AllocatorPool, snapshot: &TreeSnapshot) -> Result<Self, RatchetError<'tree>> {
+        if snapshot.nodes.len() == 0 || snapshot.nodes[0].len() == 0 {
+            return Err(RatchetError{
+                description: "Snapshot contains no leaf layer",
+                cause: RatchetErrorCause::INVALID_HEIGHT,
+                index: 0,
+                height: 0
+            });
+        }
+
+        let leaf_len: usize = snapshot.nodes[0].len();
+
+        // One layer per height, each holding a node per pair of the one below, up to a single root
+        if snapshot.nodes.len() != height_of(leaf_len - 1) + 1 {
+            return Err(RatchetError{
+                description: "Snapshot layer count does not match its number of leaves",
+                cause: RatchetErrorCause::INVALID_HEIGHT,
+                index: 0,
+                height: snapshot.nodes.len()
+            });
+        }
+
+        for height in 1..snapshot.nodes.len() {
+            if snapshot.nodes[height].len() != snapshot.nodes[height - 1].len() / 2 + 1 {
+                return Err(RatchetError{
+                    description: "Snapshot layer length does not fit the layer below it",
+                    cause: RatchetErrorCause::INVALID_HEIGHT,
+                    index: snapshot.nodes[height].len(),
+                    height: height
+                });
+            }
+        }
+
+        for orphan in snapshot.orphans.iter() {
+            if *orphan == 0 || *orphan >= leaf_len {
+                return Err(RatchetError{
+                    description: "Snapshot orphan index outside of leaf layer",
+                    cause: RatchetErrorCause::INVALID_INDEX,
+                    index: *orphan,
+                    height: 0
+                });
+            }
+
+            if Some(&snapshot.nodes[0][*orphan]) != snapshot.tombstone.as_ref() {
+                return Err(RatchetError{
+                    description: "Snapshot orphan is not a tombstone",
+                    cause: RatchetErrorCause::INCONSISTENT_NODE,
+                    index: *orphan,
+                    height: 0
+                });
+            }
+        }
+
+        let mut tree: RatchetTree<'tree> = RatchetTree::new(memory)?;
+
+        if !tree.provision_layers(snapshot.nodes.len(), memory) {
+            return Err(RatchetError{
+                description: "Not enough memory available in memory_pool for tree",
+                cause: RatchetErrorCause::OOM,
+                index: 0,
+                height: snapshot.nodes.len()
+            });
+        }
+
+        for orphan in snapshot.orphans.iter() {
+
+            tree.orphans.push(*orphan);
+        }
+
+        for (height, layer) in snapshot.nodes.iter().enumerate() {
+            tree.ensure_layer_present(height, tree.layer_memory(height, memory)?);
+
+            for i in 1..layer.len() {
+                tree.write_node(height, i, layer[i].clone(), memory);
+            }
+        }
+
+        tree.tombstone = snapshot.tombstone.clone();
+        tree.epoch = snapshot.epoch;
+
+        // Parents that don't follow from their children, wherever we hold the secrets to tell
+        let scratch: AllocatorCell = match tree.branch_memory() {
+            Ok(scratch) => scratch,
+            Err(_) => {
+                return Err(RatchetError{
+                    description: "Not enough memory available in memory_pool for tree",
+                    cause: RatchetErrorCause::OOM,
+                    index: 0,
+                    height: 0
+                });
+            }
+        };
+
+        if let Some(error) = tree.verify(&scratch).first() {
+            return Err(RatchetError{
+                description: "Snapshot holds a node inconsistent with its children",
+                cause: RatchetErrorCause::INCONSISTENT_NODE,
+                index: error.index,
+                height: error.height
+            });
+        }
+
+        return Ok(tree);
+    }
+
+    /*
+    * Verify the update was signed by the member the roster binds to the sender's leaf, that its kind is what
+    * it does to the tree, and that they computed it against the same tree we hold, before applying it.
+    * Only a leaf's own member can update it. Applied through the roster, so inserts bind `member` and
+    * removals unbind the leaf, see Roster::apply_update
+    */
+    pub fn apply_signed_update<'caller>(&mut self, message: &UpdateMessage, roster: &mut Roster, member: Option<Member>, index: usize, memory: &'tree AllocatorPool, scratch: &'caller AllocatorCell) -> Result<&Key, RatchetError<'caller>> {
+        let sender: &Member = match roster.get(message.sender) {
+            Some(member) => member,
+            None => {
+                return Err(RatchetError{
+                    description: "No member bound to the sender's leaf",
+                    cause: RatchetErrorCause::INVALID_MEMBER,
+                    index: message.sender,
+                    height: 0
+                });
+            }
+        };
+
+        if verify_update(&sender.identity, message).is_err() {
+            return Err(RatchetError{
+                description: "Update signature does not verify against sender identity",
+                cause: RatchetErrorCause::INVALID_SIGNATURE,
+                index: message.sender,
+                height: 0
+            });
+        }
+
+        let kind: UpdateKind = UpdateKind::of(self, &message.branch);
+
+        if message.kind != kind {
+            return Err(RatchetError{
+                description: "Update kind does not match what the branch does to the tree",
+                cause: RatchetErrorCause::INVALID_BRANCH,
+                index: message.branch.root,
+                height: 0
+            });
+        }
+
+        if kind == UpdateKind::UPDATE && message.sender != message.branch.root {
+            return Err(RatchetError{
+                description: "Only the member sitting in a leaf can update it",
+                cause: RatchetErrorCause::INVALID_MEMBER,
+                index: message.branch.root,
+                height: 0
+            });
+        }
+
+        if message.tree_hash != self.tree_hash() {
+            return Err(RatchetError{
+                description: "Update was computed against a different tree",
+                cause: RatchetErrorCause::INVALID_TREE_HASH,
+                index: message.branch.root,
+                height: 0
+            });
+        }
+
+        return roster.apply_update(self, &message.branch, member, index, memory, scratch);
+    }
+
+    // Do not immediately commit the key, return a commit view so we can commit on txn confirmation
     pub fn insert<'caller>(&self, key: &Key, scratch: &'caller AllocatorCell) -> Result<RatchetBranch<'caller>, RatchetError<'caller>> {
         return self.ratchet(self.get_next_index(), key, &scratch);
     }
 
     pub fn remove<'caller>(&self, index: usize, scratch: &'caller AllocatorCell) -> Result<RatchetBranch<'caller>, RatchetError<'caller>> {
         let leaf_node_len: usize = self.get_layer_len(0);
-        let sibling_index: usize = get_sibling_index(index);
 
-        if index >= leaf_node_len || sibling_index >= leaf_node_len {
+        // The last leaf has no sibling when the leaf count is odd, removing it just shrinks the tree
+        if index == 0 || index >= leaf_node_len {
             return Err(RatchetError{
                 description: "index provided larger than leaf-node array len",
                 cause: RatchetErrorCause::INVALID_INDEX,
@@ -411,6 +2238,7 @@ impl<'tree> RatchetTree<'tree> {
                 });
             }
 
+            // The key replaced is dropped, wiping its secret
             layer[index] = value;
 
             return Ok(());
@@ -424,8 +2252,217 @@ impl<'tree> RatchetTree<'tree> {
         });
     }
 
-    pub fn get_layer(&self, height: usize) -> Option<&BumpVec<Key>> {
-        return self.nodes.get(height);
+    /*
+    * Walk every layer and check each parent against its children using the same rules as ratchet:
+    * tombstone when both children are empty, pass-through when only one is, otherwise the DH of both.
+    * The DH rule can only be checked where we hold the secret of at least one child, other parents
+    * are taken as-is. Orphans must point at tombstoned leaves. Every inconsistent node is reported,
+    * an empty result means the tree is consistent.
+    */
+    pub fn verify<'caller>(&self, scratch: &'caller AllocatorCell) -> BumpVec<'caller, RatchetError<'caller>> {
+        let mut errors: BumpVec<'caller, RatchetError<'caller>> = BumpVec::new_in(scratch);
+        let tombstone: Option<&Key> = self.tombstone.as_ref();
+
+        for orphan in self.orphans.iter() {
+            if self.get(0, *orphan) != tombstone {
+                errors.push(RatchetError{
+                    description: "Orphaned leaf is not a tombstone",
+                    cause: RatchetErrorCause::INCONSISTENT_NODE,
+                    index: *orphan,
+                    height: 0
+                });
+            }
+        }
+
+        for height in 1..self.height() + 1 {
+            let children: usize = self.nodes.get(height - 1).map_or(0, |layer| layer.len());
+            let parents: usize = self.nodes.get(height).map_or(0, |layer| layer.len());
+            let count: usize = core::cmp::max(get_next_index(children.saturating_sub(1)) + 1, parents);
+
+            for index in 1..count {
+                let parent: Option<&Key> = self.get(height, index);
+                let k1: Option<&Key> = self.get(height - 1, index * 2 - 1);
+                let k2: Option<&Key> = self.get(height - 1, index * 2);
+
+                let no_key1: bool = k1.is_none() || k1 == tombstone;
+                let no_key2: bool = k2.is_none() || k2 == tombstone;
+
+                let description: Option<&'static str> = if parent.is_none() {
+                    match no_key1 && no_key2 {
+                        true => None,
+                        false => Some("Node is missing while its children are present")
+                    }
+                } else if no_key1 && no_key2 {
+                    match parent == tombstone {
+                        true => None,
+                        false => Some("Node should be a tombstone as both children are empty")
+                    }
+                } else if no_key1 || no_key2 {
+                    match parent == if no_key1 { k2 } else { k1 } {
+                        true => None,
+                        false => Some("Node should pass through its only child")
+                    }
+                } else if k1.unwrap().sk.is_none() && k2.unwrap().sk.is_none() {
+                    None
+                } else {
+                    match k1.unwrap().diffie_hellman(k2.unwrap()) {
+                        Ok(key) if parent == Some(&key) => None,
+                        Ok(_) => Some("Node is not the Diffie-Hellman of its children"),
+                        Err(_) => Some("Diffie hellman failed")
+                    }
+                };
+
+                if let Some(description) = description {
+                    errors.push(RatchetError{
+                        description: description,
+                        cause: RatchetErrorCause::INCONSISTENT_NODE,
+                        index: index,
+                        height: height
+                    });
+                }
+            }
+        }
+
+        return errors;
+    }
+
+    /*
+    * Merkle-style hash over every public node in the tree, so members can cheaply check they agree.
+    * Leaves hash as H(0 || index || pk), parents as H(1 || height || index || present || pk || left || right)
+    * with empty subtrees hashing to zero. The root hash is then bound to the tombstone and orphan list:
+    * H(label || tombstone || orphan count || orphans... || root hash). Secrets never contribute.
+    */
+    pub fn tree_hash(&self) -> [u8; TREE_HASH_LEN] {
+        let mut digest: Sha256 = Sha256::new();
+
+        digest.update(TREE_HASH_LABEL);
+
+        match &self.tombstone {
+            Some(tombstone) => digest.update(tombstone.pk.to_encoded_point(true).as_bytes()),
+            None => digest.update(&[0])
+        }
+
+        digest.update(&(self.orphans.len() as u64).to_be_bytes());
+
+        for orphan in self.orphans.iter() {
+            digest.update(&(*orphan as u64).to_be_bytes());
+        }
+
+        digest.update(&self.node_hash(self.height(), 1));
+
+        let mut hash: [u8; TREE_HASH_LEN] = [0; TREE_HASH_LEN];
+        hash.copy_from_slice(&digest.finalize());
+
+        return hash;
+    }
+
+    // Children of (height, index) sit at (height - 1, 2 * index - 1) & (height - 1, 2 * index)
+    fn node_hash(&self, height: usize, index: usize) -> [u8; TREE_HASH_LEN] {
+        let key: Option<&Key> = self.get(height, index);
+        let mut digest: Sha256 = Sha256::new();
+
+        if height == 0 {
+            match key {
+                Some(key) => {
+                    digest.update(&[0]);
+                    digest.update(&(index as u64).to_be_bytes());
+                    digest.update(key.pk.to_encoded_point(true).as_bytes());
+                },
+                None => return EMPTY_NODE_HASH
+            }
+        } else {
+            let left: [u8; TREE_HASH_LEN] = self.node_hash(height - 1, index * 2 - 1);
+            let right: [u8; TREE_HASH_LEN] = self.node_hash(height - 1, index * 2);
+
+            if key.is_none() && left == EMPTY_NODE_HASH && right == EMPTY_NODE_HASH {
+                return EMPTY_NODE_HASH;
+            }
+
+            digest.update(&[1]);
+            digest.update(&(height as u64).to_be_bytes());
+            digest.update(&(index as u64).to_be_bytes());
+
+            match key {
+                Some(key) => {
+                    digest.update(&[1]);
+                    digest.update(key.pk.to_encoded_point(true).as_bytes());
+                },
+                None => digest.update(&[0])
+            }
+
+            digest.update(&left);
+            digest.update(&right);
+        }
+
+        let mut hash: [u8; TREE_HASH_LEN] = [0; TREE_HASH_LEN];
+        hash.copy_from_slice(&digest.finalize());
+
+        return hash;
+    }
+
+    /*
+    * Every node from leaf `index` up to the root, in the same order as a RatchetBranch rooted at `index`.
+    * These are the nodes a member holds the secrets for.
+    */
+    pub fn direct_path<'caller>(&self, index: usize, scratch: &'caller AllocatorCell) -> Result<BumpVec<'caller, PathNode>, RatchetError<'caller>> {
+        self.check_leaf_index(index)?;
+
+        let mut path: BumpVec<'caller, PathNode> = BumpVec::new_in(scratch);
+        let mut position: usize = index;
+
+        for height in 0..self.height() + 1 {
+            path.push(self.path_node(height, position));
+            position = get_next_index(position);
+        }
+
+        return Ok(path);
+    }
+
+    /*
+    * The sibling of every node on the direct path of leaf `index`, root excluded.
+    * These are the public keys a member needs to re-derive their path.
+    */
+    pub fn copath<'caller>(&self, index: usize, scratch: &'caller AllocatorCell) -> Result<BumpVec<'caller, PathNode>, RatchetError<'caller>> {
+        self.check_leaf_index(index)?;
+
+        let mut copath: BumpVec<'caller, PathNode> = BumpVec::new_in(scratch);
+        let mut position: usize = index;
+
+        for height in 0..self.height() {
+            copath.push(self.path_node(height, get_sibling_index(position)));
+            position = get_next_index(position);
+        }
+
+        return Ok(copath);
+    }
+
+    fn path_node(&self, height: usize, index: usize) -> PathNode {
+        return PathNode {
+            height: height,
+            index: index,
+            pk: self.get(height, index).map(|key| key.pk)
+        };
+    }
+
+    fn check_leaf_index<'caller>(&self, index: usize) -> Result<(), RatchetError<'caller>> {
+        if index == 0 || index >= self.get_layer_len(0) {
+            return Err(RatchetError{
+                description: "index provided larger than leaf-node array len",
+                cause: RatchetErrorCause::INVALID_INDEX,
+                index: index,
+                height: 0
+            });
+        }
+
+        return Ok(());
+    }
+
+    pub fn get_orphans(&self) -> &[usize] {
+        return self.orphans.as_slice();
+    }
+
+    pub fn get_layer(&self, height: usize) -> Option<&[Key]> {
+        return self.nodes.get(height).map(|layer| &**layer);
     }
     
     pub fn get_layer_len(&self, height: usize) -> usize {
@@ -435,4 +2472,24 @@ impl<'tree> RatchetTree<'tree> {
 
         return 0;
     }
-}
\ No newline at end of file
+}
+// Give our slots back to the pool, for the next tree registered to claim
+impl<'tree> Drop for RatchetTree<'tree> {
+    fn drop(&mut self) {
+        let memory: &AllocatorPool = self.journal.memory;
+
+        // Released allocators are reset (& wiped), so every key held in our slots goes first
+        self.journal.trim(0);
+        self.spare.clear();
+        self.nodes.clear();
+        self.orphans.clear();
+
+        for slot in [SLOT_ROOT_NODES, SLOT_ORPHANS, SLOT_JOURNAL, SLOT_BRANCH] {
+            let _ = memory.release(&self.slot_name(slot));
+        }
+
+        for height in 0..self.slots.len() {
+            let _ = memory.release(&self.slot_name(&alloc::format!("{}.{}", SLOT_LAYER, height)));
+        }
+    }
+}
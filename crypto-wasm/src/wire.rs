@@ -0,0 +1,801 @@
+extern crate alloc;
+
+use core::fmt;
+
+use serde::{
+    Serialize,
+    Serializer,
+    Deserialize,
+    ser::SerializeSeq,
+    de::Deserializer,
+    de::DeserializeSeed,
+    de::SeqAccess,
+    de::Visitor,
+    de::Error as DeError
+};
+
+use serde_cbor::{
+    ser::Serializer as CborSerializer,
+    ser::SliceWrite,
+    de::Deserializer as CborDeserializer
+};
+
+use bumpalo::collections::Vec as BumpVec;
+
+use k256::{
+    PublicKey,
+    FieldBytes,
+    ecdsa::Signature,
+    ecdsa::VerifyingKey
+};
+use elliptic_curve::sec1::ToEncodedPoint;
+use elliptic_curve::zeroize::Zeroizing;
+
+use crate::ecdh::{
+    Key,
+    Secret
+};
+use crate::mem::AllocatorCell;
+use crate::tree::{
+    RatchetTree,
+    RatchetBranch,
+    MAX_TREE_HEIGHT,
+    TREE_HASH_LEN
+};
+use crate::roster::Member;
+
+pub const WIRE_VERSION: u8 = 2;
+pub const SNAPSHOT_VERSION: u8 = 1;
+
+// Compressed SEC1 encoding, 1 byte tag + 32 byte x-coordinate
+pub const COMPRESSED_KEY_LEN: usize = 33;
+
+// Upper bound on the CBOR framing around the path: array headers, version, kind, index & epoch
+const MESSAGE_HEADER_LEN: usize = 32;
+// CBOR byte string header for a 33 byte string
+const KEY_HEADER_LEN: usize = 2;
+// Snapshot node: array header, compressed public key & optional 32 byte secret scalar
+const SNAPSHOT_KEY_LEN: usize = 1 + KEY_HEADER_LEN + COMPRESSED_KEY_LEN + KEY_HEADER_LEN + SECRET_KEY_LEN;
+// CBOR unsigned integer, worst case 64 bit
+const SNAPSHOT_INDEX_LEN: usize = 9;
+// Snapshot member: array header, leaf index, member id & compressed identity key
+const SNAPSHOT_MEMBER_LEN: usize = 1 + SNAPSHOT_INDEX_LEN * 2 + KEY_HEADER_LEN + COMPRESSED_KEY_LEN;
+
+pub const SECRET_KEY_LEN: usize = 32;
+// Fixed size r || s ECDSA signature
+pub const SIGNATURE_LEN: usize = 64;
+
+#[derive(Debug, Clone)]
+pub struct WireError<'a> {
+    pub reason: &'a str
+}
+
+impl<'a> fmt::Display for WireError<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return write!(f, "Invalid Wire Operation: {}", self.reason);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UpdateKind {
+    INSERT,
+    UPDATE,
+    REMOVE
+}
+
+impl UpdateKind {
+    pub fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => return Some(UpdateKind::INSERT),
+            1 => return Some(UpdateKind::UPDATE),
+            2 => return Some(UpdateKind::REMOVE),
+            _ => return None
+        }
+    }
+
+    pub fn as_u8(&self) -> u8 {
+        match self {
+            UpdateKind::INSERT => return 0,
+            UpdateKind::UPDATE => return 1,
+            UpdateKind::REMOVE => return 2
+        }
+    }
+
+    /*
+    * What committing `branch` to `tree` does: tombstoning a leaf removes its member, filling an empty
+    * or orphaned leaf inserts one, anything else updates the sitting member.
+    */
+    pub fn of(tree: &RatchetTree, branch: &RatchetBranch) -> Self {
+        let leaf: Option<&Key> = tree.get(0, branch.root);
+
+        if branch.get_node(0) == tree.tombstone.as_ref() {
+            return UpdateKind::REMOVE;
+        }
+
+        if leaf.is_none() || leaf == tree.tombstone.as_ref() {
+            return UpdateKind::INSERT;
+        }
+
+        return UpdateKind::UPDATE;
+    }
+}
+
+/*
+* Update message as sent to other members of the group.
+* Encoded as a CBOR array: [version, kind, sender, leaf index, epoch, tree hash, [compressed public keys...], signature or null]
+* Only the public half of each Key on the path is ever written, the Secret is dropped on encode
+* and every Key decoded from the wire has no Secret. The epoch is the branch's own, and `tree_hash` is
+* RatchetTree::tree_hash of the tree the branch was computed against.
+* `sender` is the leaf of the member who committed the branch, whose identity signs it, see
+* identity::IdentityKey::sign_update. For updates it's the leaf being changed.
+*/
+pub struct UpdateMessage<'a> {
+    pub kind: UpdateKind,
+    pub sender: usize,
+    pub tree_hash: [u8; TREE_HASH_LEN],
+    pub branch: RatchetBranch<'a>,
+    pub signature: Option<Signature>
+}
+
+struct CompressedKey<'k>(&'k Key);
+
+struct Path<'b, 'a>(&'b RatchetBranch<'a>);
+
+struct PathSeed<'a> {
+    scratch: &'a AllocatorCell,
+    root: usize
+}
+
+struct UpdateMessageSeed<'a> {
+    scratch: &'a AllocatorCell
+}
+
+/*
+* Full tree snapshot, for persisting a group between sessions.
+* Encoded as a CBOR array: [version, epoch, secrets, tombstone, [orphans...], [[node...]...], [member...]]
+* where each node is [compressed public key, secret scalar or null] and each member is
+* [leaf index, member id, compressed identity key]. Secrets are only ever written when `secrets` is set,
+* see RatchetTree::snapshot and Roster::snapshot.
+*/
+pub struct TreeSnapshot<'a> {
+    pub epoch: u64,
+    pub secrets: bool,
+    pub tombstone: Option<Key>,
+    pub orphans: BumpVec<'a, usize>,
+    pub nodes: BumpVec<'a, BumpVec<'a, Key>>,
+    pub members: BumpVec<'a, (usize, Member)>
+}
+
+struct Bytes<'b>(&'b [u8]);
+
+struct SnapshotKey<'k>(&'k Key, bool);
+
+struct SnapshotTombstone<'k>(&'k Option<Key>);
+
+struct SnapshotOrphans<'b, 'a>(&'b BumpVec<'a, usize>);
+
+struct SnapshotLayer<'b, 'a>(&'b BumpVec<'a, Key>, bool);
+
+struct SnapshotNodes<'b, 'a>(&'b BumpVec<'a, BumpVec<'a, Key>>, bool);
+
+struct SnapshotMembers<'b, 'a>(&'b BumpVec<'a, (usize, Member)>);
+
+struct SnapshotKeyValue(Key);
+
+struct SnapshotMemberValue(usize, Member);
+
+struct OrphansSeed<'a> {
+    scratch: &'a AllocatorCell
+}
+
+struct LayerSeed<'a> {
+    scratch: &'a AllocatorCell
+}
+
+struct NodesSeed<'a> {
+    scratch: &'a AllocatorCell
+}
+
+struct MembersSeed<'a> {
+    scratch: &'a AllocatorCell
+}
+
+struct TreeSnapshotSeed<'a> {
+    scratch: &'a AllocatorCell
+}
+
+fn decode_public_key<E: DeError>(bytes: &[u8]) -> Result<PublicKey, E> {
+    if bytes.len() != COMPRESSED_KEY_LEN {
+        return Err(E::invalid_length(bytes.len(), &"a 33 byte compressed public key"));
+    }
+
+    match PublicKey::from_sec1_bytes(bytes) {
+        Ok(pk) => return Ok(pk),
+        Err(_) => return Err(E::custom("invalid compressed public key"))
+    }
+}
+
+// Indexes are u64 on the wire, reject any that don't fit in a usize rather than truncating them on wasm32
+fn decode_index<E: DeError>(index: u64) -> Result<usize, E> {
+    match usize::try_from(index) {
+        Ok(index) => return Ok(index),
+        Err(_) => return Err(E::custom("index does not fit in usize"))
+    }
+}
+
+impl<'b> Serialize for Bytes<'b> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        return serializer.serialize_bytes(self.0);
+    }
+}
+
+impl<'k> Serialize for CompressedKey<'k> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        return serializer.serialize_bytes(self.0.pk.to_encoded_point(true).as_bytes());
+    }
+}
+
+impl<'a> Serialize for UpdateMessage<'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(Some(8))?;
+
+        seq.serialize_element(&WIRE_VERSION)?;
+        seq.serialize_element(&self.kind.as_u8())?;
+        seq.serialize_element(&(self.sender as u64))?;
+        seq.serialize_element(&(self.branch.root as u64))?;
+        seq.serialize_element(&self.branch.epoch)?;
+        seq.serialize_element(&Bytes(&self.tree_hash))?;
+        seq.serialize_element(&Path(&self.branch))?;
+
+        match &self.signature {
+            Some(signature) => seq.serialize_element(&Some(Bytes(signature.as_ref())))?,
+            None => seq.serialize_element(&Option::<Bytes>::None)?
+        }
+
+        return seq.end();
+    }
+}
+
+impl<'b, 'a> Serialize for Path<'b, 'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(Some(self.0.len()))?;
+
+        for key in self.0.iter() {
+            seq.serialize_element(&CompressedKey(key))?;
+        }
+
+        return seq.end();
+    }
+}
+
+impl<'de, 'a> DeserializeSeed<'de> for PathSeed<'a> {
+    type Value = RatchetBranch<'a>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        return deserializer.deserialize_seq(self);
+    }
+}
+
+impl<'de, 'a> Visitor<'de> for PathSeed<'a> {
+    type Value = RatchetBranch<'a>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return write!(f, "an array of compressed SEC1 public keys");
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut branch: RatchetBranch<'a> = RatchetBranch::new(self.scratch, self.root);
+
+        while let Some(bytes) = seq.next_element::<&'de [u8]>()? {
+            // A path holds a node per layer, root included
+            if branch.len() > MAX_TREE_HEIGHT {
+                return Err(A::Error::invalid_length(branch.len() + 1, &"a path no longer than the maximum tree height"));
+            }
+
+            branch.add_node(Key::from(decode_public_key::<A::Error>(bytes)?));
+        }
+
+        return Ok(branch);
+    }
+}
+
+impl<'de, 'a> DeserializeSeed<'de> for UpdateMessageSeed<'a> {
+    type Value = UpdateMessage<'a>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        return deserializer.deserialize_seq(self);
+    }
+}
+
+impl<'de, 'a> Visitor<'de> for UpdateMessageSeed<'a> {
+    type Value = UpdateMessage<'a>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return write!(f, "an update message array");
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let version: u8 = seq.next_element()?.ok_or_else(|| A::Error::invalid_length(0, &self))?;
+
+        if version != WIRE_VERSION {
+            return Err(A::Error::custom("unsupported update message version"));
+        }
+
+        let kind: u8 = seq.next_element()?.ok_or_else(|| A::Error::invalid_length(1, &self))?;
+        let kind: UpdateKind = UpdateKind::from_u8(kind).ok_or_else(|| A::Error::custom("unknown update message kind"))?;
+
+        let sender: u64 = seq.next_element()?.ok_or_else(|| A::Error::invalid_length(2, &self))?;
+        let root: u64 = seq.next_element()?.ok_or_else(|| A::Error::invalid_length(3, &self))?;
+        let epoch: u64 = seq.next_element()?.ok_or_else(|| A::Error::invalid_length(4, &self))?;
+        let hash: &'de [u8] = seq.next_element()?.ok_or_else(|| A::Error::invalid_length(5, &self))?;
+
+        if hash.len() != TREE_HASH_LEN {
+            return Err(A::Error::invalid_length(hash.len(), &"a 32 byte tree hash"));
+        }
+
+        let mut tree_hash: [u8; TREE_HASH_LEN] = [0; TREE_HASH_LEN];
+        tree_hash.copy_from_slice(hash);
+
+        let mut branch: RatchetBranch<'a> = seq.next_element_seed(PathSeed{
+            scratch: self.scratch,
+            root: decode_index::<A::Error>(root)?
+        })?.ok_or_else(|| A::Error::invalid_length(6, &self))?;
+
+        branch.epoch = epoch;
+
+        let signature: Option<&'de [u8]> = seq.next_element()?.ok_or_else(|| A::Error::invalid_length(7, &self))?;
+        let signature: Option<Signature> = match signature {
+            Some(bytes) => match Signature::try_from(bytes) {
+                Ok(signature) => Some(signature),
+                Err(_) => return Err(A::Error::custom("invalid update signature"))
+            },
+            None => None
+        };
+
+        return Ok(UpdateMessage {
+            kind: kind,
+            sender: decode_index::<A::Error>(sender)?,
+            tree_hash: tree_hash,
+            branch: branch,
+            signature: signature
+        });
+    }
+}
+
+impl<'a> UpdateMessage<'a> {
+    pub fn new(kind: UpdateKind, sender: usize, tree_hash: [u8; TREE_HASH_LEN], branch: RatchetBranch<'a>) -> Self {
+        return Self {
+            kind: kind,
+            sender: sender,
+            tree_hash: tree_hash,
+            branch: branch,
+            signature: None
+        };
+    }
+
+    pub fn encoded_len(&self) -> usize {
+        return MESSAGE_HEADER_LEN + SNAPSHOT_INDEX_LEN + KEY_HEADER_LEN * 2 + TREE_HASH_LEN + SIGNATURE_LEN + self.branch.len() * (COMPRESSED_KEY_LEN + KEY_HEADER_LEN);
+    }
+
+    pub fn encode<'caller>(&self, scratch: &'caller AllocatorCell) -> Result<BumpVec<'caller, u8>, WireError<'caller>> {
+        let mut buffer: BumpVec<'caller, u8> = bumpalo::vec![in scratch; 0; self.encoded_len()];
+        let mut serializer = CborSerializer::new(SliceWrite::new(buffer.as_mut_slice()));
+
+        if self.serialize(&mut serializer).is_err() {
+            return Err(WireError{
+                reason: "Unable to serialize update message"
+            });
+        }
+
+        let written: usize = serializer.into_inner().bytes_written();
+        buffer.truncate(written);
+
+        return Ok(buffer);
+    }
+
+    pub fn decode(bytes: &[u8], scratch: &'a AllocatorCell) -> Result<Self, WireError<'a>> {
+        let mut deserializer = CborDeserializer::from_slice_with_scratch(bytes, &mut []);
+
+        let message: UpdateMessage<'a> = match (UpdateMessageSeed{ scratch: scratch }).deserialize(&mut deserializer) {
+            Ok(message) => message,
+            Err(_) => {
+                return Err(WireError{
+                    reason: "Unable to deserialize update message"
+                });
+            }
+        };
+
+        if deserializer.end().is_err() {
+            return Err(WireError{
+                reason: "Trailing bytes after update message"
+            });
+        }
+
+        return Ok(message);
+    }
+}
+
+impl<'k> Serialize for SnapshotKey<'k> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(Some(2))?;
+
+        seq.serialize_element(&CompressedKey(self.0))?;
+
+        match self.0.sk.as_ref() {
+            Some(secret) if self.1 => {
+                // Wiped once it's been written out
+                let repr: Zeroizing<[u8; SECRET_KEY_LEN]> = Zeroizing::new(secret.to_repr().into());
+
+                seq.serialize_element(&Some(Bytes(repr.as_slice())))?
+            },
+            _ => seq.serialize_element(&Option::<Bytes>::None)?
+        }
+
+        return seq.end();
+    }
+}
+
+impl<'k> Serialize for SnapshotTombstone<'k> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.0 {
+            Some(key) => return serializer.serialize_some(&CompressedKey(key)),
+            None => return serializer.serialize_none()
+        }
+    }
+}
+
+impl<'b, 'a> Serialize for SnapshotOrphans<'b, 'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(Some(self.0.len()))?;
+
+        for orphan in self.0.iter() {
+            seq.serialize_element(&(*orphan as u64))?;
+        }
+
+        return seq.end();
+    }
+}
+
+impl<'b, 'a> Serialize for SnapshotLayer<'b, 'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(Some(self.0.len()))?;
+
+        for key in self.0.iter() {
+            seq.serialize_element(&SnapshotKey(key, self.1))?;
+        }
+
+        return seq.end();
+    }
+}
+
+impl<'b, 'a> Serialize for SnapshotNodes<'b, 'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(Some(self.0.len()))?;
+
+        for layer in self.0.iter() {
+            seq.serialize_element(&SnapshotLayer(layer, self.1))?;
+        }
+
+        return seq.end();
+    }
+}
+
+impl<'b, 'a> Serialize for SnapshotMembers<'b, 'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(Some(self.0.len()))?;
+
+        for (index, member) in self.0.iter() {
+            seq.serialize_element(&(*index as u64, member.id, Bytes(member.identity.to_bytes().as_slice())))?;
+        }
+
+        return seq.end();
+    }
+}
+
+impl<'a> Serialize for TreeSnapshot<'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(Some(7))?;
+
+        seq.serialize_element(&SNAPSHOT_VERSION)?;
+        seq.serialize_element(&self.epoch)?;
+        seq.serialize_element(&self.secrets)?;
+        seq.serialize_element(&SnapshotTombstone(&self.tombstone))?;
+        seq.serialize_element(&SnapshotOrphans(&self.orphans))?;
+        seq.serialize_element(&SnapshotNodes(&self.nodes, self.secrets))?;
+        seq.serialize_element(&SnapshotMembers(&self.members))?;
+
+        return seq.end();
+    }
+}
+
+impl<'de> Deserialize<'de> for SnapshotKeyValue {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        return deserializer.deserialize_seq(SnapshotKeyVisitor);
+    }
+}
+
+struct SnapshotKeyVisitor;
+
+impl<'de> Visitor<'de> for SnapshotKeyVisitor {
+    type Value = SnapshotKeyValue;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return write!(f, "a [public key, secret] pair");
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let pk: &'de [u8] = seq.next_element()?.ok_or_else(|| A::Error::invalid_length(0, &self))?;
+        let sk: Option<&'de [u8]> = seq.next_element()?.ok_or_else(|| A::Error::invalid_length(1, &self))?;
+
+        let pk: PublicKey = decode_public_key::<A::Error>(pk)?;
+
+        let key: Key = match sk {
+            Some(bytes) => {
+                if bytes.len() != SECRET_KEY_LEN {
+                    return Err(A::Error::invalid_length(bytes.len(), &"a 32 byte secret scalar"));
+                }
+
+                let secret: Secret = match Secret::from_repr(FieldBytes::from_slice(bytes)) {
+                    Ok(secret) => secret,
+                    Err(_) => return Err(A::Error::custom("invalid secret scalar"))
+                };
+
+                if secret.public_key() != pk {
+                    return Err(A::Error::custom("secret scalar does not match public key"));
+                }
+
+                Key::from(secret)
+            },
+            None => Key::from(pk)
+        };
+
+        return Ok(SnapshotKeyValue(key));
+    }
+}
+
+impl<'de, 'a> DeserializeSeed<'de> for OrphansSeed<'a> {
+    type Value = BumpVec<'a, usize>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        return deserializer.deserialize_seq(self);
+    }
+}
+
+impl<'de, 'a> Visitor<'de> for OrphansSeed<'a> {
+    type Value = BumpVec<'a, usize>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return write!(f, "an array of orphaned leaf indexes");
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut orphans: BumpVec<'a, usize> = BumpVec::new_in(self.scratch);
+
+        while let Some(orphan) = seq.next_element::<u64>()? {
+            orphans.push(decode_index::<A::Error>(orphan)?);
+        }
+
+        return Ok(orphans);
+    }
+}
+
+impl<'de, 'a> DeserializeSeed<'de> for LayerSeed<'a> {
+    type Value = BumpVec<'a, Key>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        return deserializer.deserialize_seq(self);
+    }
+}
+
+impl<'de, 'a> Visitor<'de> for LayerSeed<'a> {
+    type Value = BumpVec<'a, Key>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return write!(f, "an array of tree nodes");
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut layer: BumpVec<'a, Key> = BumpVec::new_in(self.scratch);
+
+        while let Some(SnapshotKeyValue(key)) = seq.next_element()? {
+            layer.push(key);
+        }
+
+        return Ok(layer);
+    }
+}
+
+impl<'de, 'a> DeserializeSeed<'de> for NodesSeed<'a> {
+    type Value = BumpVec<'a, BumpVec<'a, Key>>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        return deserializer.deserialize_seq(self);
+    }
+}
+
+impl<'de, 'a> Visitor<'de> for NodesSeed<'a> {
+    type Value = BumpVec<'a, BumpVec<'a, Key>>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return write!(f, "an array of tree layers");
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut nodes: BumpVec<'a, BumpVec<'a, Key>> = BumpVec::new_in(self.scratch);
+
+        while let Some(layer) = seq.next_element_seed(LayerSeed{ scratch: self.scratch })? {
+            if nodes.len() > MAX_TREE_HEIGHT {
+                return Err(A::Error::invalid_length(nodes.len() + 1, &"no more layers than the maximum tree height"));
+            }
+
+            nodes.push(layer);
+        }
+
+        return Ok(nodes);
+    }
+}
+
+impl<'de> Deserialize<'de> for SnapshotMemberValue {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        return deserializer.deserialize_seq(SnapshotMemberVisitor);
+    }
+}
+
+struct SnapshotMemberVisitor;
+
+impl<'de> Visitor<'de> for SnapshotMemberVisitor {
+    type Value = SnapshotMemberValue;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return write!(f, "a [leaf index, member id, identity key] triple");
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let index: u64 = seq.next_element()?.ok_or_else(|| A::Error::invalid_length(0, &self))?;
+        let id: u64 = seq.next_element()?.ok_or_else(|| A::Error::invalid_length(1, &self))?;
+        let identity: &'de [u8] = seq.next_element()?.ok_or_else(|| A::Error::invalid_length(2, &self))?;
+
+        if index == 0 {
+            return Err(A::Error::custom("member bound to leaf index 0"));
+        }
+
+        let identity: VerifyingKey = match VerifyingKey::from_sec1_bytes(identity) {
+            Ok(identity) => identity,
+            Err(_) => return Err(A::Error::custom("invalid member identity key"))
+        };
+
+        return Ok(SnapshotMemberValue(decode_index::<A::Error>(index)?, Member {
+            id: id,
+            identity: identity
+        }));
+    }
+}
+
+impl<'de, 'a> DeserializeSeed<'de> for MembersSeed<'a> {
+    type Value = BumpVec<'a, (usize, Member)>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        return deserializer.deserialize_seq(self);
+    }
+}
+
+impl<'de, 'a> Visitor<'de> for MembersSeed<'a> {
+    type Value = BumpVec<'a, (usize, Member)>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return write!(f, "an array of roster members");
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut members: BumpVec<'a, (usize, Member)> = BumpVec::new_in(self.scratch);
+
+        while let Some(SnapshotMemberValue(index, member)) = seq.next_element()? {
+            members.push((index, member));
+        }
+
+        return Ok(members);
+    }
+}
+
+impl<'de, 'a> DeserializeSeed<'de> for TreeSnapshotSeed<'a> {
+    type Value = TreeSnapshot<'a>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        return deserializer.deserialize_seq(self);
+    }
+}
+
+impl<'de, 'a> Visitor<'de> for TreeSnapshotSeed<'a> {
+    type Value = TreeSnapshot<'a>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return write!(f, "a tree snapshot array");
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let version: u8 = seq.next_element()?.ok_or_else(|| A::Error::invalid_length(0, &self))?;
+
+        if version != SNAPSHOT_VERSION {
+            return Err(A::Error::custom("unsupported snapshot version"));
+        }
+
+        let epoch: u64 = seq.next_element()?.ok_or_else(|| A::Error::invalid_length(1, &self))?;
+        let secrets: bool = seq.next_element()?.ok_or_else(|| A::Error::invalid_length(2, &self))?;
+        let tombstone: Option<&'de [u8]> = seq.next_element()?.ok_or_else(|| A::Error::invalid_length(3, &self))?;
+
+        let tombstone: Option<Key> = match tombstone {
+            Some(bytes) => Some(Key::from(decode_public_key::<A::Error>(bytes)?)),
+            None => None
+        };
+
+        let orphans: BumpVec<'a, usize> = seq.next_element_seed(OrphansSeed{ scratch: self.scratch })?
+            .ok_or_else(|| A::Error::invalid_length(4, &self))?;
+        let nodes: BumpVec<'a, BumpVec<'a, Key>> = seq.next_element_seed(NodesSeed{ scratch: self.scratch })?
+            .ok_or_else(|| A::Error::invalid_length(5, &self))?;
+
+        let members: BumpVec<'a, (usize, Member)> = seq.next_element_seed(MembersSeed{ scratch: self.scratch })?
+            .ok_or_else(|| A::Error::invalid_length(6, &self))?;
+
+        if !secrets && nodes.iter().any(|layer| layer.iter().any(|key| key.sk.is_some())) {
+            return Err(A::Error::custom("public snapshot contains secrets"));
+        }
+
+        return Ok(TreeSnapshot {
+            epoch: epoch,
+            secrets: secrets,
+            tombstone: tombstone,
+            orphans: orphans,
+            nodes: nodes,
+            members: members
+        });
+    }
+}
+
+impl<'a> TreeSnapshot<'a> {
+    pub fn encoded_len(&self) -> usize {
+        let mut len: usize = MESSAGE_HEADER_LEN + SNAPSHOT_INDEX_LEN + self.orphans.len() * SNAPSHOT_INDEX_LEN + self.members.len() * SNAPSHOT_MEMBER_LEN;
+
+        for layer in self.nodes.iter() {
+            len += SNAPSHOT_INDEX_LEN + layer.len() * SNAPSHOT_KEY_LEN;
+        }
+
+        return len;
+    }
+
+    pub fn encode<'caller>(&self, scratch: &'caller AllocatorCell) -> Result<BumpVec<'caller, u8>, WireError<'caller>> {
+        let mut buffer: BumpVec<'caller, u8> = bumpalo::vec![in scratch; 0; self.encoded_len()];
+        let mut serializer = CborSerializer::new(SliceWrite::new(buffer.as_mut_slice()));
+
+        if self.serialize(&mut serializer).is_err() {
+            return Err(WireError{
+                reason: "Unable to serialize tree snapshot"
+            });
+        }
+
+        let written: usize = serializer.into_inner().bytes_written();
+        buffer.truncate(written);
+
+        return Ok(buffer);
+    }
+
+    pub fn decode(bytes: &[u8], scratch: &'a AllocatorCell) -> Result<Self, WireError<'a>> {
+        let mut deserializer = CborDeserializer::from_slice_with_scratch(bytes, &mut []);
+
+        let snapshot: TreeSnapshot<'a> = match (TreeSnapshotSeed{ scratch: scratch }).deserialize(&mut deserializer) {
+            Ok(snapshot) => snapshot,
+            Err(_) => {
+                return Err(WireError{
+                    reason: "Unable to deserialize tree snapshot"
+                });
+            }
+        };
+
+        if deserializer.end().is_err() {
+            return Err(WireError{
+                reason: "Trailing bytes after tree snapshot"
+            });
+        }
+
+        return Ok(snapshot);
+    }
+}
@@ -0,0 +1,210 @@
+#![cfg(test)]
+#[macro_use]
+extern crate crypto_art;
+
+use wasm_bindgen_test::*;
+
+use crypto_art::log::*;
+
+use crypto_art::{
+    ecdh::Key,
+    ecdh::Secret,
+    mem::AllocatorPool,
+    mem::AllocatorCell,
+    schedule::KeySchedule,
+    cipher::GroupCipher,
+    cipher::MessageHeader,
+    cipher::HEADER_LEN,
+    cipher::TAG_LEN
+};
+
+use elliptic_curve::zeroize::Zeroize;
+
+use bumpalo::{
+    Bump,
+    collections::Vec
+};
+
+use rand_core::OsRng;
+
+#[wasm_bindgen_test]
+fn test_cipher_roundtrip() {
+    let root_allocator: Bump = AllocatorPool::create_bumpalo::<&Bump>(4);
+    let memory: AllocatorPool = AllocatorPool::new_with_init::<u64>(&root_allocator, 4, 32);
+    let scratch: AllocatorCell = memory.get(0).expect("No allocator at index 0");
+
+    let root: Key = Secret::random(&mut OsRng).into();
+    let mut schedule: KeySchedule = KeySchedule::new();
+    schedule.advance(&root).expect("Unable to advance schedule");
+
+    let mut alice: GroupCipher = GroupCipher::new(&schedule, 1, 2, memory.get_ref(1).expect("No allocator at index 1")).expect("Unable to create cipher for alice");
+    let mut bob: GroupCipher = GroupCipher::new(&schedule, 2, 2, memory.get_ref(2).expect("No allocator at index 2")).expect("Unable to create cipher for bob");
+
+    let frame: Vec<u8> = alice.encrypt(b"Hello, World!", &scratch).expect("Unable to encrypt message");
+
+    assert_eq!(frame.len(), HEADER_LEN + 13 + TAG_LEN);
+
+    let (header, plaintext) = bob.decrypt(&frame, &scratch).expect("Unable to decrypt message");
+
+    assert_eq!(header, MessageHeader{ epoch: 1, sender: 1, session: alice.session(), sequence: 0 });
+    assert_eq!(plaintext.as_slice(), b"Hello, World!");
+
+    // Replays of the same frame are rejected
+    assert!(bob.decrypt(&frame, &scratch).is_err());
+
+    // Later messages are accepted, even if some were lost
+    let _lost: Vec<u8> = alice.encrypt(b"lost", &scratch).expect("Unable to encrypt message");
+    let frame: Vec<u8> = alice.encrypt(b"second", &scratch).expect("Unable to encrypt message");
+    let (header, plaintext) = bob.decrypt(&frame, &scratch).expect("Unable to decrypt message");
+
+    assert_eq!(header.sequence, 2);
+    assert_eq!(plaintext.as_slice(), b"second");
+
+    // Wiped, nothing is sealed or opened until the next rekey
+    alice.zeroize();
+    bob.zeroize();
+
+    assert!(alice.encrypt(b"wiped", &scratch).is_err());
+    assert!(bob.decrypt(&frame, &scratch).is_err());
+
+    alice.rekey(&schedule, 2).expect("Unable to rekey alice");
+    assert!(alice.encrypt(b"rekeyed", &scratch).is_ok());
+}
+
+#[wasm_bindgen_test]
+fn test_cipher_rejects_tampering() {
+    let root_allocator: Bump = AllocatorPool::create_bumpalo::<&Bump>(4);
+    let memory: AllocatorPool = AllocatorPool::new_with_init::<u64>(&root_allocator, 4, 32);
+    let scratch: AllocatorCell = memory.get(0).expect("No allocator at index 0");
+
+    let root: Key = Secret::random(&mut OsRng).into();
+    let mut schedule: KeySchedule = KeySchedule::new();
+    schedule.advance(&root).expect("Unable to advance schedule");
+
+    let mut alice: GroupCipher = GroupCipher::new(&schedule, 1, 2, memory.get_ref(1).expect("No allocator at index 1")).expect("Unable to create cipher for alice");
+    let mut bob: GroupCipher = GroupCipher::new(&schedule, 2, 2, memory.get_ref(2).expect("No allocator at index 2")).expect("Unable to create cipher for bob");
+
+    let frame: Vec<u8> = alice.encrypt(b"Hello, World!", &scratch).expect("Unable to encrypt message");
+
+    // Sender index is bound as associated data
+    let mut spoofed: Vec<u8> = frame.clone();
+    spoofed[15] = 3;
+    assert!(bob.decrypt(&spoofed, &scratch).is_err());
+
+    let mut flipped: Vec<u8> = frame.clone();
+    flipped[HEADER_LEN] ^= 1;
+    assert!(bob.decrypt(&flipped, &scratch).is_err());
+
+    // Failed messages don't burn the sequence number
+    assert!(bob.decrypt(&frame, &scratch).is_ok());
+
+    // Messages from another epoch are rejected
+    schedule.advance(&root).expect("Unable to advance schedule");
+    bob.rekey(&schedule, 2).expect("Unable to rekey bob");
+
+    let frame: Vec<u8> = alice.encrypt(b"stale", &scratch).expect("Unable to encrypt message");
+    assert!(bob.decrypt(&frame, &scratch).is_err());
+
+    alice.rekey(&schedule, 2).expect("Unable to rekey alice");
+
+    let frame: Vec<u8> = alice.encrypt(b"fresh", &scratch).expect("Unable to encrypt message");
+    let (header, plaintext) = bob.decrypt(&frame, &scratch).expect("Unable to decrypt message");
+
+    assert_eq!(header.epoch, 2);
+    assert_eq!(header.sequence, 0);
+    assert_eq!(plaintext.as_slice(), b"fresh");
+}
+
+#[wasm_bindgen_test]
+fn test_cipher_rejects_out_of_range() {
+    let root_allocator: Bump = AllocatorPool::create_bumpalo::<&Bump>(4);
+    let memory: AllocatorPool = AllocatorPool::new_with_init::<u64>(&root_allocator, 4, 32);
+    let scratch: AllocatorCell = memory.get(0).expect("No allocator at index 0");
+
+    // No cipher until the schedule has absorbed a tree key
+    let mut schedule: KeySchedule = KeySchedule::new();
+    assert!(GroupCipher::new(&schedule, 1, 2, memory.get_ref(1).expect("No allocator at index 1")).is_err());
+
+    let root: Key = Secret::random(&mut OsRng).into();
+    schedule.advance(&root).expect("Unable to advance schedule");
+
+    // Nor for a leaf outside the group
+    assert!(GroupCipher::new(&schedule, 3, 2, memory.get_ref(1).expect("No allocator at index 1")).is_err());
+
+    let mut bob: GroupCipher = GroupCipher::new(&schedule, 2, 2, memory.get_ref(2).expect("No allocator at index 2")).expect("Unable to create cipher for bob");
+    let allocated: usize = memory.get_ref(2).expect("No allocator at index 2").allocated_bytes();
+
+    let forge = |sender: usize, sequence: u64| -> Vec<u8> {
+        let header: MessageHeader = MessageHeader{ epoch: 1, sender: sender, session: 0, sequence: sequence };
+        let mut frame: Vec<u8> = Vec::new_in(&scratch);
+
+        frame.extend_from_slice(&header.to_bytes());
+        frame.extend_from_slice(&[0; TAG_LEN]);
+
+        return frame;
+    };
+
+    // Senders outside the group are rejected before they can grow the replay window
+    let error = bob.decrypt(&forge(usize::MAX, 0), &scratch).err().expect("Decrypted message from outside the group");
+    assert_eq!(error.reason, "Message sender is not part of the group");
+
+    let error = bob.decrypt(&forge(0, 0), &scratch).err().expect("Decrypted message from leaf 0");
+    assert_eq!(error.reason, "Message sender is not part of the group");
+
+    // Nor can unauthenticated sessions from a member
+    assert!(bob.decrypt(&forge(1, 0), &scratch).is_err());
+
+    assert_eq!(memory.get_ref(2).expect("No allocator at index 2").allocated_bytes(), allocated);
+
+    // The last sequence number has no successor
+    let error = bob.decrypt(&forge(1, u64::MAX), &scratch).err().expect("Decrypted message with the last sequence number");
+    assert_eq!(error.reason, "Message sequence number out of range");
+
+    // Sender indexes wider than the nonce are never accepted
+    let mut wide: Vec<u8> = forge(1, 0);
+    wide[8..16].copy_from_slice(&(u32::MAX as u64 + 2).to_be_bytes());
+    assert!(bob.decrypt(&wide, &scratch).is_err());
+}
+
+#[wasm_bindgen_test]
+fn test_cipher_rebuilt_never_reuses_nonce() {
+    let root_allocator: Bump = AllocatorPool::create_bumpalo::<&Bump>(4);
+    let memory: AllocatorPool = AllocatorPool::new_with_init::<u64>(&root_allocator, 4, 32);
+    let scratch: AllocatorCell = memory.get(0).expect("No allocator at index 0");
+
+    let root: Key = Secret::random(&mut OsRng).into();
+    let mut schedule: KeySchedule = KeySchedule::new();
+    schedule.advance(&root).expect("Unable to advance schedule");
+
+    // Two ciphers for the same leaf at the same epoch, as after a reload, both start at sequence 0
+    let mut alice: GroupCipher = GroupCipher::new(&schedule, 1, 2, memory.get_ref(1).expect("No allocator at index 1")).expect("Unable to create cipher for alice");
+    let mut rebuilt: GroupCipher = GroupCipher::new(&schedule, 1, 2, memory.get_ref(3).expect("No allocator at index 3")).expect("Unable to create rebuilt cipher for alice");
+    let mut bob: GroupCipher = GroupCipher::new(&schedule, 2, 2, memory.get_ref(2).expect("No allocator at index 2")).expect("Unable to create cipher for bob");
+
+    assert_ne!(alice.session(), rebuilt.session());
+
+    let first: Vec<u8> = alice.encrypt(b"Hello, World!", &scratch).expect("Unable to encrypt message");
+    let second: Vec<u8> = rebuilt.encrypt(b"Hello, World!", &scratch).expect("Unable to encrypt message");
+
+    let (first_header, _) = bob.decrypt(&first, &scratch).expect("Unable to decrypt message");
+    let (second_header, plaintext) = bob.decrypt(&second, &scratch).expect("Unable to decrypt rebuilt message");
+
+    // Same sender and sequence, but each session seals under its own key, so the keystreams differ
+    assert_eq!(first_header.sequence, second_header.sequence);
+    assert_ne!(first_header.session, second_header.session);
+    assert_ne!(&first[HEADER_LEN..], &second[HEADER_LEN..]);
+    assert_eq!(plaintext.as_slice(), b"Hello, World!");
+
+    // Each session keeps its own replay window
+    assert!(bob.decrypt(&first, &scratch).is_err());
+    assert!(bob.decrypt(&second, &scratch).is_err());
+
+    // Rekeying at the same epoch starts a new session too
+    let session: u64 = alice.session();
+    alice.rekey(&schedule, 2).expect("Unable to rekey alice");
+    assert_ne!(alice.session(), session);
+
+    let third: Vec<u8> = alice.encrypt(b"Hello, World!", &scratch).expect("Unable to encrypt message");
+    assert_ne!(&first[HEADER_LEN..], &third[HEADER_LEN..]);
+    assert!(bob.decrypt(&third, &scratch).is_ok());
+}
@@ -8,7 +8,9 @@ use core::convert;
 
 use k256::Secp256k1;
 use k256::PublicKey;
+use k256::NonZeroScalar;
 use elliptic_curve::ScalarCore;
+use elliptic_curve::zeroize::Zeroize;
 
 use crypto_art::ecdh::{
     Secret,
@@ -73,4 +75,38 @@ fn test_key_container_dh() {
         .expect("Unable to derive EC keypair from key containers");
 
     assert_eq!(s1p2.pk, s2p1.pk)
-}
\ No newline at end of file
+}
+
+#[wasm_bindgen_test]
+fn test_key_zeroize() {
+    let secret: Secret = Secret::random(&mut OsRng);
+    let mut key: Key = secret.clone().into();
+    let pk: PublicKey = key.pk;
+
+    // Wiped secrets hold the scalar 1, the smallest a NonZeroScalar can be
+    let mut wiped: Secret = secret.clone();
+    wiped.zeroize();
+
+    assert_eq!(wiped.public_key(), Key::default().pk);
+    assert_ne!(wiped.public_key(), secret.public_key());
+
+    key.zeroize();
+
+    assert!(key.sk.is_none());
+    assert_eq!(key.pk, pk);
+}
+
+#[wasm_bindgen_test]
+fn test_key_set_secret_scalar() {
+    let mut key: Key = Secret::random(&mut OsRng).into();
+    let scalar: NonZeroScalar = NonZeroScalar::random(&mut OsRng);
+
+    // The key's own secret takes the scalar, along with its public key
+    key.set_secret_scalar(scalar).expect("Unable to set secret scalar");
+
+    assert_eq!(key.pk, PublicKey::from_secret_scalar(&scalar));
+    assert_eq!(key.sk.as_ref().expect("Key lost its secret").public_key(), key.pk);
+
+    let mut public: Key = Key::new(key.pk, None);
+    assert!(public.set_secret_scalar(scalar).is_err());
+}
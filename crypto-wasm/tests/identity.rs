@@ -0,0 +1,207 @@
+#![cfg(test)]
+#[macro_use]
+extern crate crypto_art;
+
+use wasm_bindgen_test::*;
+
+use crypto_art::log::*;
+
+use crypto_art::{
+    ecdh::Key,
+    ecdh::Secret,
+    mem::AllocatorPool,
+    mem::AllocatorCell,
+    tree::RatchetBranch,
+    tree::RatchetTree,
+    tree::RatchetError,
+    tree::RatchetErrorCause,
+    wire::UpdateKind,
+    wire::UpdateMessage,
+    identity::IdentityKey,
+    identity::verify_update,
+    roster::Member,
+    roster::Roster,
+    wire::TreeSnapshot
+};
+
+use bumpalo::{
+    Bump,
+    collections::Vec
+};
+
+use rand_core::OsRng;
+
+#[wasm_bindgen_test]
+fn test_identity_sign_verify() {
+    let root_allocator: Bump = AllocatorPool::create_bumpalo::<&Bump>(4);
+    let memory: AllocatorPool = AllocatorPool::new_with_init::<Key>(&root_allocator, 5, 32);
+    let tree: RatchetTree = RatchetTree::new(&memory).expect("Unable to create tree");
+
+    let identity: IdentityKey = IdentityKey::random(&mut OsRng);
+    let other: IdentityKey = IdentityKey::random(&mut OsRng);
+
+    let scratch: AllocatorCell = crypto_art::tree::branch_memory(&memory).expect("Unable to get branch memory");
+    let key: Key = Secret::random(&mut OsRng).into();
+    let branch: RatchetBranch = tree.insert(&key, &scratch).expect("Error inserting key into tree");
+
+    let mut message: UpdateMessage = UpdateMessage::new(UpdateKind::INSERT, 1, tree.tree_hash(), branch);
+
+    assert!(verify_update(&identity.verifying_key(), &message).is_err());
+
+    identity.sign_update(&mut message).expect("Unable to sign update");
+
+    assert!(verify_update(&identity.verifying_key(), &message).is_ok());
+    assert!(verify_update(&other.verifying_key(), &message).is_err());
+
+    // Signature survives the wire
+    let encoded: Vec<u8> = message.encode(&scratch).expect("Unable to encode update message");
+    let mut decoded: UpdateMessage = UpdateMessage::decode(&encoded, &scratch).expect("Unable to decode update message");
+
+    assert!(verify_update(&identity.verifying_key(), &decoded).is_ok());
+
+    // Epoch, index, sender & kind are all covered by the signature
+    decoded.branch.epoch = 4;
+    assert!(verify_update(&identity.verifying_key(), &decoded).is_err());
+    decoded.branch.epoch = 0;
+
+    decoded.branch.root = 2;
+    assert!(verify_update(&identity.verifying_key(), &decoded).is_err());
+    decoded.branch.root = 1;
+
+    decoded.sender = 2;
+    assert!(verify_update(&identity.verifying_key(), &decoded).is_err());
+    decoded.sender = 1;
+
+    decoded.kind = UpdateKind::UPDATE;
+    assert!(verify_update(&identity.verifying_key(), &decoded).is_err());
+    decoded.kind = UpdateKind::INSERT;
+
+    decoded.tree_hash[0] ^= 1;
+    assert!(verify_update(&identity.verifying_key(), &decoded).is_err());
+}
+
+#[wasm_bindgen_test]
+fn test_tree_apply_signed_update() {
+    let root_allocator: Bump = AllocatorPool::create_bumpalo::<&Bump>(12);
+    let test_allocator: Bump = AllocatorPool::create_bumpalo::<Key>(8);
+
+    let initiator_memory: AllocatorPool = AllocatorPool::new_with_init::<Key>(&root_allocator, 12, 32);
+    let member_memory: AllocatorPool = AllocatorPool::new_with_init::<Key>(&root_allocator, 12, 32);
+    let scratch: AllocatorCell = crypto_art::tree::branch_memory(&initiator_memory).expect("Unable to get branch memory");
+
+    let initiator: Key = Secret::random(&mut OsRng).into();
+    let setup_key: Key = Secret::random(&mut OsRng).into();
+
+    let mut prekeys: Vec<Key> = Vec::new_in(&test_allocator);
+    for _ in 0..3 {
+        prekeys.push(Secret::random(&mut OsRng).into());
+    }
+
+    let (mut tree, setup) = RatchetTree::setup(&initiator_memory, &initiator, &setup_key, &prekeys, &scratch)
+        .expect("Unable to setup group");
+    let mut member: RatchetTree = RatchetTree::from_setup(&member_memory, &setup, &prekeys[1], &scratch)
+        .expect("Unable to rebuild group for member");
+
+    let member_identity: IdentityKey = IdentityKey::random(&mut OsRng);
+    let impostor: IdentityKey = IdentityKey::random(&mut OsRng);
+
+    // The impostor is a member too, just not the one sitting in leaf 3
+    let mut snapshot: TreeSnapshot = tree.snapshot(false, &scratch);
+    snapshot.members.push((1, Member{ id: 1, identity: impostor.verifying_key() }));
+    snapshot.members.push((3, Member{ id: 3, identity: member_identity.verifying_key() }));
+
+    let mut roster: Roster = Roster::restore(&snapshot, &tree, &scratch).expect("Unable to restore roster");
+
+    // member (leaf 3) rotates their leaf
+    let rotated: Key = Secret::random(&mut OsRng).into();
+    let branch: RatchetBranch = member.ratchet(3, &rotated, &scratch).expect("Unable to ratchet member");
+    let mut message: UpdateMessage = UpdateMessage::new(UpdateKind::UPDATE, 3, member.tree_hash(), branch);
+
+    impostor.sign_update(&mut message).expect("Unable to sign update");
+
+    let error: RatchetError = tree.apply_signed_update(&message, &mut roster, None, 1, &initiator_memory, &scratch)
+        .expect_err("Applied update signed by impostor");
+    assert_eq!(error.cause, RatchetErrorCause::INVALID_SIGNATURE);
+
+    // Signing as themselves doesn't let another member update leaf 3 either
+    message.sender = 1;
+    impostor.sign_update(&mut message).expect("Unable to sign update");
+
+    let error: RatchetError = tree.apply_signed_update(&message, &mut roster, None, 1, &initiator_memory, &scratch)
+        .expect_err("Applied update to another member's leaf");
+    assert_eq!((error.cause, error.index), (RatchetErrorCause::INVALID_MEMBER, 3));
+
+    message.sender = 3;
+
+    // Updates sent from a leaf nobody is bound to are rejected, whoever signed them
+    let unbound: RatchetBranch = member.ratchet(2, &rotated, &scratch).expect("Unable to ratchet member");
+    let mut unbound: UpdateMessage = UpdateMessage::new(UpdateKind::UPDATE, 2, member.tree_hash(), unbound);
+    impostor.sign_update(&mut unbound).expect("Unable to sign update");
+
+    let error: RatchetError = tree.apply_signed_update(&unbound, &mut roster, None, 1, &initiator_memory, &scratch)
+        .expect_err("Applied update to an unbound leaf");
+    assert_eq!(error.cause, RatchetErrorCause::INVALID_MEMBER);
+
+    // An update computed against a diverged view of the tree is rejected even when properly signed
+    let tree_hash: [u8; crypto_art::tree::TREE_HASH_LEN] = message.tree_hash;
+    message.tree_hash[0] ^= 1;
+    member_identity.sign_update(&mut message).expect("Unable to sign update");
+
+    let error: RatchetError = tree.apply_signed_update(&message, &mut roster, None, 1, &initiator_memory, &scratch)
+        .expect_err("Applied update computed against a diverged tree");
+    assert_eq!(error.cause, RatchetErrorCause::INVALID_TREE_HASH);
+
+    message.tree_hash = tree_hash;
+    member_identity.sign_update(&mut message).expect("Unable to sign update");
+
+    let expected: Key = message.branch.get_last().expect("Empty update branch").clone();
+    let root: &Key = tree.apply_signed_update(&message, &mut roster, None, 1, &initiator_memory, &scratch)
+        .expect("Unable to apply signed update");
+
+    assert_eq!(root, &expected);
+
+    member.commit(&message.branch, &member_memory).expect("Unable to commit own update");
+
+    // member (leaf 3) adds someone, signing as the committer rather than the leaf filled
+    let joiner: IdentityKey = IdentityKey::random(&mut OsRng);
+    let joining: Member = Member{ id: 5, identity: joiner.verifying_key() };
+
+    let insert: RatchetBranch = member.insert(&Secret::random(&mut OsRng).into(), &scratch).expect("Unable to insert member");
+    let mut message: UpdateMessage = UpdateMessage::new(UpdateKind::UPDATE, 3, member.tree_hash(), insert);
+    member_identity.sign_update(&mut message).expect("Unable to sign insert");
+
+    let error: RatchetError = tree.apply_signed_update(&message, &mut roster, Some(joining), 1, &initiator_memory, &scratch)
+        .expect_err("Applied an insert sent as an update");
+    assert_eq!(error.cause, RatchetErrorCause::INVALID_BRANCH);
+
+    message.kind = UpdateKind::INSERT;
+    member_identity.sign_update(&mut message).expect("Unable to sign insert");
+
+    tree.apply_signed_update(&message, &mut roster, Some(joining), 1, &initiator_memory, &scratch)
+        .expect("Unable to apply signed insert");
+
+    assert_eq!(roster.get(5), Some(&joining));
+
+    member.commit(&message.branch, &member_memory).expect("Unable to commit own insert");
+
+    // Then removes them again, which unbinds the leaf
+    let remove: RatchetBranch = member.remove(5, &scratch).expect("Unable to remove member");
+    let mut message: UpdateMessage = UpdateMessage::new(UpdateKind::INSERT, 3, member.tree_hash(), remove);
+    member_identity.sign_update(&mut message).expect("Unable to sign remove");
+
+    let error: RatchetError = tree.apply_signed_update(&message, &mut roster, None, 1, &initiator_memory, &scratch)
+        .expect_err("Applied a removal sent as an insert");
+    assert_eq!(error.cause, RatchetErrorCause::INVALID_BRANCH);
+
+    message.kind = UpdateKind::REMOVE;
+    member_identity.sign_update(&mut message).expect("Unable to sign remove");
+
+    tree.apply_signed_update(&message, &mut roster, None, 1, &initiator_memory, &scratch)
+        .expect("Unable to apply signed remove");
+
+    assert_eq!(roster.get(5), None);
+    assert_eq!(roster.len(), 2);
+
+    member.commit(&message.branch, &member_memory).expect("Unable to commit own remove");
+    assert_eq!(tree.tree_hash(), member.tree_hash());
+}
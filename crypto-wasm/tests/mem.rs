@@ -18,6 +18,10 @@ use crypto_art::{
     mem::SharedRingBuffer,
     mem::AllocatorPool,
     mem::AllocatorCell,
+    mem::AllocatorSlot,
+    mem::AllocatorStats,
+    mem::PoolStats,
+    mem::MemoryPool,
     sync::AtomicLockJS,
     log::*
 };
@@ -385,18 +389,18 @@ fn test_allocator_pool_cell_drop() {
     let alloc: Bump = AllocatorPool::create_bumpalo::<&Bump>(4);
     let pool: AllocatorPool = AllocatorPool::new_with_init::<usize>(&alloc, 4, 4);
 
-    let previous_allocated_bytes: usize = pool.get(0).allocated_bytes();
+    let previous_allocated_bytes: usize = pool.get(0).expect("No allocator at index 0").allocated_bytes();
 
     // After this block is complete, drop should be called on the AllocatorCell, which resets the allocator
     {
-        let cell: AllocatorCell = pool.get(0);
+        let cell: AllocatorCell = pool.get(0).expect("No allocator at index 0");
 
         cell.alloc_slice_fill_with(64, |_| return 1);
 
         assert_ne!(previous_allocated_bytes, cell.allocated_bytes());
     }
 
-    let cell: AllocatorCell = pool.get(0);
+    let cell: AllocatorCell = pool.get(0).expect("No allocator at index 0");
 
     // Memory should have been reset as the pool cell no longer had any references living to it
     assert_eq!(previous_allocated_bytes, cell.allocated_bytes());
@@ -407,7 +411,7 @@ fn test_allocator_pool_clear() {
     let alloc: Bump = AllocatorPool::create_bumpalo::<&Bump>(4);
     let mut pool: AllocatorPool = AllocatorPool::new_with_init::<usize>(&alloc, 4, 4);
 
-    let cell: AllocatorCell = pool.get(0);
+    let cell: AllocatorCell = pool.get(0).expect("No allocator at index 0");
     assert!(pool.clear(0).is_err());
     assert!(pool.clear(1).is_ok());
 
@@ -442,4 +446,235 @@ fn test_allocator_pool_shrink() {
 
     pool.shrink(2);
     assert_eq!(pool.capacity(), 2);
-}
\ No newline at end of file
+}
+#[wasm_bindgen_test]
+fn test_allocator_pool_slots() {
+    let alloc: Bump = AllocatorPool::create_bumpalo::<&Bump>(4);
+    let mut pool: AllocatorPool = AllocatorPool::new_with_init::<usize>(&alloc, 2, 4);
+
+    // Registered slots claim the allocators the pool already holds first
+    let buffer: AllocatorSlot<usize> = pool.register::<usize>("buffer", 4).expect("Unable to register buffer slot");
+    let messages: AllocatorSlot<u64> = pool.register::<u64>("messages", 4).expect("Unable to register messages slot");
+
+    assert_eq!(buffer.index(), 0);
+    assert_eq!(messages.index(), 1);
+    assert_eq!(pool.unclaimed(), 0);
+
+    // Same name & type is the same slot, a different type is refused
+    assert_eq!(pool.register::<usize>("buffer", 8).expect("Unable to re-register buffer slot").index(), 0);
+    assert!(pool.register::<u8>("buffer", 4).is_err());
+    assert!(pool.slot::<u8>("buffer").is_err());
+    assert_eq!(pool.slot::<u64>("messages").expect("No messages slot").capacity(), 4);
+
+    // Then grows the pool
+    let tree: AllocatorSlot<usize> = pool.register::<usize>("tree", 4).expect("Unable to register tree slot");
+
+    assert_eq!(tree.index(), 2);
+    assert_eq!(pool.len(), 3);
+
+    let cell: AllocatorCell = pool.get_slot(&tree).expect("No allocator for tree slot");
+    cell.alloc_slice_fill_with(4, |_| return 1usize);
+    drop(cell);
+
+    assert!(pool.slot::<usize>("missing").is_err());
+    assert!(pool.get(pool.len()).is_err());
+
+    // Allocators provisioned without a name are left for the next slot to claim
+    let spare: usize = pool.provision::<usize>(4).expect("Unable to provision allocator");
+
+    assert_eq!(pool.register::<usize>("spare", 4).expect("Unable to register spare slot").index(), spare);
+    assert_eq!(pool.register::<usize>("more", 4).expect("Unable to register more slot").index(), 4);
+    assert_eq!(pool.len(), 5);
+
+    // Slots go with their allocators
+    pool.shrink(1);
+    assert!(pool.slot::<u64>("messages").is_err());
+    assert!(pool.get_slot(&buffer).is_ok());
+}
+
+#[wasm_bindgen_test]
+fn test_allocator_pool_refs_outlive_growth() {
+    let alloc: Bump = AllocatorPool::create_bumpalo::<&Bump>(4);
+    let pool: AllocatorPool = AllocatorPool::new_with_init::<usize>(&alloc, 1, 4);
+
+    let buffer: AllocatorSlot<usize> = pool.register::<usize>("buffer", 4).expect("Unable to register buffer slot");
+    let cell: &AllocatorCell = pool.get_slot_ref(&buffer).expect("No allocator for buffer slot");
+
+    // Registering past the pool's length moves the list of cells, never the cells themselves
+    for i in 0..32 {
+        pool.register::<usize>(&format!("grown.{}", i), 4).expect("Unable to register slot");
+    }
+
+    assert_eq!(pool.len(), 33);
+    assert!(core::ptr::eq(cell, pool.get_slot_ref(&buffer).expect("No allocator for buffer slot")));
+
+    cell.alloc_slice_fill_with(4, |_| return 1usize);
+    assert!(pool.slot_stats(&buffer).expect("No stats for buffer slot").allocated_bytes > 0);
+}
+
+#[wasm_bindgen_test]
+fn test_allocator_pool_release() {
+    let alloc: Bump = AllocatorPool::create_bumpalo::<&Bump>(4);
+    let pool: AllocatorPool = AllocatorPool::new_with_init::<usize>(&alloc, 4, 4);
+
+    let buffer: AllocatorSlot<usize> = pool.register::<usize>("buffer", 4).expect("Unable to register buffer slot");
+    pool.register::<usize>("messages", 4).expect("Unable to register messages slot");
+
+    // Slots registered from the callback don't disturb the walk, they're seen next time round
+    let mut names: usize = 0;
+    pool.for_each_slot(|name, _| {
+        pool.register::<usize>(&format!("{}.copy", name), 4).expect("Unable to register slot from callback");
+        names += 1;
+    });
+
+    assert_eq!(names, 2);
+    assert!(pool.has_slot("buffer.copy"));
+    assert!(pool.has_slot("messages.copy"));
+    assert_eq!(pool.unclaimed(), 0);
+
+    // A released slot's allocator goes to the next slot registered
+    assert!(pool.release("buffer").is_ok());
+    assert!(!pool.has_slot("buffer"));
+    assert!(pool.release("buffer").is_err());
+    assert_eq!(pool.unclaimed(), 1);
+    assert_eq!(pool.register::<u64>("reused", 4).expect("Unable to register reused slot").index(), buffer.index());
+
+    // Released allocators are reset, & no longer secret for whoever claims them next
+    let secrets: AllocatorSlot<u8> = pool.register_secret::<u8>("secrets", 32).expect("Unable to register secrets slot");
+    let written: *const u8 = pool.get_slot_ref(&secrets).expect("No allocator for secrets slot").alloc_slice_fill_copy(32, 0xffu8).as_ptr();
+
+    assert!(pool.release("secrets").is_ok());
+
+    let stats: AllocatorStats = pool.slot_stats(&secrets).expect("No stats for secrets slot");
+
+    assert_eq!(stats.resets, 1);
+    assert_eq!(stats.allocated_bytes, 0);
+    assert!(!pool.get_slot_ref(&secrets).expect("No allocator for secrets slot").is_secret());
+
+    for i in 0..32 {
+        assert_eq!(unsafe { core::ptr::read_volatile(written.add(i)) }, 0);
+    }
+
+    // Unless a clone of the cell is still held, which resets it once dropped
+    let held: AllocatorSlot<u8> = pool.register::<u8>("held", 32).expect("Unable to register held slot");
+    let cell: AllocatorCell = pool.get_slot(&held).expect("No allocator for held slot");
+    cell.alloc_slice_fill_copy(32, 0xffu8);
+
+    assert!(pool.release("held").is_ok());
+    assert_eq!(pool.slot_stats(&held).expect("No stats for held slot").resets, 1);
+
+    drop(cell);
+    assert_eq!(pool.slot_stats(&held).expect("No stats for held slot").resets, 2);
+}
+
+#[wasm_bindgen_test]
+fn test_allocator_pool_stats() {
+    let alloc: Bump = AllocatorPool::create_bumpalo::<&Bump>(4);
+    let mut pool: AllocatorPool = AllocatorPool::new_with_init::<usize>(&alloc, 4, 4);
+
+    let buffer: AllocatorSlot<usize> = pool.register::<usize>("buffer", 4).expect("Unable to register buffer slot");
+    let empty: AllocatorStats = pool.slot_stats(&buffer).expect("No stats for buffer slot");
+
+    assert_eq!(empty.resets, 0);
+    assert_eq!(empty.references, 1);
+
+    {
+        let cell: AllocatorCell = pool.get_slot(&buffer).expect("No allocator for buffer slot");
+        cell.alloc_slice_fill_with(16, |_| return 1usize);
+
+        let stats: AllocatorStats = pool.slot_stats(&buffer).expect("No stats for buffer slot");
+
+        assert_eq!(stats.references, 2);
+        assert!(stats.allocated_bytes > empty.allocated_bytes);
+        assert!(stats.chunk_capacity >= stats.allocated_bytes - empty.allocated_bytes);
+    }
+
+    // Dropping the last clone reset the allocator
+    let stats: AllocatorStats = pool.stats(buffer.index()).expect("No stats for buffer slot");
+
+    assert_eq!(stats.resets, 1);
+    assert_eq!(stats.references, 1);
+    assert_eq!(stats.allocated_bytes, empty.allocated_bytes);
+
+    assert!(pool.clear(buffer.index()).is_ok());
+
+    // Clearing is refused while a clone of the cell is still held, dropping that clone resets it instead
+    let held: AllocatorCell = pool.get_slot(&buffer).expect("No allocator for buffer slot");
+    assert!(pool.clear(buffer.index()).is_err());
+    drop(held);
+
+    assert_eq!(pool.slot_stats(&buffer).expect("No stats for buffer slot").resets, 3);
+
+    let mut names: usize = 0;
+    pool.for_each_slot(|name, stats| {
+        assert_eq!(name, "buffer");
+        assert_eq!(stats.resets, 3);
+        names += 1;
+    });
+    assert_eq!(names, 1);
+
+    let total: AllocatorStats = pool.total_stats();
+
+    assert_eq!(total.references, 4);
+    assert_eq!(total.resets, 3);
+    assert!(total.allocated_bytes >= 4 * empty.allocated_bytes);
+    assert!(pool.root_bytes() > 0);
+    assert!(pool.stats(4).is_err());
+}
+
+#[wasm_bindgen_test]
+fn test_allocator_pool_secret_slots() {
+    let alloc: Bump = AllocatorPool::create_bumpalo::<&Bump>(4);
+    let pool: AllocatorPool = AllocatorPool::new_with_init::<usize>(&alloc, 2, 4);
+
+    let secrets: AllocatorSlot<u8> = pool.register_secret::<u8>("secrets", 32).expect("Unable to register secrets slot");
+    let public: AllocatorSlot<u8> = pool.register::<u8>("public", 32).expect("Unable to register public slot");
+
+    assert!(pool.get_slot_ref(&secrets).expect("No allocator for secrets slot").is_secret());
+    assert!(!pool.get_slot_ref(&public).expect("No allocator for public slot").is_secret());
+
+    let written: *const u8 = {
+        let cell: AllocatorCell = pool.get_slot(&secrets).expect("No allocator for secrets slot");
+        cell.alloc_slice_fill_copy(32, 0xffu8).as_ptr()
+    };
+
+    // The allocator keeps its chunk across the reset, which has to have wiped it
+    assert_eq!(pool.slot_stats(&secrets).expect("No stats for secrets slot").resets, 1);
+
+    for i in 0..32 {
+        assert_eq!(unsafe { core::ptr::read_volatile(written.add(i)) }, 0);
+    }
+}
+
+#[wasm_bindgen_test]
+fn test_memory_pool_report() {
+    let memory: MemoryPool = MemoryPool::new(2, 64).expect("Unable to create memory pool");
+
+    assert_eq!(memory.pool().len(), 2);
+    assert_eq!(memory.stats().slot_count(), 0);
+
+    let buffer: AllocatorSlot<u8> = memory.pool().register::<u8>("buffer", 64).expect("Unable to register buffer slot");
+    memory.pool().register::<u8>("messages", 64).expect("Unable to register messages slot");
+    memory.pool().register::<u8>("grown", 64).expect("Unable to register grown slot");
+
+    let cell: AllocatorCell = memory.pool().get_slot(&buffer).expect("No allocator for buffer slot");
+    cell.alloc_slice_fill_copy(32, 1u8);
+
+    // Every slot by name, in the order registered, & the pool summed
+    let stats: PoolStats = memory.stats();
+
+    assert_eq!(stats.slot_count(), 3);
+    assert_eq!(stats.slot_name(0).as_deref(), Some("buffer"));
+    assert_eq!(stats.slot_name(2).as_deref(), Some("grown"));
+    assert_eq!(stats.slot_name(3), None);
+    assert_eq!(stats.slot_stats(0), stats.get("buffer"));
+    assert_eq!(stats.slot_stats(0).expect("No stats for buffer slot").references, 2);
+    assert!(stats.slot_stats(0).expect("No stats for buffer slot").allocated_bytes >= 32);
+
+    assert_eq!(stats.total(), memory.pool().total_stats());
+    assert_eq!(stats.total().references, 4);
+    assert_eq!(stats.slots().len(), 3);
+
+    drop(cell);
+    assert_eq!(memory.stats().get("buffer").expect("No stats for buffer slot").resets, 1);
+}
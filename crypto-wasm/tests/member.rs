@@ -0,0 +1,142 @@
+#![cfg(test)]
+#[macro_use]
+extern crate crypto_art;
+
+use wasm_bindgen_test::*;
+
+use crypto_art::log::*;
+
+use crypto_art::{
+    ecdh::Key,
+    ecdh::Secret,
+    mem::AllocatorPool,
+    mem::AllocatorCell,
+    tree::RatchetBranch,
+    tree::RatchetTree,
+    tree::RatchetError,
+    tree::RatchetErrorCause,
+    member::MemberTree
+};
+
+use bumpalo::{
+    Bump,
+    collections::Vec
+};
+
+use rand_core::OsRng;
+
+#[wasm_bindgen_test]
+fn test_member_tree_updates() {
+    let root_allocator: Bump = AllocatorPool::create_bumpalo::<&Bump>(12);
+    let test_allocator: Bump = AllocatorPool::create_bumpalo::<Key>(8);
+
+    let initiator_memory: AllocatorPool = AllocatorPool::new_with_init::<Key>(&root_allocator, 12, 32);
+    let member_memory: AllocatorPool = AllocatorPool::new_with_init::<Key>(&root_allocator, 12, 32);
+    let scratch: AllocatorCell = crypto_art::tree::branch_memory(&initiator_memory).expect("Unable to get branch memory");
+    let view_memory: AllocatorCell = crypto_art::tree::branch_memory(&member_memory).expect("Unable to get branch memory");
+
+    let initiator: Key = Secret::random(&mut OsRng).into();
+    let setup_key: Key = Secret::random(&mut OsRng).into();
+
+    let mut prekeys: Vec<Key> = Vec::new_in(&test_allocator);
+    for _ in 0..3 {
+        prekeys.push(Secret::random(&mut OsRng).into());
+    }
+
+    let (mut tree, setup) = RatchetTree::setup(&initiator_memory, &initiator, &setup_key, &prekeys, &scratch)
+        .expect("Unable to setup group");
+    let full: RatchetTree = RatchetTree::from_setup(&member_memory, &setup, &prekeys[1], &scratch)
+        .expect("Unable to rebuild group for member");
+
+    // Leaf 2 belongs to someone else, we only hold its public key
+    let error: RatchetError = MemberTree::from_tree(&full, 2, &view_memory).err().expect("Built view without leaf secret");
+    assert_eq!(error.cause, RatchetErrorCause::MISSING_SECRET);
+
+    let mut view: MemberTree = MemberTree::from_tree(&full, 3, &view_memory).expect("Unable to build member view");
+
+    assert_eq!(view.get_root(), tree.get_root());
+    assert_eq!(view.get_copath().len(), view.height());
+    assert!(view.get_path().iter().all(|key| key.sk.is_some()));
+    assert!(view.get_copath().iter().all(|key| key.sk.is_none()));
+
+    // Anything off our own path is refused
+    let foreign: Key = Secret::random(&mut OsRng).into();
+    let error: RatchetError = view.ratchet(1, &foreign, &scratch).err().expect("Ratcheted a foreign leaf");
+    assert_eq!(error.cause, RatchetErrorCause::MISSING_SECRET);
+
+    let error: RatchetError = view.remove(3, &scratch).err().expect("Removed own leaf without sibling secrets");
+    assert_eq!(error.cause, RatchetErrorCause::MISSING_SECRET);
+
+    // Our own update, applied by the initiator
+    let rotated: Key = Secret::random(&mut OsRng).into();
+    let update: RatchetBranch = view.ratchet(3, &rotated, &scratch).expect("Unable to ratchet own leaf");
+
+    view.commit(&update).expect("Unable to commit own update");
+    tree.apply_update(&update, 1, &initiator_memory, &scratch).expect("Initiator unable to apply update");
+    assert_eq!(view.get_root(), tree.get_root());
+
+    // Initiator's update crosses our copath
+    let rotated: Key = Secret::random(&mut OsRng).into();
+    let update: RatchetBranch = tree.ratchet(1, &rotated, &scratch).expect("Unable to ratchet initiator");
+
+    tree.commit(&update, &initiator_memory).expect("Unable to commit initiator update");
+    view.apply_update(&update, &scratch).expect("Unable to apply initiator update");
+    assert_eq!(view.get_root(), tree.get_root());
+    assert!(view.get_root().expect("No root").sk.is_some());
+
+    // An insert grows the tree by a layer
+    let joining: Key = Secret::random(&mut OsRng).into();
+    let insert: RatchetBranch = tree.insert(&joining, &scratch).expect("Unable to compute insert");
+
+    tree.commit(&insert, &initiator_memory).expect("Unable to commit insert");
+    view.apply_update(&insert, &scratch).expect("Unable to apply insert");
+
+    assert_eq!(view.height(), 3);
+    assert_eq!(view.get_root(), tree.get_root());
+
+    // Tampered branches are rejected before anything is written
+    let mut tampered: RatchetBranch = tree.ratchet(2, &Secret::random(&mut OsRng).into(), &scratch).expect("Unable to ratchet");
+    let last: usize = tampered.len() - 1;
+    tampered.nodes[last] = Secret::random(&mut OsRng).into();
+
+    let error: RatchetError = view.apply_update(&tampered, &scratch).err().expect("Tampered update applied");
+    assert_eq!(error.cause, RatchetErrorCause::INVALID_BRANCH);
+    assert_eq!(view.get_root(), tree.get_root());
+}
+
+#[wasm_bindgen_test]
+fn test_member_tree_shrinks_with_tree() {
+    let root_allocator: Bump = AllocatorPool::create_bumpalo::<&Bump>(12);
+    let test_allocator: Bump = AllocatorPool::create_bumpalo::<Key>(8);
+
+    let memory: AllocatorPool = AllocatorPool::new_with_init::<Key>(&root_allocator, 12, 32);
+    let view_pool: AllocatorPool = AllocatorPool::new_with_init::<Key>(&root_allocator, 12, 32);
+    let scratch: AllocatorCell = crypto_art::tree::branch_memory(&memory).expect("Unable to get branch memory");
+    let view_memory: AllocatorCell = crypto_art::tree::branch_memory(&view_pool).expect("Unable to get branch memory");
+
+    let mut keys: Vec<Key> = Vec::new_in(&test_allocator);
+    for _ in 0..5 {
+        keys.push(Secret::random(&mut OsRng).into());
+    }
+
+    let mut tree: RatchetTree = RatchetTree::from_leaves(&memory, &keys).expect("Unable to build tree");
+    let mut view: MemberTree = MemberTree::from_tree(&tree, 1, &view_memory).expect("Unable to build member view");
+
+    // Removing the last leaf shrinks both by a layer at the same epoch
+    let remove: RatchetBranch = tree.remove(5, &scratch).expect("Unable to remove leaf 5");
+    view.apply_update(&remove, &scratch).expect("Unable to apply removal");
+    tree.commit(&remove, &memory).expect("Unable to commit removal");
+
+    assert_eq!(tree.height(), 2);
+    assert_eq!(view.height(), 2);
+    assert_eq!(view.epoch(), tree.epoch());
+    assert_eq!(view.get_root(), tree.get_root());
+    assert_eq!(view.get_copath().len(), view.height());
+
+    // Later updates ratchet against the shrunk tree
+    let update: RatchetBranch = view.ratchet(1, &Secret::random(&mut OsRng).into(), &scratch).expect("Unable to ratchet own leaf");
+    tree.commit(&update, &memory).expect("Unable to commit member update");
+    view.commit(&update).expect("Unable to commit own update");
+
+    assert_eq!(view.get_root(), tree.get_root());
+}
@@ -0,0 +1,190 @@
+#![cfg(test)]
+#[macro_use]
+extern crate crypto_art;
+
+use wasm_bindgen_test::*;
+
+use crypto_art::log::*;
+
+use crypto_art::{
+    ecdh::Key,
+    ecdh::Secret,
+    mem::AllocatorPool,
+    mem::AllocatorCell,
+    tree::RatchetBranch,
+    tree::RatchetTree,
+    tree::RatchetError,
+    tree::RatchetErrorCause,
+    identity::IdentityKey,
+    oplog::OperationLog,
+    roster::Member,
+    roster::Roster,
+    wire::UpdateKind
+};
+
+use bumpalo::{
+    Bump,
+    collections::Vec
+};
+
+use rand_core::OsRng;
+
+fn member(id: u64) -> Member {
+    return Member {
+        id: id,
+        identity: IdentityKey::random(&mut OsRng).verifying_key()
+    };
+}
+
+fn assert_replays(log: &OperationLog, tree: &RatchetTree, roster: &Roster, root_allocator: &Bump) {
+    let memory: AllocatorPool = AllocatorPool::new_with_init::<Key>(root_allocator, 12, 32);
+    let roster_memory: AllocatorCell = AllocatorCell::new(root_allocator, AllocatorPool::create_bumpalo::<Member>(8));
+    let scratch: AllocatorCell = crypto_art::tree::branch_memory(&memory).expect("Unable to get branch memory");
+    let (replayed, replayed_roster) = log.replay(&memory, &roster_memory, &scratch).expect("Unable to replay log");
+
+    assert_eq!(replayed.tree_hash(), tree.tree_hash());
+    assert_eq!(replayed.epoch(), tree.epoch());
+    assert_eq!(replayed.get_orphans(), tree.get_orphans());
+    assert_eq!(replayed.get_root(), tree.get_root());
+    assert!(replayed.verify(&scratch).is_empty());
+
+    // Member bindings come back with the tree
+    assert_eq!(replayed_roster.len(), roster.len());
+
+    for (index, member) in roster.iter() {
+        assert_eq!(replayed_roster.get(index), Some(member));
+    }
+}
+
+#[wasm_bindgen_test]
+fn test_oplog_replay_checkpoint() {
+    let root_allocator: Bump = AllocatorPool::create_bumpalo::<&Bump>(12);
+    let test_allocator: Bump = AllocatorPool::create_bumpalo::<Key>(8);
+
+    let memory: AllocatorPool = AllocatorPool::new_with_init::<Key>(&root_allocator, 12, 32);
+    let log_memory: AllocatorCell = AllocatorCell::new(&root_allocator, AllocatorPool::create_bumpalo::<Key>(64));
+    let scratch: AllocatorCell = crypto_art::tree::branch_memory(&memory).expect("Unable to get branch memory");
+
+    let roster_memory: AllocatorCell = AllocatorCell::new(&root_allocator, AllocatorPool::create_bumpalo::<Member>(8));
+
+    let mut tree: RatchetTree = RatchetTree::new(&memory).expect("Unable to create tree");
+    let mut roster: Roster = Roster::new(&roster_memory);
+    let mut log: OperationLog = OperationLog::new(&log_memory);
+
+    for id in 100..103 {
+        let branch: RatchetBranch = tree.insert(&Secret::random(&mut OsRng).into(), &scratch).expect("Unable to insert");
+        log.commit(&mut tree, &mut roster, &branch, Some(member(id)), &memory).expect("Unable to commit insert");
+    }
+
+    let branch: RatchetBranch = tree.ratchet(2, &Secret::random(&mut OsRng).into(), &scratch).expect("Unable to ratchet");
+    log.commit(&mut tree, &mut roster, &branch, None, &memory).expect("Unable to commit update");
+
+    let removed: Member = *roster.get(1).expect("No member at leaf 1");
+    let branch: RatchetBranch = tree.remove(1, &scratch).expect("Unable to remove");
+    log.commit(&mut tree, &mut roster, &branch, None, &memory).expect("Unable to commit removal");
+
+    let mut keys: Vec<Key> = Vec::new_in(&test_allocator);
+    keys.push(Secret::random(&mut OsRng).into());
+    keys.push(Secret::random(&mut OsRng).into());
+
+    let mut branches: Vec<RatchetBranch> = Vec::new_in(&test_allocator);
+    branches.push(tree.ratchet(3, &keys[0], &scratch).expect("Unable to ratchet"));
+    branches.push(tree.ratchet(2, &keys[1], &scratch).expect("Unable to ratchet"));
+    log.commit_batch(&mut tree, &mut roster, &branches, &[None, None], &memory, &scratch).expect("Unable to commit batch");
+
+    let kinds: Vec<UpdateKind> = {
+        let mut kinds: Vec<UpdateKind> = Vec::new_in(&test_allocator);
+        kinds.extend(log.entries().iter().map(|entry| entry.kind));
+        kinds
+    };
+
+    assert_eq!(kinds.as_slice(), &[
+        UpdateKind::INSERT, UpdateKind::INSERT, UpdateKind::INSERT,
+        UpdateKind::UPDATE, UpdateKind::REMOVE, UpdateKind::UPDATE, UpdateKind::UPDATE
+    ]);
+    assert_eq!(log.epoch(), 6);
+    assert!(log.entries().iter().all(|entry| entry.branch.iter().all(|key| key.sk.is_none())));
+
+    // Inserts log who joined, removals who left
+    assert_eq!(log.get_member(0).map(|member| member.id), Some(100));
+    assert_eq!(log.get_member(3), None);
+    assert_eq!(log.get_member(4), Some(&removed));
+
+    assert_replays(&log, &tree, &roster, &root_allocator);
+
+    // Compact, then keep going on top of the checkpoint, which keeps the roster
+    log.checkpoint(&tree, &roster).expect("Unable to checkpoint");
+    assert_eq!(log.len(), 0);
+    assert_eq!(log.epoch(), 6);
+    assert_eq!(log.get_checkpoint().expect("No checkpoint").members.len(), 2);
+    assert_replays(&log, &tree, &roster, &root_allocator);
+
+    for id in 103..105 {
+        let branch: RatchetBranch = tree.insert(&Secret::random(&mut OsRng).into(), &scratch).expect("Unable to insert");
+        log.commit(&mut tree, &mut roster, &branch, Some(member(id)), &memory).expect("Unable to commit insert");
+    }
+
+    assert_eq!(log.len(), 2);
+    assert_eq!(roster.index_of(103), Some(1));
+    assert_replays(&log, &tree, &roster, &root_allocator);
+
+    // Rolled back commits leave the log & roster too, but never past the checkpoint
+    log.rollback(&mut tree, &mut roster, 1).expect("Unable to roll back");
+    assert_eq!(log.len(), 1);
+    assert_eq!(roster.index_of(104), None);
+    assert_replays(&log, &tree, &roster, &root_allocator);
+
+    let error: RatchetError = log.rollback(&mut tree, &mut roster, 2).err().expect("Rolled back past the checkpoint");
+    assert_eq!(error.cause, RatchetErrorCause::JOURNAL_EXHAUSTED);
+
+    // Commits made around the log are caught
+    let branch: RatchetBranch = tree.ratchet(2, &Secret::random(&mut OsRng).into(), &scratch).expect("Unable to ratchet");
+    tree.commit(&branch, &memory).expect("Unable to commit update");
+
+    let branch: RatchetBranch = tree.ratchet(3, &Secret::random(&mut OsRng).into(), &scratch).expect("Unable to ratchet");
+    let error: RatchetError = log.commit(&mut tree, &mut roster, &branch, None, &memory).err().expect("Logged commit on a tree ahead of the log");
+    assert_eq!(error.cause, RatchetErrorCause::STALE_EPOCH);
+}
+
+#[wasm_bindgen_test]
+fn test_oplog_replay_merged_batch() {
+    let root_allocator: Bump = AllocatorPool::create_bumpalo::<&Bump>(12);
+    let test_allocator: Bump = AllocatorPool::create_bumpalo::<Key>(8);
+
+    let memory: AllocatorPool = AllocatorPool::new_with_init::<Key>(&root_allocator, 12, 32);
+    let log_memory: AllocatorCell = AllocatorCell::new(&root_allocator, AllocatorPool::create_bumpalo::<Key>(64));
+    let scratch: AllocatorCell = crypto_art::tree::branch_memory(&memory).expect("Unable to get branch memory");
+
+    let mut keys: Vec<Key> = Vec::new_in(&test_allocator);
+    for _ in 0..4 {
+        keys.push(Secret::random(&mut OsRng).into());
+    }
+
+    let roster_memory: AllocatorCell = AllocatorCell::new(&root_allocator, AllocatorPool::create_bumpalo::<Member>(8));
+
+    let mut tree: RatchetTree = RatchetTree::from_leaves(&memory, &keys).expect("Unable to build tree");
+    let mut roster: Roster = Roster::new(&roster_memory);
+    let mut log: OperationLog = OperationLog::new(&log_memory);
+
+    log.checkpoint(&tree, &roster).expect("Unable to checkpoint");
+
+    // Leaves either side of the root update while a fifth member joins, so the merge recomputes nodes
+    // all the way up to a new root that no single logged branch was computed with
+    let mut branches: Vec<RatchetBranch> = Vec::new_in(&test_allocator);
+    branches.push(tree.ratchet(1, &Secret::random(&mut OsRng).into(), &scratch).expect("Unable to ratchet"));
+    branches.push(tree.ratchet(4, &Secret::random(&mut OsRng).into(), &scratch).expect("Unable to ratchet"));
+    branches.push(tree.insert(&Secret::random(&mut OsRng).into(), &scratch).expect("Unable to insert"));
+
+    log.commit_batch(&mut tree, &mut roster, &branches, &[None, None, Some(member(104))], &memory, &scratch).expect("Unable to commit batch");
+
+    assert_eq!(log.len(), 3);
+    assert_eq!(tree.height(), 3);
+    assert_eq!(roster.index_of(104), Some(5));
+    assert_replays(&log, &tree, &roster, &root_allocator);
+
+    // And on top of it, single commits keep replaying
+    let branch: RatchetBranch = tree.ratchet(3, &Secret::random(&mut OsRng).into(), &scratch).expect("Unable to ratchet");
+    log.commit(&mut tree, &mut roster, &branch, None, &memory).expect("Unable to commit update");
+
+    assert_replays(&log, &tree, &roster, &root_allocator);
+}
@@ -0,0 +1,371 @@
+#![cfg(test)]
+#[macro_use]
+extern crate crypto_art;
+
+use wasm_bindgen_test::*;
+
+use crypto_art::log::*;
+
+use crypto_art::{
+    ecdh::Key,
+    ecdh::Secret,
+    mem::AllocatorPool,
+    mem::AllocatorCell,
+    tree::RatchetBranch,
+    tree::RatchetTree,
+    tree::RatchetError,
+    tree::RatchetErrorCause,
+    tree::ForkResolution,
+    tree::TreeChanges,
+    identity::IdentityKey,
+    roster::Member,
+    roster::Roster,
+    wire::TreeSnapshot
+};
+
+use bumpalo::{
+    Bump,
+    collections::Vec
+};
+
+use rand_core::OsRng;
+
+fn member(id: u64) -> Member {
+    return Member {
+        id: id,
+        identity: IdentityKey::random(&mut OsRng).verifying_key()
+    };
+}
+
+#[wasm_bindgen_test]
+fn test_roster_insert_remove_reuse() {
+    let root_allocator: Bump = AllocatorPool::create_bumpalo::<&Bump>(12);
+    let memory: AllocatorPool = AllocatorPool::new_with_init::<Key>(&root_allocator, 12, 32);
+    let roster_memory: AllocatorCell = AllocatorCell::new(&root_allocator, AllocatorPool::create_bumpalo::<Member>(8));
+
+    let mut tree: RatchetTree = RatchetTree::new(&memory).expect("Unable to create tree");
+    let mut roster: Roster = Roster::new(&roster_memory);
+    let scratch: AllocatorCell = crypto_art::tree::branch_memory(&memory).expect("Unable to get branch memory");
+
+    for id in 100..104 {
+        let key: Key = Secret::random(&mut OsRng).into();
+        let branch: RatchetBranch = tree.insert(&key, &scratch).expect("Error inserting key into tree");
+
+        roster.commit(&mut tree, &branch, Some(member(id)), &memory).expect("Unable to commit insert through roster");
+    }
+
+    assert_eq!(roster.len(), 4);
+    assert_eq!(roster.index_of(100), Some(1));
+    assert_eq!(roster.index_of(103), Some(4));
+    assert_eq!(roster.get(2).expect("No member at leaf 2").id, 101);
+
+    let identity = roster.get(3).expect("No member at leaf 3").identity;
+    assert_eq!(roster.index_of_identity(&identity), Some(3));
+
+    // Updates keep the sitting member
+    let key: Key = Secret::random(&mut OsRng).into();
+    let branch: RatchetBranch = tree.ratchet(2, &key, &scratch).expect("Unable to ratchet tree");
+    roster.commit(&mut tree, &branch, None, &memory).expect("Unable to commit update through roster");
+
+    assert_eq!(roster.index_of(101), Some(2));
+
+    // Inserts must name a member, updates can't swap the sitting member
+    let key: Key = Secret::random(&mut OsRng).into();
+    let branch: RatchetBranch = tree.insert(&key, &scratch).expect("Error inserting key into tree");
+    let error: RatchetError = roster.commit(&mut tree, &branch, None, &memory).expect_err("Inserted without member");
+    assert_eq!(error.cause, RatchetErrorCause::INVALID_MEMBER);
+
+    let branch: RatchetBranch = tree.ratchet(2, &key, &scratch).expect("Unable to ratchet tree");
+    let error: RatchetError = roster.commit(&mut tree, &branch, Some(member(200)), &memory).expect_err("Swapped sitting member");
+    assert_eq!(error.cause, RatchetErrorCause::INVALID_MEMBER);
+
+    // Removal unbinds the leaf
+    let branch: RatchetBranch = tree.remove(2, &scratch).expect("Unable to compute remove for tree");
+    roster.commit(&mut tree, &branch, None, &memory).expect("Unable to commit remove through roster");
+
+    assert_eq!(roster.len(), 3);
+    assert_eq!(roster.index_of(101), None);
+    assert!(roster.get(2).is_none());
+
+    // Existing members can't join twice, and nothing is written when they try
+    let key: Key = Secret::random(&mut OsRng).into();
+    let branch: RatchetBranch = tree.insert(&key, &scratch).expect("Error inserting key into tree");
+    let duplicate: Member = *roster.get(1).expect("No member at leaf 1");
+
+    let error: RatchetError = roster.commit(&mut tree, &branch, Some(duplicate), &memory).expect_err("Member joined twice");
+    assert_eq!(error.cause, RatchetErrorCause::INVALID_MEMBER);
+    assert_eq!(tree.get(0, 2), tree.tombstone.as_ref());
+
+    // Orphaned leaf is re-used for the next member
+    assert_eq!(branch.root, 2);
+    roster.commit(&mut tree, &branch, Some(member(104)), &memory).expect("Unable to commit insert through roster");
+
+    assert_eq!(roster.index_of(104), Some(2));
+    assert_eq!(roster.len(), 4);
+}
+
+#[wasm_bindgen_test]
+fn test_roster_snapshot_roundtrip() {
+    let root_allocator: Bump = AllocatorPool::create_bumpalo::<&Bump>(12);
+    let memory: AllocatorPool = AllocatorPool::new_with_init::<Key>(&root_allocator, 12, 32);
+    let roster_memory: AllocatorCell = AllocatorCell::new(&root_allocator, AllocatorPool::create_bumpalo::<Member>(8));
+
+    let mut tree: RatchetTree = RatchetTree::new(&memory).expect("Unable to create tree");
+    let mut roster: Roster = Roster::new(&roster_memory);
+    let scratch: AllocatorCell = crypto_art::tree::branch_memory(&memory).expect("Unable to get branch memory");
+
+    for id in 0..5 {
+        let key: Key = Secret::random(&mut OsRng).into();
+        let branch: RatchetBranch = tree.insert(&key, &scratch).expect("Error inserting key into tree");
+
+        roster.commit(&mut tree, &branch, Some(member(id)), &memory).expect("Unable to commit insert through roster");
+    }
+
+    let branch: RatchetBranch = tree.remove(3, &scratch).expect("Unable to compute remove for tree");
+    roster.commit(&mut tree, &branch, None, &memory).expect("Unable to commit remove through roster");
+
+    let mut snapshot: TreeSnapshot = tree.snapshot(false, &scratch);
+    roster.snapshot(&mut snapshot);
+
+    let encoded: Vec<u8> = snapshot.encode(&scratch).expect("Unable to encode snapshot");
+    let decoded: TreeSnapshot = TreeSnapshot::decode(&encoded, &scratch).expect("Unable to decode snapshot");
+
+    let restored_memory: AllocatorCell = AllocatorCell::new(&root_allocator, AllocatorPool::create_bumpalo::<Member>(8));
+    let restored_tree_memory: AllocatorPool = AllocatorPool::new_with_init::<Key>(&root_allocator, 12, 32);
+    let restored_tree: RatchetTree = RatchetTree::restore(&restored_tree_memory, &decoded).expect("Unable to restore tree");
+    let restored: Roster = Roster::restore(&decoded, &restored_tree, &restored_memory).expect("Unable to restore roster");
+
+    assert_eq!(restored.len(), 4);
+    assert!(restored.get(3).is_none());
+
+    for (index, member) in roster.iter() {
+        assert_eq!(restored.get(index), Some(member));
+    }
+}
+
+#[wasm_bindgen_test]
+fn test_roster_restore_invalid() {
+    let root_allocator: Bump = AllocatorPool::create_bumpalo::<&Bump>(12);
+    let memory: AllocatorPool = AllocatorPool::new_with_init::<Key>(&root_allocator, 12, 32);
+    let roster_memory: AllocatorCell = AllocatorCell::new(&root_allocator, AllocatorPool::create_bumpalo::<Member>(8));
+
+    let mut tree: RatchetTree = RatchetTree::new(&memory).expect("Unable to create tree");
+    let mut roster: Roster = Roster::new(&roster_memory);
+    let scratch: AllocatorCell = crypto_art::tree::branch_memory(&memory).expect("Unable to get branch memory");
+
+    for id in 0..4 {
+        let key: Key = Secret::random(&mut OsRng).into();
+        let branch: RatchetBranch = tree.insert(&key, &scratch).expect("Error inserting key into tree");
+
+        roster.commit(&mut tree, &branch, Some(member(id)), &memory).expect("Unable to commit insert through roster");
+    }
+
+    let branch: RatchetBranch = tree.remove(2, &scratch).expect("Unable to compute remove for tree");
+    roster.commit(&mut tree, &branch, None, &memory).expect("Unable to commit remove through roster");
+
+    // Tombstoned leaf, leaf past the end of the tree, a leaf bound twice & an index that would exhaust memory
+    for (index, id, cause) in [
+        (2, 10, RatchetErrorCause::INVALID_INDEX),
+        (5, 10, RatchetErrorCause::INVALID_INDEX),
+        (1, 10, RatchetErrorCause::INVALID_MEMBER),
+        (usize::MAX, 10, RatchetErrorCause::INVALID_INDEX)
+    ] {
+        let mut snapshot: TreeSnapshot = tree.snapshot(false, &scratch);
+        roster.snapshot(&mut snapshot);
+        snapshot.members.push((index, member(id)));
+
+        let restored_memory: AllocatorCell = AllocatorCell::new(&root_allocator, AllocatorPool::create_bumpalo::<Member>(8));
+        let error: RatchetError = Roster::restore(&snapshot, &tree, &restored_memory).err().expect("Restored invalid roster");
+
+        assert_eq!(error.cause, cause);
+        assert_eq!(error.index, index);
+    }
+}
+
+#[wasm_bindgen_test]
+fn test_roster_batch_rollback() {
+    let root_allocator: Bump = AllocatorPool::create_bumpalo::<&Bump>(12);
+    let memory: AllocatorPool = AllocatorPool::new_with_init::<Key>(&root_allocator, 12, 32);
+    let receiver_memory: AllocatorPool = AllocatorPool::new_with_init::<Key>(&root_allocator, 12, 32);
+    let roster_memory: AllocatorCell = AllocatorCell::new(&root_allocator, AllocatorPool::create_bumpalo::<Member>(8));
+    let receiver_roster_memory: AllocatorCell = AllocatorCell::new(&root_allocator, AllocatorPool::create_bumpalo::<Member>(8));
+
+    let mut tree: RatchetTree = RatchetTree::new(&memory).expect("Unable to create tree");
+    let mut roster: Roster = Roster::new(&roster_memory);
+    let scratch: AllocatorCell = crypto_art::tree::branch_memory(&memory).expect("Unable to get branch memory");
+
+    let mut leaf: Key = Key::default();
+
+    for id in 100..104 {
+        leaf = Secret::random(&mut OsRng).into();
+        let branch: RatchetBranch = tree.insert(&leaf, &scratch).expect("Error inserting key into tree");
+
+        roster.commit(&mut tree, &branch, Some(member(id)), &memory).expect("Unable to commit insert through roster");
+    }
+
+    // The receiver sits in leaf 4 and follows the group from a public snapshot
+    let mut snapshot: TreeSnapshot = tree.snapshot(false, &scratch);
+    roster.snapshot(&mut snapshot);
+
+    let mut receiver: RatchetTree = RatchetTree::restore(&receiver_memory, &snapshot).expect("Unable to restore tree");
+    let mut receiver_roster: Roster = Roster::restore(&snapshot, &receiver, &receiver_roster_memory).expect("Unable to restore roster");
+    receiver.set(0, 4, leaf).expect("Unable to set receiver leaf");
+
+    // Leaf 1 updates, leaf 2 is removed & a new member joins in leaf 5, all in one epoch
+    let mut branches: Vec<RatchetBranch> = Vec::new_in(&scratch);
+    branches.push(tree.ratchet(1, &Secret::random(&mut OsRng).into(), &scratch).expect("Unable to ratchet tree"));
+    branches.push(tree.remove(2, &scratch).expect("Unable to compute remove for tree"));
+    branches.push(tree.insert(&Secret::random(&mut OsRng).into(), &scratch).expect("Error inserting key into tree"));
+
+    let joining: Member = member(104);
+
+    let error: RatchetError = roster.commit_batch(&mut tree, &branches, &[None, None], &memory, &scratch).err().expect("Committed batch without every member");
+    assert_eq!(error.cause, RatchetErrorCause::INVALID_MEMBER);
+
+    let error: RatchetError = roster.commit_batch(&mut tree, &branches, &[None, None, None], &memory, &scratch).err().expect("Inserted without member");
+    assert_eq!(error.cause, RatchetErrorCause::INVALID_MEMBER);
+    assert_eq!(tree.epoch(), 4);
+
+    let members: [Option<Member>; 3] = [None, None, Some(joining)];
+    let update = roster.commit_batch(&mut tree, &branches, &members, &memory, &scratch).expect("Unable to commit batch through roster");
+
+    assert_eq!(roster.len(), 4);
+    assert!(roster.get(2).is_none());
+    assert_eq!(roster.index_of(104), Some(5));
+
+    // The merged update is in leaf order, same as the branches here
+    receiver_roster.apply_batch(&mut receiver, &update, &members, 4, &receiver_memory, &scratch).expect("Unable to apply batch through roster");
+
+    assert_eq!(receiver.tree_hash(), tree.tree_hash());
+    for index in 0..6 {
+        assert_eq!(receiver_roster.get(index), roster.get(index));
+    }
+
+    // Rolling back puts the removed member back & unbinds the one that joined
+    assert_eq!(roster.rollback(&mut tree, 1).expect("Unable to roll back batch"), 4);
+
+    assert_eq!(roster.len(), 4);
+    assert_eq!(roster.index_of(101), Some(2));
+    assert_eq!(roster.index_of(104), None);
+
+    roster.rollback(&mut tree, 2).expect("Unable to roll back inserts");
+
+    assert_eq!(roster.len(), 2);
+    assert!(roster.get(3).is_none());
+    assert_eq!(roster.index_of(101), Some(2));
+
+    // Nothing is undone when the tree can't roll back that far
+    tree.set_journal_depth(1);
+
+    let branch: RatchetBranch = tree.remove(1, &scratch).expect("Unable to compute remove for tree");
+    roster.commit(&mut tree, &branch, None, &memory).expect("Unable to commit remove through roster");
+
+    let error: RatchetError = roster.rollback(&mut tree, 2).err().expect("Rolled back past the journal");
+    assert_eq!(error.cause, RatchetErrorCause::JOURNAL_EXHAUSTED);
+    assert!(roster.get(1).is_none());
+
+    roster.rollback(&mut tree, 1).expect("Unable to roll back removal");
+    assert_eq!(roster.index_of(100), Some(1));
+}
+
+#[wasm_bindgen_test]
+fn test_roster_resolve_fork_promote() {
+    let root_allocator: Bump = AllocatorPool::create_bumpalo::<&Bump>(12);
+    let memory_one: AllocatorPool = AllocatorPool::new_with_init::<Key>(&root_allocator, 12, 32);
+    let memory_two: AllocatorPool = AllocatorPool::new_with_init::<Key>(&root_allocator, 12, 32);
+    let fork_memory: AllocatorPool = AllocatorPool::new_with_init::<Key>(&root_allocator, 12, 32);
+    let roster_memory_one: AllocatorCell = AllocatorCell::new(&root_allocator, AllocatorPool::create_bumpalo::<Member>(8));
+    let roster_memory_two: AllocatorCell = AllocatorCell::new(&root_allocator, AllocatorPool::create_bumpalo::<Member>(8));
+    let fork_roster_memory: AllocatorCell = AllocatorCell::new(&root_allocator, AllocatorPool::create_bumpalo::<Member>(8));
+
+    let mut tree_one: RatchetTree = RatchetTree::new(&memory_one).expect("Unable to create tree");
+    let mut roster_one: Roster = Roster::new(&roster_memory_one);
+    let scratch: AllocatorCell = crypto_art::tree::branch_memory(&memory_one).expect("Unable to get branch memory");
+
+    for id in 100..104 {
+        let branch: RatchetBranch = tree_one.insert(&Secret::random(&mut OsRng).into(), &scratch).expect("Error inserting key into tree");
+
+        roster_one.commit(&mut tree_one, &branch, Some(member(id)), &memory_one).expect("Unable to commit insert through roster");
+    }
+
+    let mut snapshot: TreeSnapshot = tree_one.snapshot(true, &scratch);
+    roster_one.snapshot(&mut snapshot);
+
+    let mut tree_two: RatchetTree = RatchetTree::restore(&memory_two, &snapshot).expect("Unable to restore tree");
+    let mut roster_two: Roster = Roster::restore(&snapshot, &tree_two, &roster_memory_two).expect("Unable to restore roster");
+
+    // Two members join into the same new leaf concurrently, each side commits its own first
+    let (member_one, member_two): (Member, Member) = (member(104), member(105));
+
+    let insert_one: RatchetBranch = tree_one.insert(&Secret::random(&mut OsRng).into(), &scratch).expect("Unable to insert");
+    let insert_two: RatchetBranch = tree_two.insert(&Secret::random(&mut OsRng).into(), &scratch).expect("Unable to insert");
+
+    roster_one.commit(&mut tree_one, &insert_one, Some(member_one), &memory_one).expect("Unable to commit insert through roster");
+    roster_two.commit(&mut tree_two, &insert_two, Some(member_two), &memory_two).expect("Unable to commit insert through roster");
+
+    // An incoming insert must still name its member, and nothing is resolved when it doesn't
+    let error: RatchetError = roster_one.resolve_fork(&mut tree_one, &insert_two, None, None, &memory_one, &scratch).err().expect("Resolved insert without member");
+    assert_eq!(error.cause, RatchetErrorCause::INVALID_MEMBER);
+    assert_eq!(roster_one.get(5), Some(&member_one));
+
+    let resolution_one: ForkResolution = roster_one.resolve_fork(&mut tree_one, &insert_two, Some(member_two), None, &memory_one, &scratch).expect("Unable to resolve fork");
+    let resolution_two: ForkResolution = roster_two.resolve_fork(&mut tree_two, &insert_one, Some(member_one), None, &memory_two, &scratch).expect("Unable to resolve fork");
+
+    // Both sides agree on the tree & on who sits in the contested leaf
+    assert_eq!(tree_one.tree_hash(), tree_two.tree_hash());
+    assert_eq!(roster_one.get(5), roster_two.get(5));
+    assert_eq!(roster_one.len(), 5);
+
+    let winner: &Member = match resolution_one {
+        ForkResolution::REPLACED { loser: 5 } => &member_two,
+        _ => {
+            assert_eq!(resolution_two, ForkResolution::REPLACED { loser: 5 });
+            &member_one
+        }
+    };
+
+    assert_eq!(roster_one.get(5), Some(winner));
+
+    // A fork takes in a new member and removes leaf 1, its roster follows it along
+    let stale: Roster = roster_one.fork(&fork_roster_memory);
+    let mut fork_roster: Roster = roster_one.fork(&fork_roster_memory);
+    let changes: TreeChanges = {
+        let mut fork: RatchetTree = tree_one.fork(&fork_memory).expect("Unable to fork tree");
+
+        let insert: RatchetBranch = fork.insert(&Secret::random(&mut OsRng).into(), &scratch).expect("Unable to insert");
+        fork_roster.commit(&mut fork, &insert, Some(member(106)), &fork_memory).expect("Unable to commit insert to fork");
+
+        let remove: RatchetBranch = fork.remove(1, &scratch).expect("Unable to compute remove for tree");
+        fork_roster.commit(&mut fork, &remove, None, &fork_memory).expect("Unable to commit remove to fork");
+
+        fork.changes(&scratch).expect("Unable to take fork changes")
+    };
+
+    assert_eq!(fork_roster.index_of(106), Some(6));
+    assert!(fork_roster.get(1).is_none());
+
+    // A roster that didn't follow the fork still binds the removed leaf, nothing is promoted with it
+    let epoch: u64 = tree_one.epoch();
+
+    let error: RatchetError = roster_one.promote(&mut tree_one, &changes, &stale, &memory_one).err().expect("Promoted stale roster");
+    assert_eq!(error.cause, RatchetErrorCause::INVALID_INDEX);
+    assert_eq!(error.index, 1);
+    assert_eq!(tree_one.epoch(), epoch);
+
+    roster_one.promote(&mut tree_one, &changes, &fork_roster, &memory_one).expect("Unable to promote fork through roster");
+
+    for index in 0..7 {
+        assert_eq!(roster_one.get(index), fork_roster.get(index));
+    }
+
+    // Undone as a whole, bindings included
+    roster_one.rollback(&mut tree_one, 1).expect("Unable to roll back promotion");
+
+    assert_eq!(tree_one.epoch(), epoch);
+    assert_eq!(roster_one.index_of(100), Some(1));
+    assert_eq!(roster_one.index_of(106), None);
+
+    for index in 0..7 {
+        assert_eq!(roster_one.get(index), stale.get(index));
+    }
+}
@@ -0,0 +1,107 @@
+#![cfg(test)]
+#[macro_use]
+extern crate crypto_art;
+
+use wasm_bindgen_test::*;
+
+use crypto_art::log::*;
+
+use crypto_art::{
+    ecdh::Key,
+    ecdh::Secret,
+    mem::AllocatorPool,
+    mem::AllocatorCell,
+    tree::RatchetBranch,
+    tree::RatchetTree,
+    schedule::KeySchedule,
+    schedule::STAGE_KEY_LEN
+};
+
+use elliptic_curve::zeroize::Zeroize;
+
+use bumpalo::Bump;
+
+use rand_core::OsRng;
+
+#[wasm_bindgen_test]
+fn test_schedule_advance() {
+    let root_allocator: Bump = AllocatorPool::create_bumpalo::<&Bump>(12);
+    let memory: AllocatorPool = AllocatorPool::new_with_init::<Key>(&root_allocator, 12, 32);
+    let mut tree: RatchetTree = RatchetTree::new(&memory).expect("Unable to create tree");
+
+    let mut schedule_one: KeySchedule = KeySchedule::new();
+    let mut schedule_two: KeySchedule = KeySchedule::new();
+
+    assert_eq!(schedule_one.epoch(), 0);
+
+    let mut previous: [u8; STAGE_KEY_LEN] = *schedule_one.stage_key();
+
+    for i in 1..5 {
+        let key: Key = Secret::random(&mut OsRng).into();
+        let scratch: AllocatorCell = crypto_art::tree::branch_memory(&memory).expect("Unable to get branch memory");
+        let branch: RatchetBranch = tree.insert(&key, &scratch).expect("Error inserting key into tree");
+
+        let root: Key = tree.commit(&branch, &memory).expect("Unable to commit branch to tree").clone();
+
+        assert_eq!(schedule_one.advance(&root).expect("Unable to advance schedule_one"), i);
+        assert_eq!(schedule_two.advance(&root).expect("Unable to advance schedule_two"), i);
+
+        // Same history, same stage key. Stage key is never the raw tree key
+        assert_eq!(schedule_one.stage_key(), schedule_two.stage_key());
+        assert_ne!(schedule_one.stage_key(), &previous);
+        assert_ne!(schedule_one.stage_key().as_slice(), root.sk.unwrap().to_repr().as_slice());
+
+        previous = *schedule_one.stage_key();
+    }
+
+    // Same tree key, different history: stage keys diverge
+    let root: Key = tree.get_root().expect("No root found in tree").clone();
+    let mut fresh: KeySchedule = KeySchedule::new();
+
+    fresh.advance(&root).expect("Unable to advance fresh schedule");
+    assert_ne!(fresh.stage_key(), schedule_one.stage_key());
+
+    // Public only tree keys cannot advance the schedule
+    let public: Key = root.pk.into();
+    assert!(schedule_one.advance(&public).is_err());
+    assert_eq!(schedule_one.epoch(), 4);
+}
+
+#[wasm_bindgen_test]
+fn test_schedule_derive() {
+    let mut schedule: KeySchedule = KeySchedule::new();
+    let root: Key = Secret::random(&mut OsRng).into();
+
+    let mut message_key: [u8; 32] = [0; 32];
+    let mut message_key_again: [u8; 32] = [0; 32];
+    let mut other_key: [u8; 32] = [0; 32];
+
+    // Nothing to derive from before the first tree key
+    assert!(schedule.derive(b"message", &mut message_key).is_err());
+    assert_eq!(message_key, [0; 32]);
+
+    schedule.advance(&root).expect("Unable to advance schedule");
+
+    schedule.derive(b"message", &mut message_key).expect("Unable to derive message key");
+    schedule.derive(b"message", &mut message_key_again).expect("Unable to derive message key");
+    schedule.derive(b"other", &mut other_key).expect("Unable to derive other key");
+
+    assert_eq!(message_key, message_key_again);
+    assert_ne!(message_key, other_key);
+    assert_ne!(&message_key, schedule.stage_key());
+
+    // Labels are bound to the epoch as well
+    schedule.advance(&root).expect("Unable to advance schedule");
+    schedule.derive(b"message", &mut message_key_again).expect("Unable to derive message key");
+
+    assert_ne!(message_key, message_key_again);
+
+    let long_label: [u8; 65] = [0; 65];
+    assert!(schedule.derive(&long_label, &mut message_key).is_err());
+
+    // Wiped, the schedule has nothing left to derive from
+    schedule.zeroize();
+
+    assert_eq!(schedule.stage_key(), &[0; STAGE_KEY_LEN]);
+    assert!(schedule.derive(b"message", &mut message_key).is_err());
+}
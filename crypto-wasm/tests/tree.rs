@@ -11,10 +11,22 @@ use crypto_art::{
     ecdh::Secret,
     mem::AllocatorPool,
     mem::AllocatorCell,
+    mem::AllocatorStats,
     tree::RatchetBranch,
     tree::RatchetTree,
     tree::RatchetError,
-    tree::RatchetErrorCause
+    tree::RatchetErrorCause,
+    tree::PathNode,
+    tree::ForkResolution,
+    tree::TreeChanges,
+    tree::OrphanPolicy,
+    tree::FifoPolicy,
+    tree::LeftmostPolicy,
+    tree::BalancedPolicy,
+    tree::GrowthPolicy,
+    tree::SLOT_ROOT_NODES,
+    schedule::KeySchedule,
+    wire::TreeSnapshot
 };
 
 use bumpalo::{
@@ -27,8 +39,8 @@ use rand_core::OsRng;
 #[wasm_bindgen_test]
 fn test_tree_create() {
     let root_allocator: Bump = AllocatorPool::create_bumpalo::<&Bump>(4);
-    let mut memory: AllocatorPool = AllocatorPool::new_with_init::<Key>(&root_allocator, 4, 32);
-    let tree: RatchetTree = RatchetTree::new(&mut memory);
+    let mut memory: AllocatorPool = AllocatorPool::new_with_init::<Key>(&root_allocator, 5, 32);
+    let tree: RatchetTree = RatchetTree::new(&mut memory).expect("Unable to create tree");
 
     assert_eq!(tree.get_next_index(), 1);
     assert_eq!(tree.height(), 0);
@@ -37,12 +49,12 @@ fn test_tree_create() {
 #[wasm_bindgen_test]
 fn test_tree_insert_single() {
     let root_allocator: Bump = AllocatorPool::create_bumpalo::<&Bump>(4);
-    let memory: AllocatorPool = AllocatorPool::new_with_init::<Key>(&root_allocator, 4, 32);
-    let tree: RatchetTree = RatchetTree::new(&memory);
+    let memory: AllocatorPool = AllocatorPool::new_with_init::<Key>(&root_allocator, 5, 32);
+    let tree: RatchetTree = RatchetTree::new(&memory).expect("Unable to create tree");
 
     let key: Key = Secret::random(&mut OsRng).into();
 
-    let scratch: AllocatorCell = memory.get(crypto_art::tree::MEMORY_BRANCH_INDEX);
+    let scratch: AllocatorCell = crypto_art::tree::branch_memory(&memory).expect("Unable to get branch memory");
     let res: RatchetBranch = tree.insert(&key, &scratch).expect("Error inserting key into tree");
     assert_eq!(res.len(), 1);
     assert_eq!(res.get_node(0), Some(&key));
@@ -59,12 +71,12 @@ fn test_tree_insert_single() {
 fn test_tree_insert_double() {
     let root_allocator: Bump = AllocatorPool::create_bumpalo::<&Bump>(4);
     let memory: AllocatorPool = AllocatorPool::new_with_init::<Key>(&root_allocator, 8, 32);
-    let mut tree: RatchetTree = RatchetTree::new(&memory);
+    let mut tree: RatchetTree = RatchetTree::new(&memory).expect("Unable to create tree");
 
     let key_one: Key = Secret::random(&mut OsRng).into();
     let key_two: Key = Secret::random(&mut OsRng).into();
     
-    let scratch: AllocatorCell = memory.get(crypto_art::tree::MEMORY_BRANCH_INDEX);
+    let scratch: AllocatorCell = crypto_art::tree::branch_memory(&memory).expect("Unable to get branch memory");
     let branch_one: RatchetBranch = tree.insert(&key_one, &scratch).expect("Error inserting key_one into tree");
 
     assert_eq!(branch_one.len(), 1);
@@ -101,13 +113,13 @@ fn test_tree_insert_multiple() {
     let test_allocator: Bump = AllocatorPool::create_bumpalo::<Key>(32);
 
     let tree_one_memory: AllocatorPool = AllocatorPool::new_with_init::<Key>(&root_allocator, 12, 32);
-    let mut tree_one: RatchetTree = RatchetTree::new(&tree_one_memory);
+    let mut tree_one: RatchetTree = RatchetTree::new(&tree_one_memory).expect("Unable to create tree");
 
     let mut keys: Vec<Key> = Vec::new_in(&test_allocator);
 
     for i in 1..33 {
         let key: Key = Secret::random(&mut OsRng).into();
-        let scratch: AllocatorCell = tree_one_memory.get(crypto_art::tree::MEMORY_BRANCH_INDEX);
+        let scratch: AllocatorCell = crypto_art::tree::branch_memory(&tree_one_memory).expect("Unable to get branch memory");
         let branch: RatchetBranch = tree_one.insert(&key, &scratch).expect("Error inserting key_one into tree_one");
 
         keys.push(key);
@@ -122,10 +134,10 @@ fn test_tree_insert_multiple() {
     assert_eq!(tree_one.height(), 5);
 
     let tree_two_memory: AllocatorPool = AllocatorPool::new_with_init::<Key>(&root_allocator, 12, 32);
-    let mut tree_two: RatchetTree = RatchetTree::new(&tree_two_memory);
+    let mut tree_two: RatchetTree = RatchetTree::new(&tree_two_memory).expect("Unable to create tree");
 
     for key in keys {
-        let scratch: AllocatorCell = tree_two_memory.get(crypto_art::tree::MEMORY_BRANCH_INDEX);
+        let scratch: AllocatorCell = crypto_art::tree::branch_memory(&tree_two_memory).expect("Unable to get branch memory");
         let branch: RatchetBranch = tree_two.insert(&key, &scratch).expect("Error inserting key_one into tree_two");
 
         tree_two.commit(&branch, &tree_two_memory).expect("Unable to commit branch_two to tree");
@@ -146,13 +158,13 @@ fn test_tree_delete_single() {
     let test_allocator: Bump = AllocatorPool::create_bumpalo::<Key>(32);
 
     let tree_memory: AllocatorPool = AllocatorPool::new_with_init::<Key>(&root_allocator, 12, 32);
-    let mut tree: RatchetTree = RatchetTree::new(&tree_memory);
+    let mut tree: RatchetTree = RatchetTree::new(&tree_memory).expect("Unable to create tree");
 
     let mut keys: Vec<Key> = Vec::new_in(&test_allocator);
 
     for _ in 0..16 {
         let key: Key = Secret::random(&mut OsRng).into();
-        let scratch: AllocatorCell = tree_memory.get(crypto_art::tree::MEMORY_BRANCH_INDEX);
+        let scratch: AllocatorCell = crypto_art::tree::branch_memory(&tree_memory).expect("Unable to get branch memory");
         let branch: RatchetBranch = tree.insert(&key, &scratch).expect("Error inserting key_one into tree_one");
 
         keys.push(key);
@@ -160,11 +172,14 @@ fn test_tree_delete_single() {
         tree.commit(&branch, &tree_memory).expect("Unable to commit add_branch to tree");
     }
 
-    let scratch: AllocatorCell = tree_memory.get(crypto_art::tree::MEMORY_BRANCH_INDEX);
+    let scratch: AllocatorCell = crypto_art::tree::branch_memory(&tree_memory).expect("Unable to get branch memory");
     let remove_branch: RatchetBranch = RatchetTree::remove(&tree, 16, &scratch).expect("Unable to compute remove for tree");
 
     assert!(tree.commit(&remove_branch, &tree_memory).is_ok());
-    assert_eq!(tree.get(0, 16), tree.tombstone.as_ref());
+
+    // Removing the last leaf shrinks it away with the commit
+    assert_eq!(tree.get(0, 16), None);
+    assert_eq!(tree.get_layer_len(0), 16);
     assert_eq!(tree.get_next_index(), 16);
 
     // Tree index starts at 1, keys (Vec) starts at 0
@@ -177,7 +192,7 @@ fn test_tree_delete_insert_complex() {
     let test_allocator: Bump = AllocatorPool::create_bumpalo::<Key>(8);
 
     let tree_memory: AllocatorPool = AllocatorPool::new_with_init::<Key>(&root_allocator, 12, 32);
-    let mut tree: RatchetTree = RatchetTree::new(&tree_memory);
+    let mut tree: RatchetTree = RatchetTree::new(&tree_memory).expect("Unable to create tree");
 
     let mut keys: Vec<Key> = Vec::new_in(&test_allocator);
 
@@ -205,7 +220,7 @@ fn test_tree_delete_insert_complex() {
     let abcdefg: Key = abcd.diffie_hellman(&efg).expect("ABCDEFG Diffie-Hellman failed");
 
     for key in keys.clone() {
-        let scratch: AllocatorCell = tree_memory.get(crypto_art::tree::MEMORY_BRANCH_INDEX);
+        let scratch: AllocatorCell = crypto_art::tree::branch_memory(&tree_memory).expect("Unable to get branch memory");
         let branch: RatchetBranch = tree.insert(&key, &scratch).expect("Error inserting key into tree");
 
         tree.commit(&branch, &tree_memory).expect("Unable to commit branch to tree");
@@ -227,13 +242,14 @@ fn test_tree_delete_insert_complex() {
     let abcefg = abc.diffie_hellman(&efg).expect("ABCXEFG Diffie-Hellman failed");
 
     // Remove D from tree
-    let scratch: AllocatorCell = tree_memory.get(crypto_art::tree::MEMORY_BRANCH_INDEX);
+    let scratch: AllocatorCell = crypto_art::tree::branch_memory(&tree_memory).expect("Unable to get branch memory");
     let remove_branch_d: RatchetBranch = RatchetTree::remove(&tree, 4, &scratch).expect("Unable to compute remove for tree");
 
     tree.commit(&remove_branch_d, &tree_memory).expect("Unable to commit remove_branch_d to tree");
 
     assert_eq!(tree.get(0, 4), tree.tombstone.as_ref());
     assert_eq!(&abcefg, tree.get(tree.height(), 1).expect("Could not get final result from tree"));
+    assert!(tree.verify(&scratch).is_empty());
 
     /*
      *        (AB, EFG)
@@ -250,6 +266,7 @@ fn test_tree_delete_insert_complex() {
 
     let abefg = ab.diffie_hellman(&efg).expect("ABXXEFG Diffie-Hellman failed");
     assert_eq!(&abefg, tree.get(tree.height(), 1).expect("Could not get final result from tree"));
+    assert!(tree.verify(&scratch).is_empty());
 
     /*
      *        (B, EFG)
@@ -266,6 +283,7 @@ fn test_tree_delete_insert_complex() {
 
     let befg = keys[1].diffie_hellman(&efg).expect("BEFG Diffie-Hellman failed");
     assert_eq!(&befg, tree.get(tree.height(), 1).expect("Could not get final result from tree"));
+    assert!(tree.verify(&scratch).is_empty());
 
     /*
      *          (EFG)
@@ -281,6 +299,7 @@ fn test_tree_delete_insert_complex() {
     tree.commit(&remove_branch_b, &tree_memory).expect("Unable to commit remove_branch_b to tree");
 
     assert_eq!(&efg, tree.get(tree.height(), 1).expect("Could not get final result from tree"));
+    assert!(tree.verify(&scratch).is_empty());
 
     /*
      *        (B, EFG)
@@ -296,6 +315,7 @@ fn test_tree_delete_insert_complex() {
     tree.commit(&add_branch_b, &tree_memory).expect("Unable to commit add_branch_b to tree");
 
     assert_eq!(&befg, tree.get(tree.height(), 1).expect("Could not get final result from tree"));
+    assert!(tree.verify(&scratch).is_empty());
 
     /*
      *        (AB, EFG)
@@ -311,6 +331,7 @@ fn test_tree_delete_insert_complex() {
     tree.commit(&add_branch_a, &tree_memory).expect("Unable to commit add_branch_b to tree");
 
     assert_eq!(&abefg, tree.get(tree.height(), 1).expect("Could not get final result from tree"));
+    assert!(tree.verify(&scratch).is_empty());
 
     /*
      *       (ABC, EFG)
@@ -326,6 +347,7 @@ fn test_tree_delete_insert_complex() {
     tree.commit(&add_branch_c, &tree_memory).expect("Unable to commit add_branch_c to tree");
 
     assert_eq!(&abcefg, tree.get(tree.height(), 1).expect("Could not get final result from tree"));
+    assert!(tree.verify(&scratch).is_empty());
 
     /*
      *       (ABCD, EFG)
@@ -341,6 +363,7 @@ fn test_tree_delete_insert_complex() {
     tree.commit(&add_branch_d, &tree_memory).expect("Unable to commit add_branch_c to tree");
 
     assert_eq!(&abcdefg, tree.get(tree.height(), 1).expect("Could not get final result from tree"));
+    assert!(tree.verify(&scratch).is_empty());
 
     /*
      *        (ABCD, EFGH)
@@ -361,27 +384,1132 @@ fn test_tree_delete_insert_complex() {
     tree.commit(&add_branch_h, &tree_memory).expect("Unable to commit add_branch_h to tree");
 
     assert_eq!(&abcdefgh, tree.get(tree.height(), 1).expect("Could not get final result from tree"));
+    assert!(tree.verify(&scratch).is_empty());
 }
 
 #[wasm_bindgen_test]
 fn test_tree_commit_oom_workflow() {
     let root_allocator: Bump = AllocatorPool::create_bumpalo::<&Bump>(4);
 
-    let mut memory: AllocatorPool = AllocatorPool::new_with_init::<Key>(&root_allocator, 6, 16);
-    let mut tree: RatchetTree = RatchetTree::new(&memory);
+    let memory: AllocatorPool = AllocatorPool::new_with_init::<Key>(&root_allocator, 7, 16);
+    let mut tree: RatchetTree = RatchetTree::new(&memory).expect("Unable to create tree");
 
     let key: Key = Secret::random(&mut OsRng).into();
 
     for _ in 0..4 {
-        let scratch: AllocatorCell = memory.get(crypto_art::tree::MEMORY_BRANCH_INDEX);
+        let scratch: AllocatorCell = crypto_art::tree::branch_memory(&memory).expect("Unable to get branch memory");
         let branch: RatchetBranch = tree.insert(&key, &scratch).expect("Error inserting key_one into tree");
 
         tree.commit(&branch, &memory).expect("Unable to commit branch to tree");
     }
 
-    let scratch: AllocatorCell = memory.get(crypto_art::tree::MEMORY_BRANCH_INDEX);
+    // Capped at what the tree has allocated so far, the next layer is out of memory
+    let allocated: usize = tree.allocated_bytes(&memory);
+    tree.set_growth_policy(GrowthPolicy { max_bytes: allocated, ..GrowthPolicy::default() });
+
+    let scratch: AllocatorCell = crypto_art::tree::branch_memory(&memory).expect("Unable to get branch memory");
     let branch: RatchetBranch = tree.insert(&key, &scratch).expect("Error inserting key_one into tree");
 
     let error: RatchetError = tree.commit(&branch, &memory).expect_err("No OOM Error found, problemo");
     assert_eq!(error.cause, RatchetErrorCause::OOM.into());
-}
\ No newline at end of file
+    assert_eq!(tree.allocated_bytes(&memory), allocated);
+    assert_eq!(tree.epoch(), 4);
+}
+
+#[wasm_bindgen_test]
+fn test_tree_commit_provisions_layers() {
+    let root_allocator: Bump = AllocatorPool::create_bumpalo::<&Bump>(12);
+
+    // Allocators for the first layer only, the pool grows for the rest
+    let memory: AllocatorPool = AllocatorPool::new_with_init::<Key>(&root_allocator, 5, 32);
+    let mut tree: RatchetTree = RatchetTree::new(&memory).expect("Unable to create tree");
+
+    for _ in 0..8 {
+        let scratch: AllocatorCell = tree.branch_memory().expect("Unable to get branch memory");
+        let branch: RatchetBranch = tree.insert(&Secret::random(&mut OsRng).into(), &scratch).expect("Error inserting key into tree");
+
+        tree.commit(&branch, &memory).expect("Unable to commit branch to tree");
+    }
+
+    assert_eq!(tree.height(), 3);
+    assert_eq!(memory.len(), 8);
+
+    // Capped at what's allocated already, the next layer is out of memory
+    let allocated: usize = tree.allocated_bytes(&memory);
+    tree.set_growth_policy(GrowthPolicy { max_bytes: allocated, ..GrowthPolicy::default() });
+
+    let scratch: AllocatorCell = tree.branch_memory().expect("Unable to get branch memory");
+    let branch: RatchetBranch = tree.insert(&Secret::random(&mut OsRng).into(), &scratch).expect("Error inserting key into tree");
+
+    let error: RatchetError = tree.commit(&branch, &memory).expect_err("Provisioned past the memory cap");
+    assert_eq!(error.cause, RatchetErrorCause::OOM);
+    assert_eq!(tree.allocated_bytes(&memory), allocated);
+    assert_eq!(tree.epoch(), 8);
+
+    // Room for the next layer, with headroom provisioned ahead of it
+    tree.set_growth_policy(GrowthPolicy { max_bytes: 2 * allocated, headroom: 2, ..GrowthPolicy::default() });
+    tree.commit(&branch, &memory).expect("Unable to commit branch to tree");
+
+    assert_eq!(tree.height(), 4);
+    assert_eq!(memory.len(), 11);
+    assert!(tree.allocated_bytes(&memory) > allocated);
+    assert!(tree.allocated_bytes(&memory) <= 2 * allocated);
+
+    // Layers provisioned as headroom are the tree's too, so is every allocator in the pool
+    let stats: AllocatorStats = tree.memory_stats(&memory);
+
+    assert!(stats.allocated_bytes > 0);
+    assert_eq!(stats, memory.total_stats());
+    assert!(tree.verify(&scratch).is_empty());
+}
+
+#[wasm_bindgen_test]
+fn test_tree_setup() {
+    let root_allocator: Bump = AllocatorPool::create_bumpalo::<&Bump>(12);
+    let test_allocator: Bump = AllocatorPool::create_bumpalo::<Key>(8);
+
+    let initiator_memory: AllocatorPool = AllocatorPool::new_with_init::<Key>(&root_allocator, 12, 32);
+    let scratch: AllocatorCell = crypto_art::tree::branch_memory(&initiator_memory).expect("Unable to get branch memory");
+
+    let initiator: Key = Secret::random(&mut OsRng).into();
+    let setup_key: Key = Secret::random(&mut OsRng).into();
+
+    // Members publish the public half of their prekeys ahead of time
+    let mut prekeys: Vec<Key> = Vec::new_in(&test_allocator);
+    let mut published: Vec<Key> = Vec::new_in(&test_allocator);
+
+    for _ in 0..6 {
+        let prekey: Key = Secret::random(&mut OsRng).into();
+
+        published.push(prekey.pk.into());
+        prekeys.push(prekey);
+    }
+
+    let (tree, setup) = RatchetTree::setup(&initiator_memory, &initiator, &setup_key, &published, &scratch)
+        .expect("Unable to setup group");
+
+    assert_eq!(tree.get(0, 1), Some(&initiator));
+    assert_eq!(tree.get_next_index(), 8);
+    assert_eq!(tree.height(), 3);
+    assert!(setup.setup_key.sk.is_none());
+    assert!(setup.nodes.iter().all(|layer| layer.iter().all(|key| key.sk.is_none())));
+
+    let root: &Key = tree.get(tree.height(), 1).expect("Could not get root from initiator tree");
+    assert!(root.sk.is_some());
+
+    for (i, prekey) in prekeys.iter().enumerate() {
+        let member_memory: AllocatorPool = AllocatorPool::new_with_init::<Key>(&root_allocator, 12, 32);
+        let member_scratch: AllocatorCell = crypto_art::tree::branch_memory(&member_memory).expect("Unable to get branch memory");
+
+        let member_tree: RatchetTree = RatchetTree::from_setup(&member_memory, &setup, prekey, &member_scratch)
+            .expect("Unable to rebuild group from setup");
+
+        let leaf: &Key = member_tree.get(0, i + 2).expect("Could not get member leaf");
+        let member_root: &Key = member_tree.get(member_tree.height(), 1).expect("Could not get root from member tree");
+
+        assert!(leaf.sk.is_some());
+        assert!(member_root.sk.is_some());
+        assert_eq!(member_root, root);
+    }
+
+    // A prekey that was never part of the setup cannot rebuild the group
+    let stranger: Key = Secret::random(&mut OsRng).into();
+    let stranger_memory: AllocatorPool = AllocatorPool::new_with_init::<Key>(&root_allocator, 12, 32);
+    let stranger_scratch: AllocatorCell = crypto_art::tree::branch_memory(&stranger_memory).expect("Unable to get branch memory");
+
+    let error: RatchetError = RatchetTree::from_setup(&stranger_memory, &setup, &stranger, &stranger_scratch)
+        .err().expect("Stranger rebuilt group from setup");
+    assert_eq!(error.cause, RatchetErrorCause::INVALID_INDEX);
+}
+
+#[wasm_bindgen_test]
+fn test_tree_apply_update() {
+    let root_allocator: Bump = AllocatorPool::create_bumpalo::<&Bump>(12);
+    let test_allocator: Bump = AllocatorPool::create_bumpalo::<Key>(8);
+
+    let initiator_memory: AllocatorPool = AllocatorPool::new_with_init::<Key>(&root_allocator, 12, 32);
+    let member_one_memory: AllocatorPool = AllocatorPool::new_with_init::<Key>(&root_allocator, 12, 32);
+    let member_two_memory: AllocatorPool = AllocatorPool::new_with_init::<Key>(&root_allocator, 12, 32);
+    let scratch: AllocatorCell = crypto_art::tree::branch_memory(&initiator_memory).expect("Unable to get branch memory");
+
+    let initiator: Key = Secret::random(&mut OsRng).into();
+    let setup_key: Key = Secret::random(&mut OsRng).into();
+
+    let mut prekeys: Vec<Key> = Vec::new_in(&test_allocator);
+    for _ in 0..3 {
+        prekeys.push(Secret::random(&mut OsRng).into());
+    }
+
+    let (mut tree, setup) = RatchetTree::setup(&initiator_memory, &initiator, &setup_key, &prekeys, &scratch)
+        .expect("Unable to setup group");
+
+    let mut member_one: RatchetTree = RatchetTree::from_setup(&member_one_memory, &setup, &prekeys[0], &scratch)
+        .expect("Unable to rebuild group for member_one");
+    let mut member_two: RatchetTree = RatchetTree::from_setup(&member_two_memory, &setup, &prekeys[2], &scratch)
+        .expect("Unable to rebuild group for member_two");
+
+    // member_one (leaf 2) rotates their leaf, everyone else applies the public branch
+    let rotated: Key = Secret::random(&mut OsRng).into();
+    let update: RatchetBranch = member_one.ratchet(2, &rotated, &scratch).expect("Unable to ratchet member_one");
+    let expected: Key = update.get_last().expect("Empty update branch").clone();
+
+    member_one.commit(&update, &member_one_memory).expect("Unable to commit update for member_one");
+
+    let root: &Key = tree.apply_update(&update, 1, &initiator_memory, &scratch).expect("Initiator unable to apply update");
+    assert_eq!(root, &expected);
+    assert!(root.sk.is_some());
+
+    let root: &Key = member_two.apply_update(&update, 4, &member_two_memory, &scratch).expect("member_two unable to apply update");
+    assert_eq!(root, &expected);
+    assert!(root.sk.is_some());
+
+    // Only public keys are taken from the remote branch
+    assert!(member_two.get(0, 2).expect("No leaf at index 2").sk.is_none());
+    assert!(member_two.verify(&scratch).is_empty());
+    assert!(tree.verify(&scratch).is_empty());
+
+    // A joining member's insert grows the tree, existing members re-derive the new root
+    let joining: Key = Secret::random(&mut OsRng).into();
+    let insert: RatchetBranch = member_two.insert(&joining, &scratch).expect("Unable to compute insert");
+    let expected: Key = insert.get_last().expect("Empty insert branch").clone();
+
+    assert_eq!(insert.root, 5);
+
+    let root: &Key = member_one.apply_update(&insert, 2, &member_one_memory, &scratch).expect("member_one unable to apply insert");
+    assert_eq!(root, &expected);
+    assert!(root.sk.is_some());
+    assert_eq!(member_one.height(), 3);
+
+    // Tampered branches are rejected before anything is written
+    let mut tampered: RatchetBranch = tree.ratchet(3, &Secret::random(&mut OsRng).into(), &scratch).expect("Unable to ratchet");
+    let last: usize = tampered.len() - 1;
+    tampered.nodes[last] = Secret::random(&mut OsRng).into();
+
+    let error: RatchetError = member_two.apply_update(&tampered, 4, &member_two_memory, &scratch).expect_err("Tampered update applied");
+    assert_eq!(error.cause, RatchetErrorCause::INVALID_BRANCH);
+
+    let error: RatchetError = member_two.apply_update(&update, 2, &member_two_memory, &scratch).expect_err("Applied update to own leaf");
+    assert_eq!(error.cause, RatchetErrorCause::INVALID_INDEX);
+}
+
+#[wasm_bindgen_test]
+fn test_tree_hash() {
+    let root_allocator: Bump = AllocatorPool::create_bumpalo::<&Bump>(12);
+    let test_allocator: Bump = AllocatorPool::create_bumpalo::<Key>(8);
+
+    let initiator_memory: AllocatorPool = AllocatorPool::new_with_init::<Key>(&root_allocator, 12, 32);
+    let member_memory: AllocatorPool = AllocatorPool::new_with_init::<Key>(&root_allocator, 12, 32);
+    let scratch: AllocatorCell = crypto_art::tree::branch_memory(&initiator_memory).expect("Unable to get branch memory");
+
+    let initiator: Key = Secret::random(&mut OsRng).into();
+    let setup_key: Key = Secret::random(&mut OsRng).into();
+
+    let mut prekeys: Vec<Key> = Vec::new_in(&test_allocator);
+    for _ in 0..4 {
+        prekeys.push(Secret::random(&mut OsRng).into());
+    }
+
+    let (mut tree, setup) = RatchetTree::setup(&initiator_memory, &initiator, &setup_key, &prekeys, &scratch)
+        .expect("Unable to setup group");
+    let mut member: RatchetTree = RatchetTree::from_setup(&member_memory, &setup, &prekeys[1], &scratch)
+        .expect("Unable to rebuild group for member");
+
+    // Secrets never contribute, so both views agree
+    assert_eq!(tree.tree_hash(), member.tree_hash());
+
+    let rotated: Key = Secret::random(&mut OsRng).into();
+    let update: RatchetBranch = member.ratchet(3, &rotated, &scratch).expect("Unable to ratchet member");
+    let before: [u8; crypto_art::tree::TREE_HASH_LEN] = tree.tree_hash();
+
+    member.commit(&update, &member_memory).expect("Unable to commit update for member");
+    assert_ne!(member.tree_hash(), before);
+
+    tree.apply_update(&update, 1, &initiator_memory, &scratch).expect("Unable to apply update");
+    assert_eq!(tree.tree_hash(), member.tree_hash());
+
+    // Removals leave a tombstone, which along with the orphan list is committed to
+    let remove: RatchetBranch = tree.remove(4, &scratch).expect("Unable to compute remove");
+    let before: [u8; crypto_art::tree::TREE_HASH_LEN] = tree.tree_hash();
+
+    tree.commit(&remove, &initiator_memory).expect("Unable to commit remove");
+    assert_ne!(tree.tree_hash(), before);
+
+    member.commit(&remove, &member_memory).expect("Unable to commit remove for member");
+    assert_eq!(tree.tree_hash(), member.tree_hash());
+}
+
+#[wasm_bindgen_test]
+fn test_tree_verify() {
+    let root_allocator: Bump = AllocatorPool::create_bumpalo::<&Bump>(12);
+    let memory: AllocatorPool = AllocatorPool::new_with_init::<Key>(&root_allocator, 12, 32);
+    let mut tree: RatchetTree = RatchetTree::new(&memory).expect("Unable to create tree");
+    let scratch: AllocatorCell = crypto_art::tree::branch_memory(&memory).expect("Unable to get branch memory");
+
+    for _ in 0..5 {
+        let key: Key = Secret::random(&mut OsRng).into();
+        let branch: RatchetBranch = tree.insert(&key, &scratch).expect("Error inserting key into tree");
+
+        tree.commit(&branch, &memory).expect("Unable to commit branch to tree");
+        assert!(tree.verify(&scratch).is_empty());
+    }
+
+    // Corrupting an internal node breaks both it and its parent
+    let original: Key = tree.get(1, 1).expect("No node at height 1, index 1").clone();
+    let corrupted: Key = Key::new(Secret::random(&mut OsRng).public_key(), None);
+
+    tree.set(1, 1, corrupted).expect("Unable to set node");
+
+    let errors = tree.verify(&scratch);
+    assert_eq!(errors.len(), 2);
+    assert_eq!((errors[0].height, errors[0].index), (1, 1));
+    assert_eq!((errors[1].height, errors[1].index), (2, 1));
+    assert!(errors.iter().all(|e| e.cause == RatchetErrorCause::INCONSISTENT_NODE));
+
+    tree.set(1, 1, original).expect("Unable to set node");
+    assert!(tree.verify(&scratch).is_empty());
+
+    // Tombstoning a lone leaf without ratcheting leaves its pass-through parent behind
+    tree.set(0, 5, Key::default()).expect("Unable to set node");
+
+    let errors = tree.verify(&scratch);
+    assert_eq!(errors.len(), 1);
+    assert_eq!((errors[0].height, errors[0].index), (1, 3));
+}
+
+// Where the secret held by `key` lives
+fn secret_location(key: &Key) -> *const u8 {
+    return key.sk.as_ref().expect("Key holds no secret") as *const Secret as *const u8;
+}
+
+fn read_secret(secret: *const u8) -> [u8; core::mem::size_of::<Secret>()] {
+    let mut bytes: [u8; core::mem::size_of::<Secret>()] = [0; core::mem::size_of::<Secret>()];
+
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        *byte = unsafe { core::ptr::read_volatile(secret.add(i)) };
+    }
+
+    return bytes;
+}
+
+#[wasm_bindgen_test]
+fn test_tree_overwrite_wipes_secrets() {
+    let root_allocator: Bump = AllocatorPool::create_bumpalo::<&Bump>(12);
+    let memory: AllocatorPool = AllocatorPool::new_with_init::<Key>(&root_allocator, 12, 32);
+    let mut tree: RatchetTree = RatchetTree::new(&memory).expect("Unable to create tree");
+    let scratch: AllocatorCell = crypto_art::tree::branch_memory(&memory).expect("Unable to get branch memory");
+
+    for _ in 0..2 {
+        let key: Key = Secret::random(&mut OsRng).into();
+        let branch: RatchetBranch = tree.insert(&key, &scratch).expect("Error inserting key into tree");
+
+        tree.commit(&branch, &memory).expect("Unable to commit branch to tree");
+    }
+
+    // Overwriting a leaf with its public half leaves none of its secret behind in the layer
+    let leaf: Key = Key::from(tree.get(0, 1).expect("No leaf at index 1").pk);
+    let secret: *const u8 = secret_location(tree.get(0, 1).expect("No leaf at index 1"));
+    let bytes: [u8; core::mem::size_of::<Secret>()] = read_secret(secret);
+
+    tree.set(0, 1, leaf).expect("Unable to set node");
+
+    assert!(tree.get(0, 1).expect("No leaf at index 1").sk.is_none());
+    assert_ne!(read_secret(secret), bytes);
+
+    // Same for every node a commit replaces, here a public update to leaf 2 replacing the root we held
+    let update: RatchetBranch = tree.ratchet(2, &Secret::random(&mut OsRng).into(), &scratch).expect("Unable to ratchet leaf 2");
+    let mut public: RatchetBranch = RatchetBranch::new(&scratch, update.root);
+
+    for key in update.iter() {
+        public.add_node(Key::from(key.pk));
+    }
+
+    public.epoch = update.epoch;
+
+    let secret: *const u8 = secret_location(tree.get(1, 1).expect("No root"));
+    let bytes: [u8; core::mem::size_of::<Secret>()] = read_secret(secret);
+
+    tree.commit(&public, &memory).expect("Unable to commit public update");
+
+    assert!(tree.get(1, 1).expect("No root").sk.is_none());
+    assert_ne!(read_secret(secret), bytes);
+
+    // Nodes a commit appended are wiped where they lay once rolling it back truncates them away
+    let branch: RatchetBranch = tree.insert(&Secret::random(&mut OsRng).into(), &scratch).expect("Error inserting key into tree");
+    tree.commit(&branch, &memory).expect("Unable to commit branch to tree");
+
+    let secret: *const u8 = secret_location(tree.get(0, 3).expect("No leaf at index 3"));
+    let bytes: [u8; core::mem::size_of::<Secret>()] = read_secret(secret);
+
+    tree.rollback(1).expect("Unable to roll back insert");
+
+    assert_eq!(tree.get(0, 3), None);
+    assert_ne!(read_secret(secret), bytes);
+}
+
+#[wasm_bindgen_test]
+fn test_tree_drop_releases_memory() {
+    let root_allocator: Bump = AllocatorPool::create_bumpalo::<&Bump>(12);
+    let memory: AllocatorPool = AllocatorPool::new_with_init::<Key>(&root_allocator, 12, 32);
+    let empty: AllocatorStats = memory.total_stats();
+
+    {
+        let mut tree: RatchetTree = RatchetTree::new(&memory).expect("Unable to create tree");
+        let scratch: AllocatorCell = tree.branch_memory().expect("Unable to get branch memory");
+
+        for _ in 0..5 {
+            let branch: RatchetBranch = tree.insert(&Secret::random(&mut OsRng).into(), &scratch).expect("Error inserting key into tree");
+            tree.commit(&branch, &memory).expect("Unable to commit branch to tree");
+        }
+
+        assert!(memory.total_stats().allocated_bytes > empty.allocated_bytes);
+        assert!(memory.has_slot(&tree.slot_name(SLOT_ROOT_NODES)));
+    }
+
+    // Every allocator the tree claimed is reset & handed back, none of them still marked secret
+    assert!(!memory.has_slot("tree.0.nodes"));
+    assert_eq!(memory.unclaimed(), memory.len());
+    assert_eq!(memory.total_stats().allocated_bytes, empty.allocated_bytes);
+
+    for index in 0..memory.len() {
+        assert!(!memory.get_ref(index).expect("No allocator at index").is_secret());
+    }
+
+    // The next tree starts from the same clean pool
+    let tree: RatchetTree = RatchetTree::new(&memory).expect("Unable to create tree");
+    assert_eq!(tree.slot_name(SLOT_ROOT_NODES), "tree.0.nodes");
+}
+
+#[wasm_bindgen_test]
+fn test_tree_direct_path_copath() {
+    let root_allocator: Bump = AllocatorPool::create_bumpalo::<&Bump>(12);
+    let memory: AllocatorPool = AllocatorPool::new_with_init::<Key>(&root_allocator, 12, 32);
+    let mut tree: RatchetTree = RatchetTree::new(&memory).expect("Unable to create tree");
+    let scratch: AllocatorCell = crypto_art::tree::branch_memory(&memory).expect("Unable to get branch memory");
+
+    for _ in 0..5 {
+        let key: Key = Secret::random(&mut OsRng).into();
+        let branch: RatchetBranch = tree.insert(&key, &scratch).expect("Error inserting key into tree");
+
+        tree.commit(&branch, &memory).expect("Unable to commit branch to tree");
+    }
+
+    /*
+     *          (1)
+     *        /     \
+     *      (1)     (2)
+     *     /   \    /
+     *   (1)  (2) (3)
+     *   / \  / \  /
+     *   1 2  3 4  5
+    */
+
+    let direct_path = tree.direct_path(3, &scratch).expect("Unable to compute direct path");
+    let positions: Vec<(usize, usize)> = Vec::from_iter_in(direct_path.iter().map(|node| (node.height, node.index)), &scratch);
+    assert_eq!(positions.as_slice(), &[(0, 3), (1, 2), (2, 1), (3, 1)]);
+
+    let copath = tree.copath(3, &scratch).expect("Unable to compute copath");
+    let positions: Vec<(usize, usize)> = Vec::from_iter_in(copath.iter().map(|node| (node.height, node.index)), &scratch);
+    assert_eq!(positions.as_slice(), &[(0, 4), (1, 1), (2, 2)]);
+
+    // Stored public keys match the tree, and the branch the leaf would ratchet
+    for node in direct_path.iter().chain(copath.iter()) {
+        assert_eq!(node.pk, tree.get(node.height, node.index).map(|key| key.pk));
+    }
+
+    let branch: RatchetBranch = tree.ratchet(3, tree.get(0, 3).expect("No leaf at index 3"), &scratch).expect("Unable to ratchet");
+    for (node, key) in direct_path.iter().zip(branch.iter()) {
+        assert_eq!(node.pk, Some(key.pk));
+    }
+
+    // Leaf 5 has no sibling, the empty slot still appears in the copath
+    let copath = tree.copath(5, &scratch).expect("Unable to compute copath");
+    assert_eq!(copath[0], PathNode { height: 0, index: 6, pk: None });
+
+    let error: RatchetError = tree.copath(6, &scratch).err().expect("Computed copath for missing leaf");
+    assert_eq!(error.cause, RatchetErrorCause::INVALID_INDEX);
+    assert!(tree.direct_path(0, &scratch).is_err());
+}
+
+#[wasm_bindgen_test]
+fn test_tree_from_leaves() {
+    let root_allocator: Bump = AllocatorPool::create_bumpalo::<&Bump>(12);
+    let test_allocator: Bump = AllocatorPool::create_bumpalo::<Key>(8);
+
+    let sequential_memory: AllocatorPool = AllocatorPool::new_with_init::<Key>(&root_allocator, 12, 32);
+    let bulk_memory: AllocatorPool = AllocatorPool::new_with_init::<Key>(&root_allocator, 12, 32);
+    let scratch: AllocatorCell = crypto_art::tree::branch_memory(&sequential_memory).expect("Unable to get branch memory");
+
+    let mut keys: Vec<Key> = Vec::new_in(&test_allocator);
+
+    for count in [1, 2, 3, 5, 8, 11] {
+        keys.clear();
+
+        for _ in 0..count {
+            keys.push(Secret::random(&mut OsRng).into());
+        }
+
+        let mut sequential: RatchetTree = RatchetTree::new(&sequential_memory).expect("Unable to create tree");
+
+        for key in keys.iter() {
+            let branch: RatchetBranch = sequential.insert(key, &scratch).expect("Error inserting key into tree");
+            sequential.commit(&branch, &sequential_memory).expect("Unable to commit branch to tree");
+        }
+
+        let bulk: RatchetTree = RatchetTree::from_leaves(&bulk_memory, &keys).expect("Unable to build tree from leaves");
+
+        assert_eq!(bulk.height(), sequential.height());
+        assert_eq!(bulk.get_next_index(), sequential.get_next_index());
+        assert_eq!(bulk.tree_hash(), sequential.tree_hash());
+        assert_eq!(bulk.get_root(), sequential.get_root());
+        assert!(bulk.get_root().expect("No root").sk.is_some());
+        assert!(bulk.verify(&scratch).is_empty());
+
+        for height in 0..bulk.height() + 1 {
+            assert_eq!(bulk.get_layer_len(height), sequential.get_layer_len(height));
+        }
+    }
+
+    // The pool grows for layers it has no allocator for
+    let small_memory: AllocatorPool = AllocatorPool::new_with_init::<Key>(&root_allocator, 5, 32);
+    let bulk: RatchetTree = RatchetTree::from_leaves(&small_memory, &keys).expect("Unable to build tree in a small pool");
+
+    assert_eq!(small_memory.len(), 4 + bulk.height() + 1);
+}
+
+#[wasm_bindgen_test]
+fn test_tree_commit_batch() {
+    let root_allocator: Bump = AllocatorPool::create_bumpalo::<&Bump>(12);
+    let test_allocator: Bump = AllocatorPool::create_bumpalo::<Key>(8);
+
+    let batch_memory: AllocatorPool = AllocatorPool::new_with_init::<Key>(&root_allocator, 12, 32);
+    let sequential_memory: AllocatorPool = AllocatorPool::new_with_init::<Key>(&root_allocator, 12, 32);
+    let receiver_memory: AllocatorPool = AllocatorPool::new_with_init::<Key>(&root_allocator, 12, 32);
+    let scratch: AllocatorCell = crypto_art::tree::branch_memory(&batch_memory).expect("Unable to get branch memory");
+
+    let mut keys: Vec<Key> = Vec::new_in(&test_allocator);
+    for _ in 0..8 {
+        keys.push(Secret::random(&mut OsRng).into());
+    }
+
+    let mut tree: RatchetTree = RatchetTree::from_leaves(&batch_memory, &keys).expect("Unable to build tree");
+    let mut sequential: RatchetTree = RatchetTree::from_leaves(&sequential_memory, &keys).expect("Unable to build tree");
+
+    // The receiver sits in leaf 8 and holds no secret but its own leaf
+    let public: TreeSnapshot = tree.snapshot(false, &scratch);
+    let mut receiver: RatchetTree = RatchetTree::restore(&receiver_memory, &public).expect("Unable to restore tree");
+    receiver.set(0, 8, keys[7].clone()).expect("Unable to set receiver leaf");
+
+    // Four members update concurrently against the same tree, one of them is removed
+    let mut updates: Vec<(usize, Key)> = Vec::new_in(&test_allocator);
+    for index in [7, 2, 3] {
+        updates.push((index, Secret::random(&mut OsRng).into()));
+    }
+    updates.push((5, Key::default()));
+
+    let mut branches: Vec<RatchetBranch> = Vec::new_in(&scratch);
+    for (index, key) in updates.iter() {
+        branches.push(tree.ratchet(*index, key, &scratch).expect("Unable to ratchet"));
+    }
+
+    let update = tree.commit_batch(&branches, &batch_memory, &scratch).expect("Unable to commit batch");
+
+    // Deterministic leaf order, public keys only
+    let roots: Vec<usize> = Vec::from_iter_in(update.iter().map(|branch| branch.root), &scratch);
+    assert_eq!(roots.as_slice(), &[2, 3, 5, 7]);
+    assert!(update.iter().all(|branch| branch.iter().all(|key| key.sk.is_none())));
+
+    // Same result as committing each update one after another
+    for (index, key) in updates.iter() {
+        let branch: RatchetBranch = sequential.ratchet(*index, key, &scratch).expect("Unable to ratchet");
+        sequential.commit(&branch, &sequential_memory).expect("Unable to commit branch");
+    }
+
+    assert_eq!(tree.get_root(), sequential.get_root());
+    assert_eq!(tree.tree_hash(), sequential.tree_hash());
+    assert_eq!(tree.get_orphans(), &[5]);
+    assert!(tree.verify(&scratch).is_empty());
+
+    // Receivers reproduce the tree from the merged update, and re-derive the secrets the merge recomputed
+    let root: Key = receiver.apply_batch(&update, 8, &receiver_memory, &scratch).expect("Unable to apply merged update").clone();
+    assert_eq!(receiver.epoch(), tree.epoch());
+
+    assert_eq!(receiver.tree_hash(), tree.tree_hash());
+    assert_eq!(Some(&root), tree.get_root());
+    assert!(root.sk.is_some());
+
+    // Which is all they need to move on to the same key schedule as the committer
+    let mut ours: KeySchedule = KeySchedule::new();
+    let mut theirs: KeySchedule = KeySchedule::new();
+
+    ours.advance(tree.get_root().unwrap()).expect("Unable to advance schedule");
+    theirs.advance(&root).expect("Unable to advance schedule");
+    assert_eq!(ours.stage_key(), theirs.stage_key());
+
+    // The same leaf twice in one batch is rejected before anything is written
+    let before: [u8; crypto_art::tree::TREE_HASH_LEN] = tree.tree_hash();
+
+    branches.clear();
+    branches.push(tree.ratchet(1, &Secret::random(&mut OsRng).into(), &scratch).expect("Unable to ratchet"));
+    branches.push(tree.ratchet(1, &Secret::random(&mut OsRng).into(), &scratch).expect("Unable to ratchet"));
+
+    let error: RatchetError = tree.commit_batch(&branches, &batch_memory, &scratch).err().expect("Committed duplicate leaves");
+    assert_eq!(error.cause, RatchetErrorCause::INVALID_BRANCH);
+    assert_eq!(tree.tree_hash(), before);
+}
+
+#[wasm_bindgen_test]
+fn test_tree_commit_batch_grows() {
+    let root_allocator: Bump = AllocatorPool::create_bumpalo::<&Bump>(12);
+    let test_allocator: Bump = AllocatorPool::create_bumpalo::<Key>(8);
+
+    let batch_memory: AllocatorPool = AllocatorPool::new_with_init::<Key>(&root_allocator, 12, 32);
+    let sequential_memory: AllocatorPool = AllocatorPool::new_with_init::<Key>(&root_allocator, 12, 32);
+    let receiver_memory: AllocatorPool = AllocatorPool::new_with_init::<Key>(&root_allocator, 12, 32);
+    let joiner_memory: AllocatorPool = AllocatorPool::new_with_init::<Key>(&root_allocator, 12, 32);
+    let stranger_memory: AllocatorPool = AllocatorPool::new_with_init::<Key>(&root_allocator, 12, 32);
+    let scratch: AllocatorCell = crypto_art::tree::branch_memory(&batch_memory).expect("Unable to get branch memory");
+
+    let mut keys: Vec<Key> = Vec::new_in(&test_allocator);
+    for _ in 0..4 {
+        keys.push(Secret::random(&mut OsRng).into());
+    }
+
+    let mut tree: RatchetTree = RatchetTree::from_leaves(&batch_memory, &keys).expect("Unable to build tree");
+    let mut sequential: RatchetTree = RatchetTree::from_leaves(&sequential_memory, &keys).expect("Unable to build tree");
+
+    let public: TreeSnapshot = tree.snapshot(false, &scratch);
+    let mut receiver: RatchetTree = RatchetTree::restore(&receiver_memory, &public).expect("Unable to restore tree");
+    let mut joiner: RatchetTree = RatchetTree::restore(&joiner_memory, &public).expect("Unable to restore tree");
+    receiver.set(0, 4, keys[3].clone()).expect("Unable to set receiver leaf");
+
+    // Leaf 2 updates while a fifth member joins, growing a full tree by a layer
+    let updated: Key = Secret::random(&mut OsRng).into();
+    let joined: Key = Secret::random(&mut OsRng).into();
+
+    let mut branches: Vec<RatchetBranch> = Vec::new_in(&scratch);
+    branches.push(tree.insert(&joined, &scratch).expect("Unable to insert"));
+    branches.push(tree.ratchet(2, &updated, &scratch).expect("Unable to ratchet"));
+
+    assert_eq!(branches[0].root, 5);
+    assert_eq!(branches[0].len(), 4);
+    assert_eq!(branches[1].len(), 3);
+
+    let update = tree.commit_batch(&branches, &batch_memory, &scratch).expect("Unable to commit growing batch");
+
+    assert_eq!(tree.height(), 3);
+    assert!(update.iter().all(|branch| branch.len() == 4));
+    assert!(tree.verify(&scratch).is_empty());
+
+    for (index, key) in [(2, &updated), (5, &joined)] {
+        let branch: RatchetBranch = sequential.ratchet(index, key, &scratch).expect("Unable to ratchet");
+        sequential.commit(&branch, &sequential_memory).expect("Unable to commit branch");
+    }
+
+    assert_eq!(tree.get_root(), sequential.get_root());
+    assert_eq!(tree.tree_hash(), sequential.tree_hash());
+
+    // An untouched member and the joiner both reach the new root
+    let root: Key = receiver.apply_batch(&update, 4, &receiver_memory, &scratch).expect("Unable to apply growing batch").clone();
+
+    assert_eq!(receiver.tree_hash(), tree.tree_hash());
+    assert_eq!(Some(&root), tree.get_root());
+    assert!(root.sk.is_some());
+
+    // The joiner passes its own branch, leaf secret included, in place of the public copy
+    let mut own: Vec<RatchetBranch> = Vec::new_in(&scratch);
+    for branch in update.iter() {
+        let mut copy: RatchetBranch = RatchetBranch::new(&scratch, branch.root);
+        copy.epoch = branch.epoch;
+
+        for (height, key) in branch.iter().enumerate() {
+            copy.add_node(if branch.root == 5 && height == 0 { joined.clone() } else { key.clone() });
+        }
+
+        own.push(copy);
+    }
+
+    let root: &Key = joiner.apply_batch(&own, 5, &joiner_memory, &scratch).expect("Unable to apply growing batch as the joiner");
+
+    assert_eq!(Some(root), tree.get_root());
+    assert!(root.sk.is_some());
+    assert_eq!(joiner.tree_hash(), tree.tree_hash());
+
+    // Without its leaf secret a member can't follow the batch
+    let mut stranger: RatchetTree = RatchetTree::restore(&stranger_memory, &public).expect("Unable to restore tree");
+    let error: RatchetError = stranger.apply_batch(&update, 3, &stranger_memory, &scratch).err().expect("Applied batch without a leaf secret");
+    assert_eq!(error.cause, RatchetErrorCause::INVALID_INDEX);
+}
+
+#[wasm_bindgen_test]
+fn test_tree_commit_stale_epoch() {
+    let root_allocator: Bump = AllocatorPool::create_bumpalo::<&Bump>(12);
+    let test_allocator: Bump = AllocatorPool::create_bumpalo::<Key>(8);
+
+    let memory: AllocatorPool = AllocatorPool::new_with_init::<Key>(&root_allocator, 12, 32);
+    let scratch: AllocatorCell = crypto_art::tree::branch_memory(&memory).expect("Unable to get branch memory");
+
+    let mut keys: Vec<Key> = Vec::new_in(&test_allocator);
+    for _ in 0..4 {
+        keys.push(Secret::random(&mut OsRng).into());
+    }
+
+    let mut tree: RatchetTree = RatchetTree::from_leaves(&memory, &keys).expect("Unable to build tree");
+    assert_eq!(tree.epoch(), 0);
+
+    // Both branches are built from epoch 0, only the first one to be committed goes through
+    let first: RatchetBranch = tree.ratchet(1, &Secret::random(&mut OsRng).into(), &scratch).expect("Unable to ratchet");
+    let second: RatchetBranch = tree.ratchet(4, &Secret::random(&mut OsRng).into(), &scratch).expect("Unable to ratchet");
+
+    assert_eq!(first.epoch, 0);
+    assert_eq!(second.epoch, 0);
+
+    tree.commit(&first, &memory).expect("Unable to commit first branch");
+    assert_eq!(tree.epoch(), 1);
+
+    // Built for the epoch we just committed, so it's a fork, see test_tree_resolve_fork
+    let before: [u8; crypto_art::tree::TREE_HASH_LEN] = tree.tree_hash();
+    let error: RatchetError = tree.commit(&second, &memory).err().expect("Committed conflicting branch");
+
+    assert_eq!(error.cause, RatchetErrorCause::FORK);
+    assert_eq!(tree.tree_hash(), before);
+    assert_eq!(tree.epoch(), 1);
+
+    // Re-ratcheting against the current tree stamps the new epoch
+    let rebased: RatchetBranch = tree.ratchet(4, &Secret::random(&mut OsRng).into(), &scratch).expect("Unable to ratchet");
+    assert_eq!(rebased.epoch, 1);
+
+    tree.commit(&rebased, &memory).expect("Unable to commit rebased branch");
+    assert_eq!(tree.epoch(), 2);
+
+    // Any older than that and it's stale
+    let error: RatchetError = tree.commit(&second, &memory).err().expect("Committed stale branch");
+    assert_eq!(error.cause, RatchetErrorCause::STALE_EPOCH);
+}
+
+#[wasm_bindgen_test]
+fn test_tree_resolve_fork() {
+    let root_allocator: Bump = AllocatorPool::create_bumpalo::<&Bump>(12);
+    let test_allocator: Bump = AllocatorPool::create_bumpalo::<Key>(8);
+
+    let memory_one: AllocatorPool = AllocatorPool::new_with_init::<Key>(&root_allocator, 12, 32);
+    let memory_two: AllocatorPool = AllocatorPool::new_with_init::<Key>(&root_allocator, 12, 32);
+    let scratch: AllocatorCell = crypto_art::tree::branch_memory(&memory_one).expect("Unable to get branch memory");
+
+    let mut keys: Vec<Key> = Vec::new_in(&test_allocator);
+    for _ in 0..4 {
+        keys.push(Secret::random(&mut OsRng).into());
+    }
+
+    let mut tree_one: RatchetTree = RatchetTree::from_leaves(&memory_one, &keys).expect("Unable to build tree");
+    let mut tree_two: RatchetTree = RatchetTree::from_leaves(&memory_two, &keys).expect("Unable to build tree");
+
+    // Leaf 3 & leaf 2 both update at epoch 0, each side commits its own first
+    let three: RatchetBranch = tree_one.ratchet(3, &Secret::random(&mut OsRng).into(), &scratch).expect("Unable to ratchet");
+    let two: RatchetBranch = tree_two.ratchet(2, &Secret::random(&mut OsRng).into(), &scratch).expect("Unable to ratchet");
+
+    tree_one.commit(&three, &memory_one).expect("Unable to commit leaf 3");
+    tree_two.commit(&two, &memory_two).expect("Unable to commit leaf 2");
+    assert_ne!(tree_one.tree_hash(), tree_two.tree_hash());
+
+    let error: RatchetError = tree_one.commit(&two, &memory_one).err().expect("Committed conflicting branch");
+    assert_eq!(error.cause, RatchetErrorCause::FORK);
+
+    let error: RatchetError = tree_two.commit(&three, &memory_two).err().expect("Committed conflicting branch");
+    assert_eq!(error.cause, RatchetErrorCause::FORK);
+
+    // The lowest leaf wins on both sides
+    let resolution: ForkResolution = tree_one.resolve_fork(&two, None, &memory_one, &scratch).expect("Unable to resolve fork");
+    assert_eq!(resolution, ForkResolution::REPLACED { loser: 3 });
+
+    let resolution: ForkResolution = tree_two.resolve_fork(&three, None, &memory_two, &scratch).expect("Unable to resolve fork");
+    assert_eq!(resolution, ForkResolution::KEPT);
+
+    assert_eq!(tree_one.epoch(), 1);
+    assert_eq!(tree_one.tree_hash(), tree_two.tree_hash());
+    assert!(tree_one.verify(&scratch).is_empty());
+
+    // Re-delivering the winner is just stale, not another fork
+    let error: RatchetError = tree_one.commit(&two, &memory_one).err().expect("Committed branch twice");
+    assert_eq!(error.cause, RatchetErrorCause::STALE_EPOCH);
+
+    // The loser rebases on top of the winner
+    let rebased: RatchetBranch = tree_one.rebase(&three, &scratch).expect("Unable to rebase");
+    assert_eq!(rebased.epoch, 1);
+    assert_eq!(rebased.get_node(0), three.get_node(0));
+
+    tree_one.commit(&rebased, &memory_one).expect("Unable to commit rebased branch");
+    tree_two.commit(&rebased, &memory_two).expect("Unable to commit rebased branch");
+    assert_eq!(tree_one.tree_hash(), tree_two.tree_hash());
+
+    // Two inserts racing for the same new leaf, the loser moves to the next one
+    let insert_one: RatchetBranch = tree_one.insert(&Secret::random(&mut OsRng).into(), &scratch).expect("Unable to insert");
+    let insert_two: RatchetBranch = tree_two.insert(&Secret::random(&mut OsRng).into(), &scratch).expect("Unable to insert");
+
+    assert_eq!(insert_one.root, 5);
+    assert_eq!(insert_two.root, 5);
+
+    tree_one.commit(&insert_one, &memory_one).expect("Unable to commit insert");
+    tree_two.commit(&insert_two, &memory_two).expect("Unable to commit insert");
+
+    let resolution_one: ForkResolution = tree_one.resolve_fork(&insert_two, None, &memory_one, &scratch).expect("Unable to resolve fork");
+    let resolution_two: ForkResolution = tree_two.resolve_fork(&insert_one, None, &memory_two, &scratch).expect("Unable to resolve fork");
+
+    assert_eq!(tree_one.tree_hash(), tree_two.tree_hash());
+
+    let (winner, winner_memory, loser, loser_memory, lost) = match resolution_one {
+        ForkResolution::REPLACED { loser: 5 } => {
+            assert_eq!(resolution_two, ForkResolution::KEPT);
+            (&mut tree_two, &memory_two, &mut tree_one, &memory_one, &insert_one)
+        },
+        _ => {
+            assert_eq!(resolution_one, ForkResolution::KEPT);
+            assert_eq!(resolution_two, ForkResolution::REPLACED { loser: 5 });
+            (&mut tree_one, &memory_one, &mut tree_two, &memory_two, &insert_two)
+        }
+    };
+
+    let rebased: RatchetBranch = loser.rebase(lost, &scratch).expect("Unable to rebase insert");
+    assert_eq!(rebased.root, 6);
+
+    loser.commit(&rebased, loser_memory).expect("Unable to commit rebased insert");
+    winner.commit(&rebased, winner_memory).expect("Unable to commit rebased insert");
+
+    assert_eq!(winner.tree_hash(), loser.tree_hash());
+    assert_eq!(winner.get_next_index(), 7);
+}
+
+#[wasm_bindgen_test]
+fn test_tree_rollback() {
+    let root_allocator: Bump = AllocatorPool::create_bumpalo::<&Bump>(12);
+    let test_allocator: Bump = AllocatorPool::create_bumpalo::<Key>(8);
+
+    let memory: AllocatorPool = AllocatorPool::new_with_init::<Key>(&root_allocator, 12, 32);
+    let scratch: AllocatorCell = crypto_art::tree::branch_memory(&memory).expect("Unable to get branch memory");
+
+    let mut keys: Vec<Key> = Vec::new_in(&test_allocator);
+    for _ in 0..4 {
+        keys.push(Secret::random(&mut OsRng).into());
+    }
+
+    let mut tree: RatchetTree = RatchetTree::from_leaves(&memory, &keys).expect("Unable to build tree");
+    let initial: [u8; 32] = tree.tree_hash();
+
+    let remove: RatchetBranch = tree.remove(2, &scratch).expect("Unable to remove leaf 2");
+    tree.commit(&remove, &memory).expect("Unable to commit removal");
+    assert_eq!(tree.get_next_index(), 2);
+
+    let removed: [u8; 32] = tree.tree_hash();
+
+    // Re-uses the orphaned leaf 2, then grows the tree by a layer
+    for _ in 0..2 {
+        let insert: RatchetBranch = tree.insert(&Secret::random(&mut OsRng).into(), &scratch).expect("Unable to insert");
+        tree.commit(&insert, &memory).expect("Unable to commit insert");
+    }
+
+    assert_eq!(tree.epoch(), 3);
+    assert_eq!(tree.height(), 3);
+    assert_eq!(tree.journal_len(), 3);
+
+    assert_eq!(tree.rollback(2).expect("Unable to roll back"), 1);
+    assert_eq!(tree.tree_hash(), removed);
+    assert_eq!(tree.height(), 2);
+    assert_eq!(tree.get_layer_len(0), 5);
+    assert_eq!(tree.get_next_index(), 2);
+    assert!(tree.verify(&scratch).is_empty());
+
+    tree.rollback(1).expect("Unable to roll back");
+    assert_eq!(tree.tree_hash(), initial);
+    assert_eq!(tree.epoch(), 0);
+    assert_eq!(tree.get_next_index(), 5);
+
+    let error: RatchetError = tree.rollback(1).err().expect("Rolled back past the journal");
+    assert_eq!(error.cause, RatchetErrorCause::JOURNAL_EXHAUSTED);
+
+    // The tree carries on from the rolled back epoch
+    let insert: RatchetBranch = tree.insert(&Secret::random(&mut OsRng).into(), &scratch).expect("Unable to insert");
+    tree.commit(&insert, &memory).expect("Unable to commit after rollback");
+    assert_eq!(tree.epoch(), 1);
+
+    // Only the last `depth` commits are kept
+    tree.set_journal_depth(2);
+
+    for leaf in 1..4 {
+        let update: RatchetBranch = tree.ratchet(leaf, &Secret::random(&mut OsRng).into(), &scratch).expect("Unable to ratchet");
+        tree.commit(&update, &memory).expect("Unable to commit update");
+    }
+
+    assert_eq!(tree.journal_len(), 2);
+
+    let error: RatchetError = tree.rollback(3).err().expect("Rolled back past the journal");
+    assert_eq!(error.cause, RatchetErrorCause::JOURNAL_EXHAUSTED);
+    assert_eq!(tree.epoch(), 4);
+
+    tree.rollback(2).expect("Unable to roll back");
+    assert_eq!(tree.epoch(), 2);
+    assert!(tree.verify(&scratch).is_empty());
+}
+
+#[wasm_bindgen_test]
+fn test_tree_fork_promote() {
+    let root_allocator: Bump = AllocatorPool::create_bumpalo::<&Bump>(12);
+    let test_allocator: Bump = AllocatorPool::create_bumpalo::<Key>(8);
+
+    let memory: AllocatorPool = AllocatorPool::new_with_init::<Key>(&root_allocator, 12, 32);
+    let fork_memory: AllocatorPool = AllocatorPool::new_with_init::<Key>(&root_allocator, 12, 32);
+    let scratch: AllocatorCell = crypto_art::tree::branch_memory(&memory).expect("Unable to get branch memory");
+
+    let mut keys: Vec<Key> = Vec::new_in(&test_allocator);
+    for _ in 0..4 {
+        keys.push(Secret::random(&mut OsRng).into());
+    }
+
+    let mut tree: RatchetTree = RatchetTree::from_leaves(&memory, &keys).expect("Unable to build tree");
+    let initial: [u8; 32] = tree.tree_hash();
+
+    assert_eq!(tree.changes(&scratch).err().expect("Took changes from an unforked tree").cause, RatchetErrorCause::INVALID_BRANCH);
+
+    // Discarded fork, only the layers written to are copied & the parent is never touched
+    {
+        let mut fork: RatchetTree = tree.fork(&fork_memory).expect("Unable to fork tree");
+
+        assert_eq!(fork.tree_hash(), initial);
+        for height in 0..fork.height() + 1 {
+            assert!(fork.is_shared_layer(height));
+        }
+
+        fork.set(0, 1, Secret::random(&mut OsRng).into()).expect("Unable to set leaf");
+        assert!(!fork.is_shared_layer(0));
+        assert!(fork.is_shared_layer(1));
+        assert!(fork.is_shared_layer(2));
+
+        let update: RatchetBranch = fork.ratchet(3, &Secret::random(&mut OsRng).into(), &scratch).expect("Unable to ratchet");
+        fork.commit(&update, &fork_memory).expect("Unable to commit to fork");
+
+        assert_ne!(fork.tree_hash(), initial);
+        assert_eq!(fork.epoch(), 1);
+    }
+
+    assert_eq!(tree.tree_hash(), initial);
+    assert_eq!(tree.epoch(), 0);
+
+    // Promoted fork, growing the tree by a layer
+    let promoted: [u8; 32];
+    let changes: TreeChanges = {
+        let mut fork: RatchetTree = tree.fork(&fork_memory).expect("Unable to fork tree");
+
+        let update: RatchetBranch = fork.ratchet(3, &Secret::random(&mut OsRng).into(), &scratch).expect("Unable to ratchet");
+        fork.commit(&update, &fork_memory).expect("Unable to commit to fork");
+
+        let insert: RatchetBranch = fork.insert(&Secret::random(&mut OsRng).into(), &scratch).expect("Unable to insert");
+        fork.commit(&insert, &fork_memory).expect("Unable to commit to fork");
+
+        promoted = fork.tree_hash();
+        fork.changes(&scratch).expect("Unable to take fork changes")
+    };
+
+    assert_eq!(changes.base, 0);
+    assert_eq!(changes.epoch, 2);
+
+    tree.promote(&changes, &memory).expect("Unable to promote fork");
+    assert_eq!(tree.tree_hash(), promoted);
+    assert_eq!(tree.epoch(), 2);
+    assert_eq!(tree.height(), 3);
+    assert!(tree.verify(&scratch).is_empty());
+
+    let error: RatchetError = tree.promote(&changes, &memory).err().expect("Promoted fork twice");
+    assert_eq!(error.cause, RatchetErrorCause::STALE_EPOCH);
+
+    // A promotion is undone as a whole
+    tree.rollback(1).expect("Unable to roll back promotion");
+    assert_eq!(tree.tree_hash(), initial);
+    assert_eq!(tree.epoch(), 0);
+    assert_eq!(tree.height(), 2);
+}
+
+#[wasm_bindgen_test]
+fn test_tree_shrink() {
+    let root_allocator: Bump = AllocatorPool::create_bumpalo::<&Bump>(12);
+    let test_allocator: Bump = AllocatorPool::create_bumpalo::<Key>(16);
+
+    let memory: AllocatorPool = AllocatorPool::new_with_init::<Key>(&root_allocator, 12, 32);
+    let small_memory: AllocatorPool = AllocatorPool::new_with_init::<Key>(&root_allocator, 12, 32);
+    let scratch: AllocatorCell = crypto_art::tree::branch_memory(&memory).expect("Unable to get branch memory");
+
+    let mut keys: Vec<Key> = Vec::new_in(&test_allocator);
+    for _ in 0..10 {
+        keys.push(Secret::random(&mut OsRng).into());
+    }
+
+    let mut tree: RatchetTree = RatchetTree::from_leaves(&memory, &keys).expect("Unable to build tree");
+    assert_eq!(tree.height(), 4);
+
+    // A removal in the middle never shrinks
+    let remove: RatchetBranch = tree.remove(2, &scratch).expect("Unable to remove leaf 2");
+    tree.commit(&remove, &memory).expect("Unable to commit removal");
+    assert_eq!(tree.get_layer_len(0), 11);
+    assert_eq!(tree.height(), 4);
+
+    let full: [u8; 32] = tree.tree_hash();
+    let epoch: u64 = tree.epoch();
+
+    // Every trailing removal shrinks the tree as part of its own commit, the root is what the commit returned
+    for index in (4..11).rev() {
+        let remove: RatchetBranch = tree.remove(index, &scratch).expect("Unable to remove leaf");
+        let root: Key = tree.commit(&remove, &memory).expect("Unable to commit removal").clone();
+
+        assert_eq!(tree.get_layer_len(0), index);
+        assert_eq!(tree.get_root(), Some(&root));
+    }
+
+    assert_eq!(tree.height(), 2);
+    assert_eq!(tree.get_orphans(), &[2]);
+    assert_eq!(tree.epoch(), epoch + 7);
+    assert!(tree.verify(&scratch).is_empty());
+
+    // Same tree as building the three leaves directly
+    keys.truncate(3);
+    keys[1] = tree.tombstone.clone().unwrap();
+    let expected: RatchetTree = RatchetTree::from_leaves(&small_memory, &keys).expect("Unable to build tree");
+    assert_eq!(tree.tree_hash(), expected.tree_hash());
+
+    // Each shrink is undone with the commit it's part of
+    tree.rollback(7).expect("Unable to roll back");
+    assert_eq!(tree.tree_hash(), full);
+    assert_eq!(tree.height(), 4);
+    assert!(tree.verify(&scratch).is_empty());
+
+    for index in (4..11).rev() {
+        let remove: RatchetBranch = tree.remove(index, &scratch).expect("Unable to remove leaf");
+        tree.commit(&remove, &memory).expect("Unable to commit removal");
+    }
+
+    assert_eq!(tree.height(), 2);
+
+    // Regrows into the dropped layers without allocating them again
+    let top = memory.slot::<Key>(&tree.slot_name("layer.3")).expect("No slot for layer 3");
+    let dropped: AllocatorStats = memory.slot_stats(&top).expect("No stats for layer 3");
+
+    for _ in 0..4 {
+        let insert: RatchetBranch = tree.insert(&Secret::random(&mut OsRng).into(), &scratch).expect("Unable to insert");
+        tree.commit(&insert, &memory).expect("Unable to commit insert");
+    }
+
+    assert_eq!(tree.get_orphans().len(), 0);
+    assert_eq!(tree.get_layer_len(0), 7);
+    assert_eq!(tree.height(), 3);
+    assert_eq!(memory.slot_stats(&top).expect("No stats for layer 3").allocated_bytes, dropped.allocated_bytes);
+    assert!(tree.verify(&scratch).is_empty());
+
+    // Promoting a shrunk fork shrinks the parent the same way
+    let fork_memory: AllocatorPool = AllocatorPool::new_with_init::<Key>(&root_allocator, 12, 32);
+    let shrunk: [u8; 32];
+    let changes: TreeChanges = {
+        let mut fork: RatchetTree = tree.fork(&fork_memory).expect("Unable to fork tree");
+
+        for index in (5..7).rev() {
+            let remove: RatchetBranch = fork.remove(index, &scratch).expect("Unable to remove leaf");
+            fork.commit(&remove, &fork_memory).expect("Unable to commit removal");
+        }
+
+        assert_eq!(fork.height(), 2);
+        shrunk = fork.tree_hash();
+        fork.changes(&scratch).expect("Unable to take fork changes")
+    };
+
+    tree.promote(&changes, &memory).expect("Unable to promote fork");
+    assert_eq!(tree.tree_hash(), shrunk);
+    assert_eq!(tree.height(), 2);
+    assert!(tree.verify(&scratch).is_empty());
+}
+
+#[wasm_bindgen_test]
+fn test_tree_shrink_fork_keeps_parent() {
+    let root_allocator: Bump = AllocatorPool::create_bumpalo::<&Bump>(12);
+    let test_allocator: Bump = AllocatorPool::create_bumpalo::<Key>(8);
+
+    let memory: AllocatorPool = AllocatorPool::new_with_init::<Key>(&root_allocator, 24, 32);
+    let scratch: AllocatorCell = crypto_art::tree::branch_memory(&memory).expect("Unable to get branch memory");
+
+    let mut keys: Vec<Key> = Vec::new_in(&test_allocator);
+    for _ in 0..8 {
+        keys.push(Secret::random(&mut OsRng).into());
+    }
+
+    let tree: RatchetTree = RatchetTree::from_leaves(&memory, &keys).expect("Unable to build tree");
+    let hash: [u8; 32] = tree.tree_hash();
+
+    // A fork sharing our pool gets slots of its own, & shrinks without touching the layers it borrows from us
+    let namespace: String = {
+        let mut fork: RatchetTree = tree.fork(&memory).expect("Unable to fork tree");
+
+        assert_ne!(fork.slot_name(SLOT_ROOT_NODES), tree.slot_name(SLOT_ROOT_NODES));
+
+        for index in (4..9).rev() {
+            let remove: RatchetBranch = fork.remove(index, &scratch).expect("Unable to remove leaf");
+            fork.commit(&remove, &memory).expect("Unable to commit removal");
+        }
+
+        assert_eq!(fork.height(), 2);
+        assert!(fork.verify(&scratch).is_empty());
+        assert!(tree.verify(&scratch).is_empty());
+        fork.slot_name(SLOT_ROOT_NODES)
+    };
+
+    assert_eq!(tree.tree_hash(), hash);
+    assert_eq!(tree.height(), 3);
+    assert!(tree.verify(&scratch).is_empty());
+
+    // Its slots went back to the pool with it, for the next fork to claim
+    let fork: RatchetTree = tree.fork(&memory).expect("Unable to fork tree again");
+    assert_eq!(fork.slot_name(SLOT_ROOT_NODES), namespace);
+}
+
+#[wasm_bindgen_test]
+fn test_tree_orphan_policy() {
+    let root_allocator: Bump = AllocatorPool::create_bumpalo::<&Bump>(12);
+    let test_allocator: Bump = AllocatorPool::create_bumpalo::<Key>(8);
+
+    let memory: AllocatorPool = AllocatorPool::new_with_init::<Key>(&root_allocator, 12, 32);
+    let scratch: AllocatorCell = crypto_art::tree::branch_memory(&memory).expect("Unable to get branch memory");
+
+    let mut keys: Vec<Key> = Vec::new_in(&test_allocator);
+    for _ in 0..8 {
+        keys.push(Secret::random(&mut OsRng).into());
+    }
+
+    let mut tree: RatchetTree = RatchetTree::from_leaves(&memory, &keys).expect("Unable to build tree");
+
+    for index in [7, 2, 5] {
+        let remove: RatchetBranch = tree.remove(index, &scratch).expect("Unable to remove leaf");
+        tree.commit(&remove, &memory).expect("Unable to commit removal");
+    }
+
+    assert_eq!(tree.get_orphans(), &[7, 2, 5]);
+
+    // FIFO by default, in removal order
+    assert_eq!(tree.get_next_index(), 7);
+
+    tree.set_orphan_policy(&LeftmostPolicy);
+    assert_eq!(tree.get_next_index(), 2);
+
+    // Leaves 1-4 hold three members & 5-8 two, so the right half fills first
+    tree.set_orphan_policy(&BalancedPolicy);
+    assert_eq!(tree.get_next_index(), 5);
+
+    let insert: RatchetBranch = tree.insert(&Secret::random(&mut OsRng).into(), &scratch).expect("Unable to insert");
+    assert_eq!(insert.root, 5);
+
+    tree.commit(&insert, &memory).expect("Unable to commit insert");
+    assert_eq!(tree.get_orphans(), &[7, 2]);
+    assert!(tree.verify(&scratch).is_empty());
+
+    // Now both halves hold three members, left wins the tie
+    assert_eq!(tree.get_next_index(), 2);
+
+    tree.set_orphan_policy(&FifoPolicy);
+    assert_eq!(tree.get_next_index(), 7);
+
+    assert_eq!(BalancedPolicy.select(&[], 8), None);
+    assert_eq!(BalancedPolicy.select(&[3, 6], 6), Some(6));
+    assert_eq!(LeftmostPolicy.select(&[4, 3], 4), Some(3));
+}
@@ -0,0 +1,323 @@
+#![cfg(test)]
+#[macro_use]
+extern crate crypto_art;
+
+use wasm_bindgen_test::*;
+
+use crypto_art::log::*;
+
+use crypto_art::{
+    ecdh::Key,
+    ecdh::Secret,
+    mem::AllocatorPool,
+    mem::AllocatorCell,
+    tree::RatchetBranch,
+    tree::RatchetTree,
+    tree::RatchetError,
+    tree::RatchetErrorCause,
+    tree::TREE_HASH_LEN,
+    wire::TreeSnapshot,
+    wire::UpdateKind,
+    wire::UpdateMessage,
+    wire::WireError,
+    wire::COMPRESSED_KEY_LEN
+};
+
+use bumpalo::{
+    Bump,
+    collections::Vec
+};
+
+use rand_core::OsRng;
+
+#[wasm_bindgen_test]
+fn test_update_message_roundtrip() {
+    let root_allocator: Bump = AllocatorPool::create_bumpalo::<&Bump>(12);
+    let memory: AllocatorPool = AllocatorPool::new_with_init::<Key>(&root_allocator, 12, 32);
+    let mut tree: RatchetTree = RatchetTree::new(&memory).expect("Unable to create tree");
+
+    for _ in 0..5 {
+        let key: Key = Secret::random(&mut OsRng).into();
+        let scratch: AllocatorCell = crypto_art::tree::branch_memory(&memory).expect("Unable to get branch memory");
+        let branch: RatchetBranch = tree.insert(&key, &scratch).expect("Error inserting key into tree");
+
+        tree.commit(&branch, &memory).expect("Unable to commit branch to tree");
+    }
+
+    let scratch: AllocatorCell = crypto_art::tree::branch_memory(&memory).expect("Unable to get branch memory");
+    let key: Key = Secret::random(&mut OsRng).into();
+    let branch: RatchetBranch = tree.ratchet(3, &key, &scratch).expect("Unable to ratchet tree");
+
+    assert!(branch.iter().all(|key| key.sk.is_some()));
+
+    let message: UpdateMessage = UpdateMessage::new(UpdateKind::UPDATE, 3, tree.tree_hash(), branch);
+    let encoded: Vec<u8> = message.encode(&scratch).expect("Unable to encode update message");
+
+    // Public keys only, no room for any secrets
+    assert!(encoded.len() <= message.encoded_len());
+    assert!(encoded.len() < (COMPRESSED_KEY_LEN + 2) * message.branch.len() + TREE_HASH_LEN + 11);
+
+    let decoded: UpdateMessage = UpdateMessage::decode(&encoded, &scratch).expect("Unable to decode update message");
+
+    assert_eq!(decoded.kind, UpdateKind::UPDATE);
+    assert_eq!(decoded.sender, 3);
+    assert_eq!(decoded.branch.epoch, tree.epoch());
+    assert_eq!(decoded.tree_hash, tree.tree_hash());
+    assert!(decoded.signature.is_none());
+    assert_eq!(decoded.branch.root, 3);
+    assert_eq!(decoded.branch.len(), message.branch.len());
+
+    for (a, b) in decoded.branch.iter().zip(message.branch.iter()) {
+        assert_eq!(a, b);
+        assert!(a.sk.is_none());
+    }
+}
+
+#[wasm_bindgen_test]
+fn test_update_message_remove_roundtrip() {
+    let root_allocator: Bump = AllocatorPool::create_bumpalo::<&Bump>(12);
+    let memory: AllocatorPool = AllocatorPool::new_with_init::<Key>(&root_allocator, 12, 32);
+    let mut tree: RatchetTree = RatchetTree::new(&memory).expect("Unable to create tree");
+
+    for _ in 0..4 {
+        let key: Key = Secret::random(&mut OsRng).into();
+        let scratch: AllocatorCell = crypto_art::tree::branch_memory(&memory).expect("Unable to get branch memory");
+        let branch: RatchetBranch = tree.insert(&key, &scratch).expect("Error inserting key into tree");
+
+        tree.commit(&branch, &memory).expect("Unable to commit branch to tree");
+    }
+
+    let scratch: AllocatorCell = crypto_art::tree::branch_memory(&memory).expect("Unable to get branch memory");
+    let branch: RatchetBranch = tree.remove(2, &scratch).expect("Unable to compute remove for tree");
+    let message: UpdateMessage = UpdateMessage::new(UpdateKind::REMOVE, 1, tree.tree_hash(), branch);
+
+    let encoded: Vec<u8> = message.encode(&scratch).expect("Unable to encode update message");
+    let decoded: UpdateMessage = UpdateMessage::decode(&encoded, &scratch).expect("Unable to decode update message");
+
+    assert_eq!(decoded.kind, UpdateKind::REMOVE);
+    assert_eq!(decoded.sender, 1);
+    assert_eq!(decoded.branch.get_node(0), tree.tombstone.as_ref());
+
+    tree.commit(&decoded.branch, &memory).expect("Unable to commit decoded remove branch");
+    assert_eq!(tree.get(0, 2), tree.tombstone.as_ref());
+}
+
+#[wasm_bindgen_test]
+fn test_update_message_decode_invalid() {
+    let root_allocator: Bump = AllocatorPool::create_bumpalo::<&Bump>(4);
+    let memory: AllocatorPool = AllocatorPool::new_with_init::<Key>(&root_allocator, 5, 32);
+    let tree: RatchetTree = RatchetTree::new(&memory).expect("Unable to create tree");
+
+    let scratch: AllocatorCell = crypto_art::tree::branch_memory(&memory).expect("Unable to get branch memory");
+    let key: Key = Secret::random(&mut OsRng).into();
+    let branch: RatchetBranch = tree.insert(&key, &scratch).expect("Error inserting key into tree");
+
+    let message: UpdateMessage = UpdateMessage::new(UpdateKind::INSERT, 1, tree.tree_hash(), branch);
+    let mut encoded: Vec<u8> = message.encode(&scratch).expect("Unable to encode update message");
+
+    // Truncated messages are rejected
+    let truncated: &[u8] = &encoded[..encoded.len() - 1];
+    assert!(UpdateMessage::decode(truncated, &scratch).is_err());
+
+    // Unknown versions are rejected, version is the first element of the array
+    encoded[1] = crypto_art::wire::WIRE_VERSION + 1;
+    let error: WireError = UpdateMessage::decode(&encoded, &scratch).err().expect("Decoded unknown version");
+    assert_eq!(error.reason, "Unable to deserialize update message");
+}
+
+fn assert_trees_equal(a: &RatchetTree, b: &RatchetTree, secrets: bool) {
+    assert_eq!(a.height(), b.height());
+    assert_eq!(a.get_next_index(), b.get_next_index());
+    assert_eq!(a.get_orphans(), b.get_orphans());
+    assert_eq!(a.tombstone, b.tombstone);
+    assert_eq!(a.tree_hash(), b.tree_hash());
+    assert_eq!(a.epoch(), b.epoch());
+
+    for height in 0..a.height() + 1 {
+        let layer_a = a.get_layer(height).expect("No layer found in tree a");
+        let layer_b = b.get_layer(height).expect("No layer found in tree b");
+
+        assert_eq!(layer_a.len(), layer_b.len());
+
+        for (key_a, key_b) in layer_a.iter().zip(layer_b.iter()) {
+            assert_eq!(key_a, key_b);
+
+            if secrets {
+                assert_eq!(key_a.sk.is_some(), key_b.sk.is_some());
+            } else {
+                assert!(key_b.sk.is_none());
+            }
+        }
+    }
+}
+
+#[wasm_bindgen_test]
+fn test_tree_snapshot_roundtrip() {
+    let root_allocator: Bump = AllocatorPool::create_bumpalo::<&Bump>(12);
+    let memory: AllocatorPool = AllocatorPool::new_with_init::<Key>(&root_allocator, 12, 32);
+    let mut tree: RatchetTree = RatchetTree::new(&memory).expect("Unable to create tree");
+
+    for _ in 0..11 {
+        let key: Key = Secret::random(&mut OsRng).into();
+        let scratch: AllocatorCell = crypto_art::tree::branch_memory(&memory).expect("Unable to get branch memory");
+        let branch: RatchetBranch = tree.insert(&key, &scratch).expect("Error inserting key into tree");
+
+        tree.commit(&branch, &memory).expect("Unable to commit branch to tree");
+    }
+
+    let scratch: AllocatorCell = crypto_art::tree::branch_memory(&memory).expect("Unable to get branch memory");
+
+    for index in [4, 9] {
+        let branch: RatchetBranch = tree.remove(index, &scratch).expect("Unable to compute remove for tree");
+        tree.commit(&branch, &memory).expect("Unable to commit remove branch to tree");
+    }
+
+    // Secrets included
+    let encoded: Vec<u8> = tree.snapshot(true, &scratch).encode(&scratch).expect("Unable to encode snapshot");
+    let decoded: TreeSnapshot = TreeSnapshot::decode(&encoded, &scratch).expect("Unable to decode snapshot");
+
+    let restored_memory: AllocatorPool = AllocatorPool::new_with_init::<Key>(&root_allocator, 12, 32);
+    let mut restored: RatchetTree = RatchetTree::restore(&restored_memory, &decoded).expect("Unable to restore snapshot");
+
+    assert_trees_equal(&tree, &restored, true);
+
+    // Restored tree keeps working, orphaned slot 4 is re-used first
+    let key: Key = Secret::random(&mut OsRng).into();
+    let branch: RatchetBranch = tree.insert(&key, &scratch).expect("Error inserting key into tree");
+    let restored_scratch: AllocatorCell = crypto_art::tree::branch_memory(&restored_memory).expect("Unable to get branch memory");
+    let restored_branch: RatchetBranch = restored.insert(&key, &restored_scratch).expect("Error inserting key into restored tree");
+
+    assert_eq!(branch.root, 4);
+    assert_eq!(restored_branch.root, 4);
+
+    let root: Key = tree.commit(&branch, &memory).expect("Unable to commit branch to tree").clone();
+    let restored_root: &Key = restored.commit(&restored_branch, &restored_memory).expect("Unable to commit branch to restored tree");
+
+    assert_eq!(&root, restored_root);
+    assert!(restored_root.sk.is_some());
+
+    // Secrets excluded
+    let public: Vec<u8> = tree.snapshot(false, &scratch).encode(&scratch).expect("Unable to encode snapshot");
+    let decoded: TreeSnapshot = TreeSnapshot::decode(&public, &scratch).expect("Unable to decode snapshot");
+
+    assert!(public.len() < encoded.len());
+
+    let public_memory: AllocatorPool = AllocatorPool::new_with_init::<Key>(&root_allocator, 12, 32);
+    let public_tree: RatchetTree = RatchetTree::restore(&public_memory, &decoded).expect("Unable to restore snapshot");
+
+    assert_trees_equal(&tree, &public_tree, false);
+}
+
+#[wasm_bindgen_test]
+fn test_tree_snapshot_restore_rejects_inconsistent() {
+    let root_allocator: Bump = AllocatorPool::create_bumpalo::<&Bump>(12);
+    let memory: AllocatorPool = AllocatorPool::new_with_init::<Key>(&root_allocator, 12, 32);
+    let mut tree: RatchetTree = RatchetTree::new(&memory).expect("Unable to create tree");
+    let scratch: AllocatorCell = crypto_art::tree::branch_memory(&memory).expect("Unable to get branch memory");
+
+    for _ in 0..5 {
+        let branch: RatchetBranch = tree.insert(&Secret::random(&mut OsRng).into(), &scratch).expect("Error inserting key into tree");
+        tree.commit(&branch, &memory).expect("Unable to commit branch to tree");
+    }
+
+    let restored_memory: AllocatorPool = AllocatorPool::new_with_init::<Key>(&root_allocator, 12, 32);
+
+    // A layer that doesn't fit the one below it
+    let mut snapshot: TreeSnapshot = tree.snapshot(true, &scratch);
+    snapshot.nodes[1].truncate(2);
+
+    let error: RatchetError = RatchetTree::restore(&restored_memory, &snapshot).err().expect("Restored a misshapen snapshot");
+    assert_eq!(error.cause, RatchetErrorCause::INVALID_HEIGHT);
+
+    // Layers missing off the top
+    let mut snapshot: TreeSnapshot = tree.snapshot(true, &scratch);
+    snapshot.nodes.truncate(2);
+
+    let error: RatchetError = RatchetTree::restore(&restored_memory, &snapshot).err().expect("Restored a snapshot without its root");
+    assert_eq!(error.cause, RatchetErrorCause::INVALID_HEIGHT);
+
+    // An orphan that is still a member's leaf
+    let mut snapshot: TreeSnapshot = tree.snapshot(true, &scratch);
+    snapshot.orphans.push(2);
+
+    let error: RatchetError = RatchetTree::restore(&restored_memory, &snapshot).err().expect("Restored a live leaf as an orphan");
+    assert_eq!((error.cause, error.index), (RatchetErrorCause::INCONSISTENT_NODE, 2));
+
+    // A parent that doesn't follow from its children
+    let mut snapshot: TreeSnapshot = tree.snapshot(true, &scratch);
+    snapshot.nodes[1][1] = Secret::random(&mut OsRng).into();
+
+    let error: RatchetError = RatchetTree::restore(&restored_memory, &snapshot).err().expect("Restored an inconsistent node");
+    assert_eq!((error.cause, error.height, error.index), (RatchetErrorCause::INCONSISTENT_NODE, 1, 1));
+
+    // Nothing rejected is left behind in the pool
+    let snapshot: TreeSnapshot = tree.snapshot(true, &scratch);
+    let restored: RatchetTree = RatchetTree::restore(&restored_memory, &snapshot).expect("Unable to restore snapshot");
+
+    assert_trees_equal(&tree, &restored, true);
+}
+
+#[wasm_bindgen_test]
+fn test_tree_snapshot_restore_grows_pool() {
+    let root_allocator: Bump = AllocatorPool::create_bumpalo::<&Bump>(12);
+    let memory: AllocatorPool = AllocatorPool::new_with_init::<Key>(&root_allocator, 12, 32);
+    let mut tree: RatchetTree = RatchetTree::new(&memory).expect("Unable to create tree");
+
+    for _ in 0..8 {
+        let key: Key = Secret::random(&mut OsRng).into();
+        let scratch: AllocatorCell = crypto_art::tree::branch_memory(&memory).expect("Unable to get branch memory");
+        let branch: RatchetBranch = tree.insert(&key, &scratch).expect("Error inserting key into tree");
+
+        tree.commit(&branch, &memory).expect("Unable to commit branch to tree");
+    }
+
+    let scratch: AllocatorCell = crypto_art::tree::branch_memory(&memory).expect("Unable to get branch memory");
+    let snapshot: TreeSnapshot = tree.snapshot(false, &scratch);
+
+    // Allocators for the first layer only, restoring grows the pool for the rest
+    let small_memory: AllocatorPool = AllocatorPool::new_with_init::<Key>(&root_allocator, 5, 32);
+    let restored: RatchetTree = RatchetTree::restore(&small_memory, &snapshot).expect("Unable to restore into a small pool");
+
+    assert_eq!(small_memory.len(), 8);
+    assert_eq!(restored.tree_hash(), tree.tree_hash());
+}
+
+#[wasm_bindgen_test]
+fn test_update_message_decode_oversized() {
+    let root_allocator: Bump = AllocatorPool::create_bumpalo::<&Bump>(4);
+    let memory: AllocatorPool = AllocatorPool::new_with_init::<Key>(&root_allocator, 5, 32);
+    let scratch: AllocatorCell = crypto_art::tree::branch_memory(&memory).expect("Unable to get branch memory");
+
+    // Paths longer than the tallest tree are rejected before they're buffered
+    let key: Key = Secret::random(&mut OsRng).into();
+    let mut branch: RatchetBranch = RatchetBranch::new(&scratch, 1);
+
+    for _ in 0..crypto_art::tree::MAX_TREE_HEIGHT + 2 {
+        branch.add_node(key.clone());
+    }
+
+    let message: UpdateMessage = UpdateMessage::new(UpdateKind::UPDATE, 1, [0; TREE_HASH_LEN], branch);
+    let encoded: Vec<u8> = message.encode(&scratch).expect("Unable to encode update message");
+
+    assert!(UpdateMessage::decode(&encoded, &scratch).is_err());
+
+    // As are leaf indexes that would be truncated on 32 bit targets
+    if usize::BITS < u64::BITS {
+        let mut branch: RatchetBranch = RatchetBranch::new(&scratch, 1);
+        branch.add_node(key.clone());
+
+        let message: UpdateMessage = UpdateMessage::new(UpdateKind::UPDATE, 1, [0; TREE_HASH_LEN], branch);
+        let mut encoded: Vec<u8> = message.encode(&scratch).expect("Unable to encode update message");
+
+        // Root index is the fourth element, a single byte CBOR uint, widen it to u64::MAX
+        let position: usize = 4;
+        assert_eq!(encoded[position], 1);
+        encoded[position] = 0x1b;
+
+        for byte in [0xff; 8].iter().rev() {
+            encoded.insert(position + 1, *byte);
+        }
+
+        assert!(UpdateMessage::decode(&encoded, &scratch).is_err());
+    }
+}